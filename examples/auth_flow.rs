@@ -31,7 +31,7 @@ async fn main() -> anyhow::Result<()> {
     let mut builder =
         UserTokenBuilder::new(client_id, client_secret, redirect_url).force_verify(true);
 
-    let (url, _) = builder.generate_url();
+    let url = builder.generate_url();
 
     println!("Go to this page: {}", url);
 
@@ -45,7 +45,8 @@ async fn main() -> anyhow::Result<()> {
 
     match (map.get("state"), map.get("code")) {
         (Some(state), Some(code)) => {
-            let token = builder.get_user_token(&reqwest, state, code).await?;
+            anyhow::ensure!(builder.csrf_is_valid(state), "state mismatched");
+            let token = builder.get_user_token(&reqwest, code).await?;
             println!("Got token: {:?}", token);
         }
         _ => match (map.get("error"), map.get("error_description")) {
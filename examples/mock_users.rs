@@ -0,0 +1,54 @@
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let _ = dotenv::dotenv(); // Eat error
+    let mut args = std::env::args().skip(1);
+
+    let reqwest = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+
+    std::env::var("TWITCH_OAUTH2_URL")
+        .ok()
+        .or_else(|| args.next())
+        .map(|t| std::env::set_var("TWITCH_OAUTH2_URL", &t))
+        .expect("Please set env: TWITCH_OAUTH2_URL or pass url as first argument");
+
+    let client_id = std::env::var("MOCK_CLIENT_ID")
+        .ok()
+        .or_else(|| args.next())
+        .map(twitch_oauth2::ClientId::new)
+        .expect("Please set env: MOCK_CLIENT_ID or pass client id as an argument");
+
+    let client_secret = std::env::var("MOCK_CLIENT_SECRET")
+        .ok()
+        .or_else(|| args.next())
+        .map(twitch_oauth2::ClientSecret::new)
+        .expect("Please set env: MOCK_CLIENT_SECRET or pass client secret as an argument");
+
+    let user_ids: Vec<String> = std::env::var("MOCK_USER_IDS")
+        .ok()
+        .or_else(|| args.next())
+        .expect("Please set env: MOCK_USER_IDS or pass comma separated user ids as an argument")
+        .split(',')
+        .map(|s| s.trim().to_owned())
+        .collect();
+
+    let mut tokens = vec![];
+    for user_id in user_ids {
+        let token = twitch_oauth2::UserToken::mock_token(
+            &reqwest,
+            client_id.clone(),
+            client_secret.clone(),
+            user_id,
+            vec![],
+        )
+        .await?;
+        tokens.push(token.access_token);
+    }
+
+    for validated in twitch_oauth2::tokens::validate_tokens(&reqwest, &tokens, 8).await {
+        let validated = validated?;
+        println!("validated: {:?} ({:?})", validated.login, validated.user_id);
+    }
+    Ok(())
+}
@@ -24,6 +24,145 @@ pub trait Client: Sync + Send {
     ) -> BoxedFuture<'_, Result<http::Response<Vec<u8>>, <Self as Client>::Error>>;
 }
 
+type RequestHookFn = dyn Fn(&mut http::Request<Vec<u8>>) + Send + Sync;
+
+/// A hook called with every request before it's sent, see [`UserTokenBuilder::on_request`](crate::tokens::UserTokenBuilder::on_request)
+#[derive(Clone)]
+pub(crate) struct RequestHook {
+    hook: std::sync::Arc<RequestHookFn>,
+    redact: bool,
+}
+
+impl RequestHook {
+    pub(crate) fn new(
+        hook: impl Fn(&mut http::Request<Vec<u8>>) + Send + Sync + 'static,
+        redact: bool,
+    ) -> Self {
+        Self {
+            hook: std::sync::Arc::new(hook),
+            redact,
+        }
+    }
+
+    /// Call the hook, when redacting only changes to the headers are kept
+    fn call(&self, request: &mut http::Request<Vec<u8>>) {
+        if !self.redact {
+            return (self.hook)(request);
+        }
+        let mut redacted = crate::redact_request(request);
+        (self.hook)(&mut redacted);
+        let mut headers = std::mem::take(redacted.headers_mut());
+        let auth = http::header::AUTHORIZATION;
+        // put the secret back if the hook left the redacted authorization alone
+        if headers.contains_key(&auth) && headers.get_all(&auth).iter().all(|v| v == "[redacted]") {
+            headers.remove(&auth);
+            for value in request.headers().get_all(&auth) {
+                headers.append(&auth, value.clone());
+            }
+        }
+        *request.headers_mut() = headers;
+    }
+}
+
+/// Sends token requests somewhere else than twitch, for example to an internal token broker.
+///
+/// Requests are made for twitch, and then rewritten with [`TokenExchanger::request`] before they are sent,
+/// responses are rewritten with [`TokenExchanger::response`] into what twitch would have responded with before they are parsed.
+///
+/// See [`UserTokenBuilder::token_exchanger`](crate::tokens::UserTokenBuilder::token_exchanger) and [`UserToken::set_token_exchanger`](crate::UserToken::set_token_exchanger)
+///
+/// # Examples
+///
+/// ```rust
+/// use twitch_oauth2::client::TokenExchanger;
+///
+/// struct Broker;
+///
+/// impl TokenExchanger for Broker {
+///     fn request(&self, mut request: http::Request<Vec<u8>>) -> http::Request<Vec<u8>> {
+///         if request.uri().path() == "/oauth2/token" {
+///             let query = request.uri().query().map(|q| format!("?{q}")).unwrap_or_default();
+///             *request.uri_mut() = format!("https://broker.internal/twitch/token{query}")
+///                 .parse()
+///                 .unwrap();
+///         }
+///         request
+///     }
+/// }
+/// ```
+pub trait TokenExchanger: Send + Sync {
+    /// Rewrite a request meant for twitch into the request to send
+    fn request(&self, request: http::Request<Vec<u8>>) -> http::Request<Vec<u8>>;
+
+    /// Rewrite the response to a request into the response twitch would have sent
+    fn response(&self, response: http::Response<Vec<u8>>) -> http::Response<Vec<u8>> { response }
+}
+
+/// The default [`TokenExchanger`], sending requests to twitch directly
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DirectExchanger;
+
+impl TokenExchanger for DirectExchanger {
+    fn request(&self, request: http::Request<Vec<u8>>) -> http::Request<Vec<u8>> { request }
+}
+
+/// A [`Client`] that calls a [`RequestHook`] before sending the request with the wrapped client
+pub(crate) struct HookedClient<'a, C> {
+    client: &'a C,
+    hook: Option<&'a RequestHook>,
+    fresh_connection: bool,
+    exchanger: Option<&'a dyn TokenExchanger>,
+}
+
+impl<'a, C> HookedClient<'a, C> {
+    pub(crate) fn new(client: &'a C, hook: Option<&'a RequestHook>) -> Self {
+        Self {
+            client,
+            hook,
+            fresh_connection: false,
+            exchanger: None,
+        }
+    }
+
+    /// Send requests through a [`TokenExchanger`]
+    pub(crate) fn exchanger(mut self, exchanger: Option<&'a dyn TokenExchanger>) -> Self {
+        self.exchanger = exchanger;
+        self
+    }
+
+    /// Add `Connection: close` to every request, see [`UserTokenBuilder::force_fresh_connection`](crate::tokens::UserTokenBuilder::force_fresh_connection)
+    pub(crate) fn fresh_connection(mut self, fresh_connection: bool) -> Self {
+        self.fresh_connection = fresh_connection;
+        self
+    }
+}
+
+impl<C: Client> Client for HookedClient<'_, C> {
+    type Error = C::Error;
+
+    fn req(
+        &self,
+        mut request: http::Request<Vec<u8>>,
+    ) -> BoxedFuture<'_, Result<http::Response<Vec<u8>>, Self::Error>> {
+        if self.fresh_connection {
+            request.headers_mut().insert(
+                http::header::CONNECTION,
+                http::HeaderValue::from_static("close"),
+            );
+        }
+        if let Some(hook) = self.hook {
+            hook.call(&mut request);
+        }
+        match self.exchanger {
+            Some(exchanger) => {
+                let response = self.client.req(exchanger.request(request));
+                Box::pin(async move { response.await.map(|r| exchanger.response(r)) })
+            }
+            None => self.client.req(request),
+        }
+    }
+}
+
 #[doc(hidden)]
 #[derive(Debug, thiserror::Error, Clone)]
 #[error("this client does not do anything, only used for documentation test that only checks code integrity")]
@@ -142,7 +281,7 @@ impl Client for SurfClient {
                 })
                 .collect::<Result<_, SurfError>>()?;
 
-            let _ = std::mem::replace(&mut result.headers_mut(), Some(&mut response_headers));
+            let _ = result.headers_mut().replace(&mut response_headers);
             let result = if let Some(v) = response.version() {
                 result.version(match v {
                     surf::http::Version::Http0_9 => http::Version::HTTP_09,
@@ -162,3 +301,46 @@ impl Client for SurfClient {
         })
     }
 }
+
+/// A client that answers requests with canned responses, in order.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct MockClient {
+    responses: std::sync::Mutex<std::collections::VecDeque<http::Response<Vec<u8>>>>,
+    /// Requests received by this client
+    pub(crate) requests: std::sync::Mutex<Vec<http::Request<Vec<u8>>>>,
+}
+
+#[cfg(test)]
+impl MockClient {
+    /// Add a json response with the given status
+    pub(crate) fn respond(self, status: u16, body: &str) -> Self {
+        self.respond_with(
+            http::Response::builder()
+                .status(status)
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .body(body.as_bytes().to_vec())
+                .unwrap(),
+        )
+    }
+
+    /// Add a response
+    pub(crate) fn respond_with(self, response: http::Response<Vec<u8>>) -> Self {
+        self.responses.lock().unwrap().push_back(response);
+        self
+    }
+}
+
+#[cfg(test)]
+impl Client for MockClient {
+    type Error = DummyClient;
+
+    fn req(
+        &self,
+        request: http::Request<Vec<u8>>,
+    ) -> BoxedFuture<'_, Result<http::Response<Vec<u8>>, Self::Error>> {
+        self.requests.lock().unwrap().push(request);
+        let response = self.responses.lock().unwrap().pop_front();
+        Box::pin(async move { response.ok_or(DummyClient) })
+    }
+}
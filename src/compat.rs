@@ -0,0 +1,176 @@
+//! Conversions between the [`http`] 0.2 types used by this crate and [`http` 1.0](http1) types.
+//!
+//! Requests made with this crate, like [`AccessTokenRef::validate_token_request`](crate::AccessTokenRef::validate_token_request),
+//! can be converted to `http` 1.0 to send them with a client using that version,
+//! and the responses converted back to parse them with e.g [`ValidatedToken::from_response`](crate::ValidatedToken::from_response).
+//!
+//! Extensions are not carried over in conversions.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use twitch_oauth2::{compat, AccessToken};
+//! let request = AccessToken::from("token").validate_token_request();
+//! let request: http1::Request<Vec<u8>> = compat::request_to_http1(request);
+//! // send the request with your favorite http client
+//! # let response = http1::Response::builder().status(401).body(vec![]).unwrap();
+//! let response: http::Response<Vec<u8>> = compat::response_from_http1(response);
+//! ```
+
+/// Convert a `http` 0.2 request into a `http` 1.0 request
+pub fn request_to_http1<B>(request: http::Request<B>) -> http1::Request<B> {
+    let (parts, body) = request.into_parts();
+    let mut request = http1::Request::new(body);
+    *request.method_mut() = http1::Method::from_bytes(parts.method.as_str().as_bytes())
+        .expect("method should be valid in both http versions");
+    *request.uri_mut() = parts
+        .uri
+        .to_string()
+        .parse()
+        .expect("uri should be valid in both http versions");
+    *request.version_mut() = version_to_http1(parts.version);
+    *request.headers_mut() = headers_to_http1(parts.headers);
+    request
+}
+
+/// Convert a `http` 1.0 request into a `http` 0.2 request
+pub fn request_from_http1<B>(request: http1::Request<B>) -> http::Request<B> {
+    let (parts, body) = request.into_parts();
+    let mut request = http::Request::new(body);
+    *request.method_mut() = http::Method::from_bytes(parts.method.as_str().as_bytes())
+        .expect("method should be valid in both http versions");
+    *request.uri_mut() = parts
+        .uri
+        .to_string()
+        .parse()
+        .expect("uri should be valid in both http versions");
+    *request.version_mut() = version_from_http1(parts.version);
+    *request.headers_mut() = headers_from_http1(parts.headers);
+    request
+}
+
+/// Convert a `http` 0.2 response into a `http` 1.0 response
+pub fn response_to_http1<B>(response: http::Response<B>) -> http1::Response<B> {
+    let (parts, body) = response.into_parts();
+    let mut response = http1::Response::new(body);
+    *response.status_mut() = http1::StatusCode::from_u16(parts.status.as_u16())
+        .expect("status code should be valid in both http versions");
+    *response.version_mut() = version_to_http1(parts.version);
+    *response.headers_mut() = headers_to_http1(parts.headers);
+    response
+}
+
+/// Convert a `http` 1.0 response into a `http` 0.2 response
+pub fn response_from_http1<B>(response: http1::Response<B>) -> http::Response<B> {
+    let (parts, body) = response.into_parts();
+    let mut response = http::Response::new(body);
+    *response.status_mut() = http::StatusCode::from_u16(parts.status.as_u16())
+        .expect("status code should be valid in both http versions");
+    *response.version_mut() = version_from_http1(parts.version);
+    *response.headers_mut() = headers_from_http1(parts.headers);
+    response
+}
+
+fn version_to_http1(version: http::Version) -> http1::Version {
+    match version {
+        http::Version::HTTP_09 => http1::Version::HTTP_09,
+        http::Version::HTTP_10 => http1::Version::HTTP_10,
+        http::Version::HTTP_2 => http1::Version::HTTP_2,
+        http::Version::HTTP_3 => http1::Version::HTTP_3,
+        _ => http1::Version::HTTP_11,
+    }
+}
+
+fn version_from_http1(version: http1::Version) -> http::Version {
+    match version {
+        http1::Version::HTTP_09 => http::Version::HTTP_09,
+        http1::Version::HTTP_10 => http::Version::HTTP_10,
+        http1::Version::HTTP_2 => http::Version::HTTP_2,
+        http1::Version::HTTP_3 => http::Version::HTTP_3,
+        _ => http::Version::HTTP_11,
+    }
+}
+
+fn headers_to_http1(headers: http::HeaderMap) -> http1::HeaderMap {
+    let mut converted = http1::HeaderMap::with_capacity(headers.len());
+    for (name, value) in &headers {
+        let mut new_value = http1::HeaderValue::from_bytes(value.as_bytes())
+            .expect("header value should be valid in both http versions");
+        new_value.set_sensitive(value.is_sensitive());
+        converted.append(
+            http1::HeaderName::from_bytes(name.as_str().as_bytes())
+                .expect("header name should be valid in both http versions"),
+            new_value,
+        );
+    }
+    converted
+}
+
+fn headers_from_http1(headers: http1::HeaderMap) -> http::HeaderMap {
+    let mut converted = http::HeaderMap::with_capacity(headers.len());
+    for (name, value) in &headers {
+        let mut new_value = http::HeaderValue::from_bytes(value.as_bytes())
+            .expect("header value should be valid in both http versions");
+        new_value.set_sensitive(value.is_sensitive());
+        converted.append(
+            http::header::HeaderName::from_bytes(name.as_str().as_bytes())
+                .expect("header name should be valid in both http versions"),
+            new_value,
+        );
+    }
+    converted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_roundtrip() {
+        let request = crate::AccessToken::from("token").revoke_token_request(&"clientid".into());
+        let uri = request.uri().clone();
+        let converted = request_to_http1(request);
+        assert_eq!(converted.method(), http1::Method::POST);
+        assert_eq!(converted.uri().to_string(), uri.to_string());
+
+        let request = http::Request::get("https://id.twitch.tv/oauth2/validate")
+            .header(http::header::AUTHORIZATION, "OAuth token")
+            .header("x-multi", "a")
+            .header("x-multi", "b")
+            .body(vec![1, 2, 3])
+            .unwrap();
+        let converted = request_to_http1(request);
+        assert_eq!(
+            converted.headers()[http1::header::AUTHORIZATION],
+            "OAuth token"
+        );
+        assert_eq!(converted.headers().get_all("x-multi").iter().count(), 2);
+        let back = request_from_http1(converted);
+        assert_eq!(back.method(), http::Method::GET);
+        assert_eq!(back.uri(), "https://id.twitch.tv/oauth2/validate");
+        assert_eq!(back.headers()[http::header::AUTHORIZATION], "OAuth token");
+        assert_eq!(back.headers().get_all("x-multi").iter().count(), 2);
+        assert_eq!(back.body(), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn response_roundtrip() {
+        let response = http1::Response::builder()
+            .status(401)
+            .header(http1::header::CONTENT_TYPE, "application/json")
+            .body(br#"{"status":401,"message":"invalid access token"}"#.to_vec())
+            .unwrap();
+        let converted = response_from_http1(response);
+        assert_eq!(converted.status(), http::StatusCode::UNAUTHORIZED);
+        assert!(matches!(
+            crate::ValidatedToken::from_response(&converted),
+            Err(crate::tokens::errors::ValidationError::NotAuthorized)
+        ));
+        let back = response_to_http1(converted);
+        assert_eq!(back.status(), http1::StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            back.headers()[http1::header::CONTENT_TYPE],
+            "application/json"
+        );
+    }
+}
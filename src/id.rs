@@ -10,7 +10,11 @@ use std::time::Duration;
 ///
 /// * [`UserTokenBuilder::get_user_token_request`](crate::tokens::UserTokenBuilder::get_user_token_request)
 /// * [`AppAccessToken::::get_app_access_token_request`](crate::tokens::AppAccessToken::get_app_access_token_request)
+///
+/// Only a missing or malformed `access_token` fails deserialization, other malformed fields are left as `None`
+/// and recorded in [`TwitchTokenResponse::warnings`].
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(try_from = "serde_json::Value")]
 pub struct TwitchTokenResponse {
     /// Access token
     pub access_token: AccessToken,
@@ -21,9 +25,98 @@ pub struct TwitchTokenResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub refresh_token: Option<crate::RefreshToken>,
     /// Scopes attached to token
-    #[serde(rename = "scope", deserialize_with = "scope::deserialize")]
-    #[serde(default)]
+    #[serde(rename = "scope")]
     pub scopes: Option<Vec<crate::Scope>>,
+    /// Type of the token, should always be `bearer`. See [`TwitchTokenResponse::from_response_strict`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    /// Fields that could not be parsed
+    #[serde(skip)]
+    warnings: Vec<String>,
+    /// The response as returned by twitch, with secrets redacted
+    #[serde(skip)]
+    pub(crate) raw: Option<serde_json::Value>,
+}
+
+/// [`TwitchTokenResponse`] with the optional fields not yet parsed
+#[derive(Deserialize)]
+struct LenientTwitchTokenResponse {
+    access_token: AccessToken,
+    #[serde(default)]
+    expires_in: Option<serde_json::Value>,
+    #[serde(default)]
+    refresh_token: Option<serde_json::Value>,
+    #[serde(default)]
+    scope: Option<serde_json::Value>,
+    #[serde(default)]
+    token_type: Option<serde_json::Value>,
+}
+
+/// Parse an optional field, recording a warning instead of failing
+fn lenient<T: serde::de::DeserializeOwned>(
+    field: &str,
+    value: Option<serde_json::Value>,
+    warnings: &mut Vec<String>,
+) -> Option<T> {
+    let value = value?;
+    match serde_json::from_value::<Option<T>>(value.clone()) {
+        Ok(v) => v,
+        Err(e) => {
+            warnings.push(format!("could not parse `{field}` ({value}): {e}"));
+            None
+        }
+    }
+}
+
+impl From<LenientTwitchTokenResponse> for TwitchTokenResponse {
+    fn from(response: LenientTwitchTokenResponse) -> Self {
+        let mut warnings = vec![];
+        let expires_in = lenient("expires_in", response.expires_in, &mut warnings);
+        let refresh_token = lenient("refresh_token", response.refresh_token, &mut warnings);
+        let token_type = lenient("token_type", response.token_type, &mut warnings);
+        let scopes = response
+            .scope
+            .and_then(|value| match scope::deserialize(value.clone()) {
+                Ok(scopes) => scopes,
+                Err(e) => {
+                    warnings.push(format!("could not parse `scope` ({value}): {e}"));
+                    None
+                }
+            });
+        TwitchTokenResponse {
+            access_token: response.access_token,
+            expires_in,
+            refresh_token,
+            scopes,
+            token_type,
+            warnings,
+            raw: None,
+        }
+    }
+}
+
+impl TryFrom<serde_json::Value> for TwitchTokenResponse {
+    type Error = serde_json::Error;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        let mut raw = value.clone();
+        if let Some(object) = raw.as_object_mut() {
+            for (field, redacted) in [
+                ("access_token", "[redacted access token]"),
+                ("refresh_token", "[redacted refresh token]"),
+                ("id_token", "[redacted id token]"),
+            ] {
+                if let Some(value) = object.get_mut(field) {
+                    *value = redacted.into();
+                }
+            }
+        }
+        let response: LenientTwitchTokenResponse = serde_json::from_value(value)?;
+        Ok(TwitchTokenResponse {
+            raw: Some(raw),
+            ..response.into()
+        })
+    }
 }
 
 impl TwitchTokenResponse {
@@ -33,6 +126,34 @@ impl TwitchTokenResponse {
     ) -> Result<TwitchTokenResponse, RequestParseError> {
         crate::parse_response(response)
     }
+
+    /// Create a [TwitchTokenResponse] from a [http::Response], making sure the `token_type` is `bearer`
+    ///
+    /// Returns [`RequestParseError::UnexpectedTokenType`] otherwise, which can happen when a misconfigured proxy is in the way.
+    pub fn from_response_strict<B: AsRef<[u8]>>(
+        response: &http::Response<B>,
+    ) -> Result<TwitchTokenResponse, RequestParseError> {
+        let response = Self::from_response(response)?;
+        match response.token_type.as_deref() {
+            Some(token_type) if token_type.eq_ignore_ascii_case("bearer") => Ok(response),
+            found => Err(RequestParseError::UnexpectedTokenType {
+                found: found.map(str::to_owned),
+            }),
+        }
+    }
+
+    /// Parse with [`TwitchTokenResponse::from_response_strict`] if `strict_token_type` is set, otherwise with [`TwitchTokenResponse::from_response`]
+    #[cfg(feature = "client")]
+    pub(crate) fn from_response_with<B: AsRef<[u8]>>(
+        response: &http::Response<B>,
+        strict_token_type: bool,
+    ) -> Result<TwitchTokenResponse, RequestParseError> {
+        if strict_token_type {
+            Self::from_response_strict(response)
+        } else {
+            Self::from_response(response)
+        }
+    }
 }
 
 /// Twitch's representation of the oauth flow for errors
@@ -96,8 +217,10 @@ pub mod scope {
         let scopes: Option<Vec<crate::Scope>> = Deserialize::deserialize(de)?;
         if let Some(scopes) = scopes {
             match scopes {
-                scopes if scopes.is_empty() || scopes.len() > 1 => Ok(Some(scopes)),
-                scopes if scopes.len() == 1 && scopes.get(0).unwrap().as_str() == "" => Ok(None),
+                scopes if scopes.is_empty() || scopes.len() > 1 => {
+                    Ok(Some(crate::scopes::dedup(scopes)))
+                }
+                scopes if scopes.len() == 1 && scopes.first().unwrap().as_str() == "" => Ok(None),
                 _ => Ok(Some(scopes)),
             }
         } else {
@@ -118,4 +241,176 @@ impl TwitchTokenResponse {
 
     /// Get the scopes from this response
     pub fn scopes(&self) -> Option<&[crate::Scope]> { self.scopes.as_deref() }
+
+    /// Get the fields that could not be parsed, and were left as `None`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::id::TwitchTokenResponse;
+    /// let response: TwitchTokenResponse =
+    ///     serde_json::from_str(r#"{"access_token":"token","expires_in":"soon"}"#)?;
+    /// assert_eq!(response.expires_in(), None);
+    /// assert_eq!(response.warnings().len(), 1);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn warnings(&self) -> &[String] { &self.warnings }
+
+    /// Get the JSON twitch returned, with the access token, refresh token and id token redacted
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::id::TwitchTokenResponse;
+    /// let response: TwitchTokenResponse =
+    ///     serde_json::from_str(r#"{"access_token":"token","expires_in":3600,"extra":true}"#)?;
+    /// let raw = response.raw().unwrap();
+    /// assert_eq!(raw["access_token"], "[redacted access token]");
+    /// assert_eq!(raw["extra"], true);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn raw(&self) -> Option<&serde_json::Value> { self.raw.as_ref() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_unavailable() {
+        let body = br#"<html><head><title>503 Service Temporarily Unavailable</title></head><body><h1>503 Service Temporarily Unavailable</h1></body></html>"#;
+        let response = http::Response::builder()
+            .status(503)
+            .header(http::header::CONTENT_TYPE, "text/html")
+            .body(body)
+            .unwrap();
+        let error = TwitchTokenResponse::from_response(&response).unwrap_err();
+        assert!(matches!(
+            error,
+            RequestParseError::ServiceUnavailable(http::StatusCode::SERVICE_UNAVAILABLE)
+        ));
+    }
+
+    #[test]
+    fn raw_response() {
+        let body = br#"{"access_token":"accesstoken","expires_in":14124,"refresh_token":"refreshtoken","scope":["chat:read"],"token_type":"bearer","unknown":{"a":1}}"#;
+        let response = http::Response::builder()
+            .status(200)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .unwrap();
+        let response = TwitchTokenResponse::from_response(&response).unwrap();
+        assert_eq!(response.access_token.secret(), "accesstoken");
+        let raw = response.raw().unwrap();
+        assert_eq!(raw["access_token"], "[redacted access token]");
+        assert_eq!(raw["refresh_token"], "[redacted refresh token]");
+        assert_eq!(raw["expires_in"], 14124);
+        assert_eq!(raw["unknown"]["a"], 1);
+        assert!(!raw.to_string().contains("accesstoken"));
+        assert!(!raw.to_string().contains("refreshtoken"));
+    }
+
+    #[test]
+    fn raw_response_id_token() {
+        let response: TwitchTokenResponse = serde_json::from_str(
+            r#"{"access_token":"accesstoken","id_token":"header.payload.signature","token_type":"bearer"}"#,
+        )
+        .unwrap();
+        let raw = response.raw().unwrap();
+        assert_eq!(raw["id_token"], "[redacted id token]");
+        assert!(!raw.to_string().contains("header.payload.signature"));
+    }
+
+    #[test]
+    fn malformed_optional_fields() {
+        let response: TwitchTokenResponse = serde_json::from_str(
+            r#"{"access_token":"token","expires_in":-1,"refresh_token":5,"scope":"chat:read","token_type":"bearer"}"#,
+        )
+        .unwrap();
+        assert_eq!(response.access_token.secret(), "token");
+        assert_eq!(response.expires_in(), None);
+        assert_eq!(response.refresh_token(), None);
+        assert_eq!(response.scopes(), None);
+        assert_eq!(response.warnings().len(), 3);
+        assert!(response.warnings()[0].contains("expires_in"));
+        assert!(response.warnings()[1].contains("refresh_token"));
+        assert!(response.warnings()[2].contains("scope"));
+
+        let response: TwitchTokenResponse = serde_json::from_str(
+            r#"{"access_token":"token","expires_in":3600,"refresh_token":"refresh","scope":["chat:read"],"token_type":"bearer"}"#,
+        )
+        .unwrap();
+        assert_eq!(response.expires_in(), Some(Duration::from_secs(3600)));
+        assert_eq!(response.refresh_token().unwrap().secret(), "refresh");
+        assert_eq!(response.scopes(), Some(&[crate::Scope::ChatRead][..]));
+        assert!(response.warnings().is_empty());
+
+        // `[""]` is how twitch says there are no scopes, and `null` is the same as missing
+        let response: TwitchTokenResponse =
+            serde_json::from_str(r#"{"access_token":"token","expires_in":null,"scope":[""]}"#)
+                .unwrap();
+        assert_eq!(response.scopes(), None);
+        assert!(response.warnings().is_empty());
+    }
+
+    #[test]
+    fn strict_token_type() {
+        let response =
+            |body: &'static str| http::Response::builder().status(200).body(body).unwrap();
+
+        let bearer =
+            response(r#"{"access_token":"token","expires_in":3600,"token_type":"bearer"}"#);
+        assert_eq!(
+            TwitchTokenResponse::from_response(&bearer)
+                .unwrap()
+                .token_type
+                .as_deref(),
+            Some("bearer")
+        );
+        assert!(TwitchTokenResponse::from_response_strict(&bearer).is_ok());
+
+        let mac = response(r#"{"access_token":"token","expires_in":3600,"token_type":"mac"}"#);
+        assert!(TwitchTokenResponse::from_response(&mac).is_ok());
+        assert!(matches!(
+            TwitchTokenResponse::from_response_strict(&mac).unwrap_err(),
+            RequestParseError::UnexpectedTokenType { found: Some(found) } if found == "mac"
+        ));
+
+        let missing = response(r#"{"access_token":"token","expires_in":3600}"#);
+        assert!(TwitchTokenResponse::from_response(&missing).is_ok());
+        assert!(matches!(
+            TwitchTokenResponse::from_response_strict(&missing).unwrap_err(),
+            RequestParseError::UnexpectedTokenType { found: None }
+        ));
+    }
+
+    #[test]
+    fn duplicate_scopes() {
+        let response: TwitchTokenResponse = serde_json::from_str(
+            r#"{"access_token":"token","expires_in":3600,"scope":["chat:read","chat:edit","chat:read"],"token_type":"bearer"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            response.scopes(),
+            Some(&[crate::Scope::ChatRead, crate::Scope::ChatEdit][..])
+        );
+    }
+
+    #[test]
+    fn malformed_access_token() {
+        let body = br#"{"access_token":5,"expires_in":3600,"token_type":"bearer"}"#;
+        let response = http::Response::builder().status(200).body(body).unwrap();
+        assert!(matches!(
+            TwitchTokenResponse::from_response(&response).unwrap_err(),
+            RequestParseError::DeserializeError(_)
+        ));
+    }
+
+    #[test]
+    fn twitch_server_error() {
+        let body = br#"{"status":500,"message":"Internal Server Error"}"#;
+        let response = http::Response::builder().status(500).body(body).unwrap();
+        let error = TwitchTokenResponse::from_response(&response).unwrap_err();
+        assert!(matches!(error, RequestParseError::TwitchError(_)));
+    }
 }
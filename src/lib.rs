@@ -29,6 +29,8 @@
 //! ```
 #[cfg(feature = "client")]
 pub mod client;
+#[cfg(feature = "http1")]
+pub mod compat;
 pub mod id;
 pub mod scopes;
 pub mod tokens;
@@ -46,10 +48,13 @@ pub use tokens::{AppAccessToken, TwitchToken, UserToken, ValidatedToken};
 
 pub use url;
 
-pub use types::{AccessToken, ClientId, ClientSecret, CsrfToken, RefreshToken};
+pub use types::{AccessToken, AuthorizationCode, ClientId, ClientSecret, CsrfToken, RefreshToken};
 
 #[doc(hidden)]
-pub use types::{AccessTokenRef, ClientIdRef, ClientSecretRef, CsrfTokenRef, RefreshTokenRef};
+pub use types::{
+    AccessTokenRef, AuthorizationCodeRef, ClientIdRef, ClientSecretRef, CsrfTokenRef,
+    RefreshTokenRef,
+};
 
 #[cfg(feature = "client")]
 use self::client::Client;
@@ -117,13 +122,92 @@ pub static REVOKE_URL: once_cell::sync::Lazy<url::Url> =
     mock_env_url!("TWITCH_OAUTH2_REVOKE_URL", {
         TWITCH_OAUTH2_URL.to_string() + "revoke"
     },);
+/// Helix URL (`https://api.twitch.tv/helix/`) for `api.twitch.tv`, used by [`UserToken::get_user_info`](crate::UserToken::get_user_info)
+///
+/// Can be overridden when feature `mock_api` is enabled with environment variable `TWITCH_HELIX_URL`.
+///
+/// # Examples
+///
+/// Set the environment variable `TWITCH_HELIX_URL` to `http://localhost:8080/mock/` to use [`twitch-cli` mock](https://github.com/twitchdev/twitch-cli/blob/main/docs/mock-api.md) endpoints.
+#[cfg(feature = "helix")]
+pub static HELIX_URL: once_cell::sync::Lazy<url::Url> =
+    mock_env_url!("TWITCH_HELIX_URL", "https://api.twitch.tv/helix/");
+
+/// The environment a token was obtained from
+///
+/// Use this to make sure a token is used with the matching API, i.e to not use a token from the [`twitch-cli` mock](https://github.com/twitchdev/twitch-cli/blob/main/docs/mock-api.md) with the production Helix API.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Environment {
+    /// The production environment at `https://id.twitch.tv/oauth2/`
+    #[default]
+    Production,
+    /// The [`twitch-cli` mock](https://github.com/twitchdev/twitch-cli/blob/main/docs/mock-api.md) environment
+    Mock,
+    /// A custom environment with the given root url, set with feature `mock_api` and the environment variables for the urls, see [`AUTH_URL`]
+    Custom(url::Url),
+}
+
+impl Environment {
+    /// Get the environment the urls in this crate point to.
+    ///
+    /// This is [`Environment::Production`] unless the urls have been overridden, see [`AUTH_URL`].
+    pub fn current() -> Environment {
+        let production = [
+            (&*AUTH_URL, "https://id.twitch.tv/oauth2/authorize"),
+            (&*TOKEN_URL, "https://id.twitch.tv/oauth2/token"),
+            (&*VALIDATE_URL, "https://id.twitch.tv/oauth2/validate"),
+            (&*REVOKE_URL, "https://id.twitch.tv/oauth2/revoke"),
+        ]
+        .iter()
+        .all(|(url, default)| url.as_str() == *default);
+        if production {
+            Environment::Production
+        } else {
+            Environment::Custom(TWITCH_OAUTH2_URL.clone())
+        }
+    }
+}
+
+/// Parse an environment, `production`, `mock` or the root url of a custom environment
+///
+/// ```rust
+/// use twitch_oauth2::Environment;
+/// assert_eq!("mock".parse::<Environment>()?, Environment::Mock);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+impl std::str::FromStr for Environment {
+    type Err = url::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "production" => Ok(Environment::Production),
+            "mock" => Ok(Environment::Mock),
+            url => url.parse().map(Environment::Custom),
+        }
+    }
+}
 
 impl AccessTokenRef {
     /// Get the request needed to validate this token.
     ///
     /// Parse the response from this endpoint with [ValidatedToken::from_response](crate::ValidatedToken::from_response)
     pub fn validate_token_request(&self) -> http::Request<Vec<u8>> {
-        use http::{header::AUTHORIZATION, HeaderMap, Method};
+        self.validate_token_request_with(http::Method::GET, vec![])
+    }
+
+    /// Get the request needed to validate this token, with the given method and body.
+    ///
+    /// # Notes
+    ///
+    /// Twitch expects validation to be a `GET` without a body, as sent by [`AccessToken::validate_token_request`](AccessTokenRef::validate_token_request).
+    /// Other methods and bodies are not standard, and only meant for proxies that require them.
+    pub fn validate_token_request_with(
+        &self,
+        method: http::Method,
+        body: Vec<u8>,
+    ) -> http::Request<Vec<u8>> {
+        use http::{header::AUTHORIZATION, HeaderMap};
 
         let auth_header = format!("OAuth {}", self.secret());
         let mut headers = HeaderMap::new();
@@ -138,8 +222,8 @@ impl AccessTokenRef {
             &crate::VALIDATE_URL,
             &[],
             headers,
-            Method::GET,
-            vec![],
+            method,
+            body,
         )
     }
 
@@ -147,9 +231,9 @@ impl AccessTokenRef {
     ///
     /// Should be checked on regularly, according to <https://dev.twitch.tv/docs/authentication#validating-requests>
     #[cfg(feature = "client")]
-    pub async fn validate_token<'a, C>(
+    pub async fn validate_token<C>(
         &self,
-        client: &'a C,
+        client: &C,
     ) -> Result<ValidatedToken, ValidationError<<C as Client>::Error>>
     where
         C: Client,
@@ -163,6 +247,50 @@ impl AccessTokenRef {
         ValidatedToken::from_response(&resp).map_err(|v| v.into_other())
     }
 
+    /// Validate this token, retrying up to `attempts` times with `delay` in between if it is [not authorized](ValidationError::NotAuthorized).
+    ///
+    /// A token that was just granted can take a moment to be accepted by the validation endpoint, use this right after a grant to not fail on that.
+    /// Other errors are returned immediately.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # #[tokio::main]
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+    /// use std::time::Duration;
+    /// use twitch_oauth2::AccessToken;
+    /// let client = reqwest::Client::builder()
+    ///     .redirect(reqwest::redirect::Policy::none())
+    ///     .build()?;
+    /// let token = AccessToken::from("just granted token");
+    /// let validated = token
+    ///     .validate_with_retry(&client, 3, Duration::from_millis(500))
+    ///     .await?;
+    /// # Ok(())}
+    /// # fn main() {run();}
+    /// ```
+    #[cfg(all(feature = "client", feature = "tokio"))]
+    pub async fn validate_with_retry<C>(
+        &self,
+        client: &C,
+        attempts: u32,
+        delay: std::time::Duration,
+    ) -> Result<ValidatedToken, ValidationError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        let mut attempt = 1;
+        loop {
+            match self.validate_token(client).await {
+                Err(ValidationError::NotAuthorized) if attempt < attempts => {
+                    attempt += 1;
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
     /// Get the request needed to revoke this token.
     pub fn revoke_token_request(&self, client_id: &ClientId) -> http::Request<Vec<u8>> {
         use http::{HeaderMap, Method};
@@ -184,9 +312,9 @@ impl AccessTokenRef {
     ///
     /// See <https://dev.twitch.tv/docs/authentication#revoking-access-tokens>
     #[cfg(feature = "client")]
-    pub async fn revoke_token<'a, C>(
+    pub async fn revoke_token<C>(
         &self,
-        http_client: &'a C,
+        http_client: &C,
         client_id: &ClientId,
     ) -> Result<(), RevokeTokenError<<C as Client>::Error>>
     where
@@ -212,6 +340,18 @@ impl RefreshTokenRef {
         &self,
         client_id: &ClientId,
         client_secret: &ClientSecret,
+    ) -> http::Request<Vec<u8>> {
+        self.refresh_token_request_encoded(client_id, client_secret, BodyEncoding::default())
+    }
+
+    /// Get the request needed to refresh this token, with the parameters encoded as specified.
+    ///
+    /// See [`RefreshToken::refresh_token_request`](RefreshTokenRef::refresh_token_request)
+    pub fn refresh_token_request_encoded(
+        &self,
+        client_id: &ClientId,
+        client_secret: &ClientSecret,
+        encoding: BodyEncoding,
     ) -> http::Request<Vec<u8>> {
         use http::{HeaderMap, Method};
         use std::collections::HashMap;
@@ -222,12 +362,13 @@ impl RefreshTokenRef {
         params.insert("grant_type", "refresh_token");
         params.insert("refresh_token", self.secret());
 
-        construct_request(
+        construct_request_encoded(
             &crate::TOKEN_URL,
             &params,
             HeaderMap::new(),
             Method::POST,
             vec![],
+            encoding,
         )
     }
 
@@ -235,15 +376,39 @@ impl RefreshTokenRef {
     ///
     /// See <https://dev.twitch.tv/docs/authentication#refreshing-access-tokens>
     #[cfg(feature = "client")]
-    pub async fn refresh_token<'a, C>(
+    pub async fn refresh_token<C>(
         &self,
-        http_client: &'a C,
+        http_client: &C,
         client_id: &ClientId,
         client_secret: &ClientSecret,
     ) -> Result<
         (AccessToken, std::time::Duration, Option<RefreshToken>),
         RefreshTokenError<<C as Client>::Error>,
     >
+    where
+        C: Client,
+    {
+        let res = self
+            .refresh_token_response(http_client, client_id, client_secret, false)
+            .await?;
+
+        let expires_in = res.expires_in().ok_or(RefreshTokenError::NoExpiration)?;
+        let refresh_token = res.refresh_token;
+        let access_token = res.access_token;
+        Ok((access_token, expires_in, refresh_token))
+    }
+
+    /// Refresh the token, returning the full response from twitch.
+    ///
+    /// With `strict_token_type`, the response is parsed with [`TwitchTokenResponse::from_response_strict`](id::TwitchTokenResponse::from_response_strict).
+    #[cfg(feature = "client")]
+    pub(crate) async fn refresh_token_response<C>(
+        &self,
+        http_client: &C,
+        client_id: &ClientId,
+        client_secret: &ClientSecret,
+        strict_token_type: bool,
+    ) -> Result<id::TwitchTokenResponse, RefreshTokenError<<C as Client>::Error>>
     where
         C: Client,
     {
@@ -253,15 +418,34 @@ impl RefreshTokenRef {
             .req(req)
             .await
             .map_err(RefreshTokenError::RequestError)?;
-        let res = id::TwitchTokenResponse::from_response(&resp)?;
-
-        let expires_in = res.expires_in().ok_or(RefreshTokenError::NoExpiration)?;
-        let refresh_token = res.refresh_token;
-        let access_token = res.access_token;
-        Ok((access_token, expires_in, refresh_token))
+        if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+            if let Some(retry_after) = retry_after(resp.headers(), std::time::SystemTime::now()) {
+                return Err(RefreshTokenError::RateLimited { retry_after });
+            }
+        }
+        Ok(id::TwitchTokenResponse::from_response_with(
+            &resp,
+            strict_token_type,
+        )?)
     }
 }
 
+/// How the parameters of a request to `id.twitch.tv` are sent
+///
+/// Twitch accepts the parameters in the query of the url, which is the default.
+/// The other encodings are provided for proxies and other endpoints that expect the parameters in the body.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BodyEncoding {
+    /// Parameters are sent in the query of the url, the body is empty
+    #[default]
+    Query,
+    /// Parameters are sent in the body as `application/x-www-form-urlencoded`
+    Form,
+    /// Parameters are sent in the body as a `application/json` object
+    Json,
+}
+
 /// Construct a request that accepts `application/json` on default
 fn construct_request<I, K, V>(
     url: &url::Url,
@@ -276,13 +460,61 @@ where
     K: AsRef<str>,
     V: AsRef<str>,
 {
+    construct_request_encoded(url, params, headers, method, body, BodyEncoding::Query)
+}
+
+/// Construct a request that accepts `application/json` on default, with the parameters encoded as specified.
+///
+/// When `encoding` is not [`BodyEncoding::Query`], `body` is replaced by the encoded parameters.
+fn construct_request_encoded<I, K, V>(
+    url: &url::Url,
+    params: I,
+    mut headers: http::HeaderMap,
+    method: http::Method,
+    mut body: Vec<u8>,
+    encoding: BodyEncoding,
+) -> http::Request<Vec<u8>>
+where
+    I: std::iter::IntoIterator,
+    I::Item: std::borrow::Borrow<(K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    use std::borrow::Borrow;
+
     let mut url = url.clone();
-    url.query_pairs_mut().extend_pairs(params);
+    match encoding {
+        BodyEncoding::Query => {
+            url.query_pairs_mut().extend_pairs(params);
+        }
+        BodyEncoding::Form => {
+            body = url::form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(params)
+                .finish()
+                .into_bytes();
+            headers.insert(
+                http::header::CONTENT_TYPE,
+                "application/x-www-form-urlencoded".parse().unwrap(),
+            );
+        }
+        BodyEncoding::Json => {
+            let map: serde_json::Map<String, serde_json::Value> = params
+                .into_iter()
+                .map(|p| {
+                    let (k, v) = p.borrow();
+                    (k.as_ref().to_owned(), v.as_ref().into())
+                })
+                .collect();
+            body = serde_json::to_vec(&map).expect("a map of strings should always serialize");
+            headers.insert(
+                http::header::CONTENT_TYPE,
+                "application/json".parse().unwrap(),
+            );
+        }
+    }
     let url: String = url.into();
     let mut req = http::Request::builder().method(method).uri(url);
-    req.headers_mut()
-        .map(|h| h.extend(headers.into_iter()))
-        .unwrap();
+    req.headers_mut().map(|h| h.extend(headers)).unwrap();
     req.headers_mut()
         .map(|h| {
             if !h.contains_key(http::header::ACCEPT) {
@@ -293,6 +525,150 @@ where
     req.body(body).unwrap()
 }
 
+/// Render a request as a `curl` command, for example to reproduce a request when debugging.
+///
+/// If `mask_secrets` is `true`, secrets like the client secret, code, tokens and the `Authorization` header are replaced with `[redacted]`.
+///
+/// # Examples
+///
+/// ```rust
+/// use twitch_oauth2::tokens::UserTokenBuilder;
+/// let builder = UserTokenBuilder::new(
+///     "myclientid",
+///     "myclientsecret",
+///     url::Url::parse("http://localhost/twitch/register")?,
+/// );
+/// let request = builder.get_user_token_request("code");
+/// println!("{}", twitch_oauth2::as_curl(&request, true));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn as_curl(request: &http::Request<Vec<u8>>, mask_secrets: bool) -> String {
+    fn quote(s: &str) -> String { format!("'{}'", s.replace('\'', r"'\''")) }
+
+    let redacted;
+    let request = if mask_secrets {
+        redacted = redact_request(request);
+        &redacted
+    } else {
+        request
+    };
+    let mut command = format!(
+        "curl -X {} {}",
+        request.method(),
+        quote(&request.uri().to_string())
+    );
+    for (name, value) in request.headers() {
+        command.push_str(" \\\n  -H ");
+        command.push_str(&quote(&format!(
+            "{}: {}",
+            name,
+            String::from_utf8_lossy(value.as_bytes())
+        )));
+    }
+    if !request.body().is_empty() {
+        command.push_str(" \\\n  --data-raw ");
+        command.push_str(&quote(&String::from_utf8_lossy(request.body())));
+    }
+    command
+}
+
+/// Parameters that are secret and should not be shown
+const SECRET_PARAMS: &[&str] = &[
+    "client_secret",
+    "code",
+    "refresh_token",
+    "access_token",
+    "token",
+];
+
+/// Make a copy of the request with all secrets replaced
+pub(crate) fn redact_request(request: &http::Request<Vec<u8>>) -> http::Request<Vec<u8>> {
+    fn redact_pairs(s: &str) -> String {
+        url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(url::form_urlencoded::parse(s.as_bytes()).map(|(k, v)| {
+                if SECRET_PARAMS.contains(&k.as_ref()) {
+                    (k, "[redacted]".into())
+                } else {
+                    (k, v)
+                }
+            }))
+            .finish()
+    }
+
+    let mut uri = request.uri().path().to_owned();
+    if let Some(query) = request.uri().query() {
+        uri.push('?');
+        uri.push_str(&redact_pairs(query));
+    }
+    let mut parts = request.uri().clone().into_parts();
+    parts.path_and_query = Some(
+        uri.parse()
+            .expect("redacting the query should not make the uri invalid"),
+    );
+    let uri = http::Uri::from_parts(parts).expect("redacted uri should be valid");
+
+    let body = match request
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .map(|v| v.as_bytes())
+    {
+        Some(b"application/x-www-form-urlencoded") => {
+            redact_pairs(&String::from_utf8_lossy(request.body())).into_bytes()
+        }
+        Some(b"application/json") => {
+            match serde_json::from_slice::<serde_json::Value>(request.body()) {
+                Ok(serde_json::Value::Object(mut map)) => {
+                    for (k, v) in map.iter_mut() {
+                        if SECRET_PARAMS.contains(&k.as_str()) {
+                            *v = "[redacted]".into();
+                        }
+                    }
+                    serde_json::to_vec(&map).unwrap_or_default()
+                }
+                _ => vec![],
+            }
+        }
+        _ if request.body().is_empty() => vec![],
+        _ => b"[redacted]".to_vec(),
+    };
+
+    let mut redacted = http::Request::builder()
+        .method(request.method().clone())
+        .uri(uri)
+        .version(request.version())
+        .body(body)
+        .expect("redacted request should be valid");
+    for (name, value) in request.headers() {
+        if name == http::header::AUTHORIZATION {
+            redacted
+                .headers_mut()
+                .append(name, http::HeaderValue::from_static("[redacted]"));
+        } else {
+            redacted.headers_mut().append(name, value.clone());
+        }
+    }
+    redacted
+}
+
+/// Get the time to wait from a `Retry-After` header, given either in seconds or as an HTTP date
+#[cfg(feature = "client")]
+pub(crate) fn retry_after(
+    headers: &http::HeaderMap,
+    now: std::time::SystemTime,
+) -> Option<std::time::Duration> {
+    let value = headers
+        .get(http::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(std::time::Duration::from_secs(secs));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    // A date in the past means we can retry right away
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
 /// Parses a response, validating it and returning the response if all ok.
 pub(crate) fn parse_token_response_raw<B: AsRef<[u8]>>(
     resp: &http::Response<B>,
@@ -300,6 +676,9 @@ pub(crate) fn parse_token_response_raw<B: AsRef<[u8]>>(
     match serde_json::from_slice::<TwitchTokenErrorResponse>(resp.body().as_ref()) {
         Err(_) => match resp.status() {
             StatusCode::OK => Ok(resp),
+            status if status.is_server_error() => {
+                Err(RequestParseError::ServiceUnavailable(status))
+            }
             _ => Err(RequestParseError::Other(resp.status())),
         },
         Ok(twitch_err) => Err(RequestParseError::TwitchError(twitch_err)),
@@ -335,6 +714,232 @@ pub enum RequestParseError {
         /// Found `Content-Type` header
         found: String,
     },
+    /// twitch is unavailable, returned status code: {0}
+    ServiceUnavailable(StatusCode),
     /// twitch returned an unexpected status code: {0}
     Other(StatusCode),
+    /// twitch returned an unexpected token type: {found:?}
+    UnexpectedTokenType {
+        /// Found `token_type`
+        found: Option<String>,
+    },
+}
+
+impl RequestParseError {
+    /// Returns whether or not the request could succeed if retried later.
+    ///
+    /// This is the case for server errors, rate limits and responses that are not from twitch, for example from a proxy.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            RequestParseError::TwitchError(TwitchTokenErrorResponse { status, .. })
+            | RequestParseError::Other(status) => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            RequestParseError::ServiceUnavailable(_)
+            | RequestParseError::NotJson { .. }
+            | RequestParseError::DeserializeError(_) => true,
+            RequestParseError::UnexpectedTokenType { .. } => false,
+        }
+    }
+
+    /// Get a status code to respond with when this error happens in a web handler.
+    ///
+    /// Client errors from twitch, like an invalid code, are [`400 Bad Request`](StatusCode::BAD_REQUEST),
+    /// twitch being unavailable is [`503 Service Unavailable`](StatusCode::SERVICE_UNAVAILABLE), anything else is [`502 Bad Gateway`](StatusCode::BAD_GATEWAY).
+    pub fn http_status(&self) -> StatusCode {
+        match self {
+            RequestParseError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            RequestParseError::TwitchError(TwitchTokenErrorResponse { status, .. })
+                if status.is_client_error() && *status != StatusCode::TOO_MANY_REQUESTS =>
+            {
+                StatusCode::BAD_REQUEST
+            }
+            _ => StatusCode::BAD_GATEWAY,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    #[cfg(all(feature = "client", feature = "tokio"))]
+    async fn validate_with_retry() {
+        const VALID: &str = r#"{"client_id":"clientid","login":"login","scopes":[],"user_id":"1234","expires_in":3600}"#;
+        const INVALID: &str = r#"{"status":401,"message":"invalid access token"}"#;
+        let delay = std::time::Duration::from_secs(1);
+        let token = AccessToken::from("accesstoken");
+
+        let client = client::MockClient::default()
+            .respond(401, INVALID)
+            .respond(401, INVALID)
+            .respond(200, VALID);
+        let start = tokio::time::Instant::now();
+        let validated = token.validate_with_retry(&client, 3, delay).await.unwrap();
+        assert_eq!(validated.login.unwrap().as_str(), "login");
+        assert_eq!(client.requests.lock().unwrap().len(), 3);
+        assert_eq!(start.elapsed(), delay * 2);
+
+        let client = client::MockClient::default()
+            .respond(401, INVALID)
+            .respond(401, INVALID)
+            .respond(200, VALID);
+        assert!(matches!(
+            token.validate_with_retry(&client, 2, delay).await,
+            Err(ValidationError::NotAuthorized)
+        ));
+        assert_eq!(client.requests.lock().unwrap().len(), 2);
+
+        // other errors are not retried
+        let client = client::MockClient::default()
+            .respond(500, r#"{"status":500,"message":"Internal Server Error"}"#)
+            .respond(200, VALID);
+        assert!(matches!(
+            token.validate_with_retry(&client, 3, delay).await,
+            Err(ValidationError::RequestParseError(_))
+        ));
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "client")]
+    fn retry_after() {
+        use std::time::{Duration, SystemTime};
+
+        let headers = |value: &str| {
+            let mut headers = http::HeaderMap::new();
+            headers.insert(http::header::RETRY_AFTER, value.parse().unwrap());
+            headers
+        };
+        let now = httpdate::parse_http_date("Wed, 21 Oct 2015 07:28:00 GMT").unwrap();
+        assert_eq!(
+            super::retry_after(&headers("120"), now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            super::retry_after(&headers("Wed, 21 Oct 2015 07:29:30 GMT"), now),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(
+            super::retry_after(&headers("Wed, 21 Oct 2015 07:27:00 GMT"), now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(super::retry_after(&headers("soon"), now), None);
+        assert_eq!(
+            super::retry_after(&http::HeaderMap::new(), SystemTime::now()),
+            None
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn refresh_rate_limited() {
+        let rate_limited = |retry_after: &str| {
+            http::Response::builder()
+                .status(429)
+                .header(http::header::RETRY_AFTER, retry_after)
+                .body(br#"{"status":429,"message":"too many requests"}"#.to_vec())
+                .unwrap()
+        };
+        let client = crate::client::MockClient::default()
+            .respond_with(rate_limited("30"))
+            .respond_with(rate_limited(&httpdate::fmt_http_date(
+                std::time::SystemTime::now() + std::time::Duration::from_secs(3600),
+            )))
+            .respond(429, r#"{"status":429,"message":"too many requests"}"#);
+        let token = RefreshToken::from("refreshtoken");
+        let (client_id, secret) = (ClientId::from("clientid"), ClientSecret::from("secret"));
+
+        assert!(matches!(
+            token.refresh_token(&client, &client_id, &secret).await,
+            Err(RefreshTokenError::RateLimited { retry_after })
+                if retry_after == std::time::Duration::from_secs(30)
+        ));
+        assert!(matches!(
+            token.refresh_token(&client, &client_id, &secret).await,
+            Err(RefreshTokenError::RateLimited { retry_after })
+                if retry_after > std::time::Duration::from_secs(3590)
+                    && retry_after <= std::time::Duration::from_secs(3600)
+        ));
+        // Without the header, this is the usual twitch error
+        assert!(matches!(
+            token.refresh_token(&client, &client_id, &secret).await,
+            Err(RefreshTokenError::RequestParseError(_))
+        ));
+    }
+
+    #[test]
+    fn validate_token_request() {
+        let token = AccessToken::from("accesstoken");
+        let request = token.validate_token_request();
+        assert_eq!(request.method(), http::Method::GET);
+        assert_eq!(request.uri(), "https://id.twitch.tv/oauth2/validate");
+        assert_eq!(
+            request.headers()[http::header::AUTHORIZATION],
+            "OAuth accesstoken"
+        );
+        assert!(request.body().is_empty());
+
+        let request = token.validate_token_request_with(http::Method::POST, b"{}".to_vec());
+        assert_eq!(request.method(), http::Method::POST);
+        assert_eq!(request.uri(), "https://id.twitch.tv/oauth2/validate");
+        assert_eq!(
+            request.headers()[http::header::AUTHORIZATION],
+            "OAuth accesstoken"
+        );
+        assert_eq!(request.body(), b"{}");
+    }
+
+    #[test]
+    fn as_curl() {
+        let request = construct_request_encoded(
+            &url::Url::parse("https://id.twitch.tv/oauth2/token").unwrap(),
+            &[
+                ("client_id", "clientid"),
+                ("client_secret", "secret"),
+                ("code", "code"),
+            ],
+            http::HeaderMap::new(),
+            http::Method::POST,
+            vec![],
+            BodyEncoding::Form,
+        );
+        assert_eq!(
+            super::as_curl(&request, false),
+            "curl -X POST 'https://id.twitch.tv/oauth2/token' \\\n  \
+             -H 'content-type: application/x-www-form-urlencoded' \\\n  \
+             -H 'accept: application/json' \\\n  \
+             --data-raw 'client_id=clientid&client_secret=secret&code=code'"
+        );
+        assert_eq!(
+            super::as_curl(&request, true),
+            "curl -X POST 'https://id.twitch.tv/oauth2/token' \\\n  \
+             -H 'content-type: application/x-www-form-urlencoded' \\\n  \
+             -H 'accept: application/json' \\\n  \
+             --data-raw 'client_id=clientid&client_secret=%5Bredacted%5D&code=%5Bredacted%5D'"
+        );
+
+        let request = construct_request_encoded(
+            &url::Url::parse("https://id.twitch.tv/oauth2/token").unwrap(),
+            &[("code", "it's a code")],
+            http::HeaderMap::new(),
+            http::Method::POST,
+            vec![],
+            BodyEncoding::Json,
+        );
+        assert!(
+            super::as_curl(&request, false).ends_with(r#"--data-raw '{"code":"it'\''s a code"}'"#)
+        );
+
+        let request = AccessToken::from("token").validate_token_request();
+        let curl = super::as_curl(&request, true);
+        assert!(
+            curl.starts_with("curl -X GET 'https://id.twitch.tv/oauth2/validate"),
+            "{curl}"
+        );
+        assert!(curl.contains("-H 'authorization: [redacted]'"));
+        assert!(!curl.contains("--data-raw"));
+        assert!(super::as_curl(&request, false).contains("-H 'authorization: OAuth token'"));
+    }
 }
@@ -15,7 +15,7 @@ macro_rules! scope_impls {
         #[doc = "Scopes for twitch."]
         #[doc = ""]
         #[doc = "<https://dev.twitch.tv/docs/authentication/#scopes>"]
-        #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+        #[derive(Debug, Clone, Deserialize, Serialize)]
         #[non_exhaustive]
         #[serde(from = "String")]
         #[serde(into = "String")]
@@ -72,6 +72,14 @@ macro_rules! scope_impls {
                 ]
             }
 
+            /// Every scope known to this crate, including deprecated scopes
+            const KNOWN: &'static [&'static str] = &[
+                $(
+                    $(#[cfg($cfg)])*
+                    $rename,
+                )*
+            ];
+
             #[doc = "Get a description for the token"]
             pub const fn description(&self) -> &'static str {
                 #![allow(deprecated)]
@@ -218,6 +226,26 @@ impl std::borrow::Borrow<str> for Scope {
     fn borrow(&self) -> &str { self.as_str() }
 }
 
+/// Scopes are compared by their string, so `Scope::Other("chat:read".into())` is equal to [`Scope::ChatRead`]
+impl PartialEq for Scope {
+    fn eq(&self, other: &Self) -> bool { self.as_str() == other.as_str() }
+}
+
+impl Eq for Scope {}
+
+impl std::hash::Hash for Scope {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) { self.as_str().hash(state) }
+}
+
+/// Scopes are ordered by their string, like `chat:edit` before `chat:read`
+impl PartialOrd for Scope {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> { Some(self.cmp(other)) }
+}
+
+impl Ord for Scope {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering { self.as_str().cmp(other.as_str()) }
+}
+
 impl From<String> for Scope {
     fn from(s: String) -> Self { Scope::parse(s) }
 }
@@ -226,6 +254,310 @@ impl From<Scope> for String {
     fn from(s: Scope) -> Self { s.to_string() }
 }
 
+/// Parse a scope, ignoring surrounding whitespace and case.
+///
+/// Use [`Scope::parse`] to parse a scope exactly as given.
+///
+/// ```rust
+/// use twitch_oauth2::Scope;
+/// assert_eq!(" Chat:Read ".parse::<Scope>(), Ok(Scope::ChatRead));
+/// ```
+impl std::str::FromStr for Scope {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> { Ok(Scope::parse(s.trim().to_lowercase())) }
+}
+
+impl Scope {
+    /// Parse a list of scopes, for example one copied or exported from the [developer console](https://dev.twitch.tv/console).
+    ///
+    /// The list can be a JSON array of scopes, a JSON object with a `scopes` (or `scope`) array, or scopes separated by whitespace, newlines or commas.
+    /// Scopes that are not known are parsed as [`Scope::Other`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::Scope;
+    /// assert_eq!(
+    ///     Scope::parse_list(r#"["chat:read", "chat:edit"]"#)?,
+    ///     vec![Scope::ChatRead, Scope::ChatEdit]
+    /// );
+    /// assert_eq!(
+    ///     Scope::parse_list("chat:read\nchat:edit\n")?,
+    ///     vec![Scope::ChatRead, Scope::ChatEdit]
+    /// );
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn parse_list(input: &str) -> Result<Vec<Scope>, serde_json::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum ScopeList {
+            List(Vec<String>),
+            Object {
+                #[serde(alias = "scope")]
+                scopes: Vec<String>,
+            },
+        }
+
+        let input = input.trim();
+        let scopes = if input.starts_with('[') || input.starts_with('{') {
+            match serde_json::from_str(input)? {
+                ScopeList::List(scopes) | ScopeList::Object { scopes } => scopes,
+            }
+        } else {
+            input
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .map(ToOwned::to_owned)
+                .collect()
+        };
+        Ok(scopes
+            .into_iter()
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.trim().parse().unwrap_or_else(|e| match e {}))
+            .collect())
+    }
+
+    /// Returns whether `scope` is known to this crate, including deprecated scopes, and not parsed as [`Scope::Other`].
+    ///
+    /// This is a `const fn`, used by [`scopes!`](crate::scopes!) to check scopes at compile time.
+    pub const fn is_known(scope: &str) -> bool {
+        let scope = scope.as_bytes();
+        let mut i = 0;
+        while i < Scope::KNOWN.len() {
+            let known = Scope::KNOWN[i].as_bytes();
+            if known.len() == scope.len() {
+                let mut j = 0;
+                while j < known.len() && known[j] == scope[j] {
+                    j += 1;
+                }
+                if j == known.len() {
+                    return true;
+                }
+            }
+            i += 1;
+        }
+        false
+    }
+}
+
+/// Create a `Vec<Scope>` from scope names, checking at compile time that every scope is known.
+///
+/// # Examples
+///
+/// ```rust
+/// use twitch_oauth2::{scopes, Scope};
+/// assert_eq!(
+///     scopes![chat:read, channel:moderate],
+///     vec![Scope::ChatRead, Scope::ChannelModerate]
+/// );
+/// ```
+///
+/// A typo fails to compile
+///
+/// ```rust,compile_fail
+/// let scopes = twitch_oauth2::scopes![chat:raed];
+/// ```
+///
+/// Use [`Scope::parse`] for scopes that are not known to this crate.
+#[macro_export]
+macro_rules! scopes {
+    ($($first:ident $(: $rest:ident)*),* $(,)?) => {
+        {
+            let scopes: ::std::vec::Vec<$crate::Scope> = ::std::vec![$({
+                const SCOPE: &str = concat!(stringify!($first) $(, ":", stringify!($rest))*);
+                const _: () = assert!(
+                    $crate::Scope::is_known(SCOPE),
+                    concat!("unknown scope `", stringify!($first) $(, ":", stringify!($rest))*, "`")
+                );
+                $crate::Scope::parse(SCOPE)
+            }),*];
+            scopes
+        }
+    };
+}
+
+/// Sets of scopes commonly needed together, see [`UserTokenBuilder::with_moderation_scopes`](crate::tokens::UserTokenBuilder::with_moderation_scopes) and similar.
+pub mod presets {
+    use super::Scope;
+
+    /// All `moderator:read:*` and `moderator:manage:*` scopes, for acting as a moderator in channels.
+    pub const MODERATION: &[Scope] = &[
+        Scope::ModeratorManageAnnouncements,
+        Scope::ModeratorManageAutoMod,
+        Scope::ModeratorManageAutomodSettings,
+        Scope::ModeratorManageBannedUsers,
+        Scope::ModeratorManageBlockedTerms,
+        Scope::ModeratorManageChatMessages,
+        Scope::ModeratorManageChatSettings,
+        Scope::ModeratorManageShieldMode,
+        Scope::ModeratorManageShoutouts,
+        Scope::ModeratorReadAutomodSettings,
+        Scope::ModeratorReadBlockedTerms,
+        Scope::ModeratorReadChatSettings,
+        Scope::ModeratorReadChatters,
+        Scope::ModeratorReadFollowers,
+        Scope::ModeratorReadShieldMode,
+        Scope::ModeratorReadShoutouts,
+    ];
+
+    /// Scopes for reading and sending chat and whisper messages.
+    pub const CHAT_BOT: &[Scope] = &[
+        Scope::ChatRead,
+        Scope::ChatEdit,
+        Scope::WhispersRead,
+        Scope::WhispersEdit,
+    ];
+
+    /// Scopes for reading the subscriptions to a channel and of a user.
+    pub const SUBSCRIPTIONS: &[Scope] = &[
+        Scope::ChannelReadSubscriptions,
+        Scope::UserReadSubscriptions,
+    ];
+}
+
+impl Scope {
+    /// Get the category of the scope, the part before the first `:`, like `channel` for `channel:read:subscriptions`
+    ///
+    /// ```rust
+    /// use twitch_oauth2::Scope;
+    /// assert_eq!(Scope::ChannelReadSubscriptions.category(), "channel");
+    /// assert_eq!(Scope::ChatRead.category(), "chat");
+    /// ```
+    pub fn category(&self) -> &str { self.as_str().split(':').next().unwrap_or_default() }
+}
+
+/// Serialize scopes as objects with their name, category and description, for example for a settings UI.
+///
+/// Use with `#[serde(with = "twitch_oauth2::scopes::rich")]` on a `Vec<Scope>`.
+/// Deserializing accepts both the objects and plain strings.
+///
+/// # Examples
+///
+/// ```rust
+/// use twitch_oauth2::Scope;
+///
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Settings {
+///     #[serde(with = "twitch_oauth2::scopes::rich")]
+///     scopes: Vec<Scope>,
+/// }
+///
+/// let settings = Settings {
+///     scopes: vec![Scope::ChatRead],
+/// };
+/// assert_eq!(
+///     serde_json::to_string(&settings)?,
+///     r#"{"scopes":[{"name":"chat:read","category":"chat","description":"View live stream chat and rooms messages."}]}"#
+/// );
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub mod rich {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Scope;
+
+    /// A [`Scope`] serialized with its name, category and description
+    #[derive(Debug, Clone, Copy, Serialize)]
+    pub struct RichScope<'a> {
+        /// The scope, like `chat:read`
+        pub name: &'a str,
+        /// The [category](Scope::category) of the scope
+        pub category: &'a str,
+        /// The [description](Scope::description) of the scope
+        pub description: &'static str,
+    }
+
+    impl<'a> From<&'a Scope> for RichScope<'a> {
+        fn from(scope: &'a Scope) -> Self {
+            RichScope {
+                name: scope.as_str(),
+                category: scope.category(),
+                description: scope.description(),
+            }
+        }
+    }
+
+    /// Serialize the scopes as [`RichScope`]s
+    pub fn serialize<S: Serializer>(scopes: &[Scope], ser: S) -> Result<S::Ok, S::Error> {
+        ser.collect_seq(scopes.iter().map(RichScope::from))
+    }
+
+    /// Deserialize scopes from [`RichScope`]s or plain strings
+    pub fn deserialize<'de, D: Deserializer<'de>>(de: D) -> Result<Vec<Scope>, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Either {
+            Plain(String),
+            Rich { name: String },
+        }
+
+        Ok(Vec::<Either>::deserialize(de)?
+            .into_iter()
+            .map(|scope| match scope {
+                Either::Plain(name) | Either::Rich { name } => Scope::parse(name),
+            })
+            .collect())
+    }
+}
+
+/// Remove duplicate scopes, keeping the first occurrence
+pub(crate) fn dedup(scopes: Vec<Scope>) -> Vec<Scope> {
+    let mut deduped = Vec::with_capacity(scopes.len());
+    for scope in scopes {
+        if !deduped.contains(&scope) {
+            deduped.push(scope);
+        }
+    }
+    deduped
+}
+
+/// Accumulated scope requirements, for example for features that are enabled over time.
+///
+/// Use [`ScopeRequirements::missing_for_token`] to get the scopes that need to be requested when reauthorizing.
+///
+/// # Examples
+///
+/// ```rust
+/// use twitch_oauth2::{scopes::ScopeRequirements, Scope};
+/// let mut requirements = ScopeRequirements::new();
+/// requirements.register(&[Scope::ChatRead, Scope::ChatEdit]);
+/// requirements.register(&[Scope::ChatRead, Scope::ModeratorManageBannedUsers]);
+/// assert_eq!(requirements.scopes().len(), 3);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScopeRequirements {
+    scopes: Vec<Scope>,
+}
+
+impl ScopeRequirements {
+    /// Create a new, empty, set of requirements
+    pub fn new() -> Self { Self::default() }
+
+    /// Register the scopes needed by a feature. Scopes already registered are ignored.
+    pub fn register(&mut self, scopes: &[Scope]) -> &mut Self {
+        for scope in scopes {
+            if !self.scopes.contains(scope) {
+                self.scopes.push(scope.clone());
+            }
+        }
+        self
+    }
+
+    /// All registered scopes, in the order they were first registered.
+    pub fn scopes(&self) -> &[Scope] { &self.scopes }
+
+    /// Get the registered scopes that the token does not have.
+    pub fn missing_for_token<T>(&self, token: &T) -> Vec<Scope>
+    where T: crate::TwitchToken + ?Sized {
+        let token_scopes = token.scopes();
+        self.scopes
+            .iter()
+            .filter(|s| !token_scopes.contains(s))
+            .cloned()
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -237,6 +569,128 @@ mod tests {
         )
     }
 
+    #[test]
+    fn eq_matches_ord() {
+        let other = Scope::Other(Cow::from("chat:read"));
+        assert_eq!(other, Scope::ChatRead);
+        assert_eq!(other.cmp(&Scope::ChatRead), std::cmp::Ordering::Equal);
+        let mut scopes = vec![Scope::ChatRead, Scope::ChatEdit, other];
+        scopes.sort();
+        scopes.dedup();
+        assert_eq!(scopes, [Scope::ChatEdit, Scope::ChatRead]);
+        let set: std::collections::HashSet<_> = scopes.into_iter().collect();
+        assert!(set.contains(&Scope::Other(Cow::from("chat:edit"))));
+        assert!(set.contains("chat:read"));
+    }
+
+    #[test]
+    fn scopes_macro() {
+        assert_eq!(
+            crate::scopes![chat:read, moderator:manage:chat_settings, channel_subscriptions,],
+            vec![
+                Scope::ChatRead,
+                Scope::ModeratorManageChatSettings,
+                Scope::parse("channel_subscriptions"),
+            ]
+        );
+        assert!(crate::scopes![].is_empty());
+        assert!(Scope::is_known("user:read:email"));
+        assert!(!Scope::is_known("user:read:emai"));
+        assert!(!Scope::is_known("user:read:emails"));
+    }
+
+    #[test]
+    fn from_str_normalized() {
+        for input in ["chat:read", "Chat:Read ", "  CHAT:READ", "\tchat:read\n"] {
+            assert_eq!(
+                input.parse::<Scope>().unwrap(),
+                Scope::ChatRead,
+                "{input:?}"
+            );
+        }
+        assert_eq!(
+            " Some:New_Scope ".parse::<Scope>().unwrap(),
+            Scope::Other(Cow::from("some:new_scope"))
+        );
+        assert_eq!(
+            Scope::parse_list("Chat:Read, CHAT:EDIT").unwrap(),
+            vec![Scope::ChatRead, Scope::ChatEdit]
+        );
+    }
+
+    #[test]
+    fn parse_list() {
+        let expected = vec![
+            Scope::ChatRead,
+            Scope::ChatEdit,
+            Scope::Other(Cow::from("some:new_scope")),
+        ];
+        assert_eq!(
+            Scope::parse_list(r#"["chat:read", "chat:edit", "some:new_scope"]"#).unwrap(),
+            expected
+        );
+        assert_eq!(
+            Scope::parse_list(r#"{"scopes": ["chat:read", "chat:edit", "some:new_scope"]}"#)
+                .unwrap(),
+            expected
+        );
+        assert_eq!(
+            Scope::parse_list("chat:read\r\nchat:edit\n\nsome:new_scope\n").unwrap(),
+            expected
+        );
+        assert_eq!(
+            Scope::parse_list("chat:read, chat:edit some:new_scope").unwrap(),
+            expected
+        );
+        assert!(Scope::parse_list("").unwrap().is_empty());
+        assert!(Scope::parse_list(r#"["chat:read""#).is_err());
+    }
+
+    #[test]
+    fn rich_serialization() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Settings {
+            #[serde(with = "rich")]
+            scopes: Vec<Scope>,
+        }
+
+        let settings = Settings {
+            scopes: vec![
+                Scope::ChannelReadSubscriptions,
+                Scope::Other(Cow::from("custom")),
+            ],
+        };
+        let json = serde_json::to_value(&settings).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"scopes": [
+                {
+                    "name": "channel:read:subscriptions",
+                    "category": "channel",
+                    "description": Scope::ChannelReadSubscriptions.description(),
+                },
+                {
+                    "name": "custom",
+                    "category": "custom",
+                    "description": "unknown scope",
+                },
+            ]})
+        );
+        assert_eq!(serde_json::from_value::<Settings>(json).unwrap(), settings);
+        assert_eq!(
+            serde_json::from_str::<Settings>(
+                r#"{"scopes":["channel:read:subscriptions","custom"]}"#
+            )
+            .unwrap(),
+            settings
+        );
+        // plain scopes still serialize as strings
+        assert_eq!(
+            serde_json::to_string(&settings.scopes).unwrap(),
+            r#"["channel:read:subscriptions","custom"]"#
+        );
+    }
+
     #[test]
     fn roundabout() {
         for scope in Scope::all() {
@@ -251,4 +705,29 @@ mod tests {
             assert!(scope != Scope::ChannelSubscriptions)
         }
     }
+
+    #[test]
+    fn scope_requirements() {
+        let token =
+            crate::UserToken::test_token(false, &[Scope::ChatRead, Scope::UserReadEmail], None);
+        let mut requirements = ScopeRequirements::new();
+        assert!(requirements.missing_for_token(&token).is_empty());
+        requirements
+            .register(&[Scope::ChatRead, Scope::ChatEdit])
+            .register(&[Scope::ChatEdit, Scope::ModeratorManageBannedUsers])
+            .register(&[Scope::UserReadEmail, Scope::ChatRead]);
+        assert_eq!(
+            requirements.scopes(),
+            &[
+                Scope::ChatRead,
+                Scope::ChatEdit,
+                Scope::ModeratorManageBannedUsers,
+                Scope::UserReadEmail
+            ]
+        );
+        assert_eq!(
+            requirements.missing_for_token(&token),
+            vec![Scope::ChatEdit, Scope::ModeratorManageBannedUsers]
+        );
+    }
 }
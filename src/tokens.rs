@@ -1,12 +1,48 @@
 //! Twitch token types
 
 mod app_access_token;
+#[cfg(feature = "client")]
+mod cached_validator;
 pub mod errors;
+mod events;
+#[cfg(feature = "hmac_state")]
+mod hmac_state;
+#[cfg(feature = "import")]
+pub mod import;
+mod refresher;
+#[cfg(feature = "client")]
+mod shared_token;
+#[cfg(feature = "client")]
+mod single_flight;
+#[cfg(feature = "client")]
+mod token_source;
+mod twitch_auth;
+#[cfg(feature = "helix")]
+mod user_info;
 mod user_token;
 
 pub use app_access_token::AppAccessToken;
+#[cfg(feature = "client")]
+pub use cached_validator::CachedValidator;
+pub use events::{EventSink, TokenEvent};
+pub use refresher::Refresher;
+#[cfg(feature = "client")]
+pub use shared_token::SharedToken;
+#[cfg(feature = "client")]
+pub use single_flight::SingleFlightRefresher;
+#[cfg(feature = "client")]
+pub use token_source::{FallbackTokenSource, TokenSource};
+pub use twitch_auth::TwitchAuth;
+#[cfg(feature = "client")]
+pub use twitch_auth::TwitchAuthClient;
 use twitch_types::{UserId, UserIdRef, UserName, UserNameRef};
-pub use user_token::{ImplicitUserTokenBuilder, UserToken, UserTokenBuilder};
+#[cfg(feature = "helix")]
+pub use user_info::UserInfo;
+pub use user_token::{
+    AccountAuthUrl, AuthUrlParts, ExpirySource, FragmentParams, ImplicitUserTokenBuilder,
+    LoginChanged, Prompt, RedirectAllowlist, RedirectMatch, RefreshOutcome, ScopeAdvisory,
+    ScopeOrder, TwitchCallbackQuery, UserToken, UserTokenBuilder, UserTokenRequests,
+};
 
 #[cfg(feature = "client")]
 use crate::client::Client;
@@ -16,7 +52,7 @@ use errors::ValidationError;
 #[cfg(feature = "client")]
 use errors::{RefreshTokenError, RevokeTokenError};
 
-use crate::types::{AccessToken, ClientId};
+use crate::types::{AccessToken, ClientId, ClientIdRef};
 use serde::Deserialize;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -31,6 +67,51 @@ pub enum BearerTokenType {
     AppAccessToken,
 }
 
+/// Remaining lifetime of a token, see [`TwitchToken::expiry`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Expiry {
+    /// The token never expires.
+    Never,
+    /// The token expires after the given remaining lifetime, which is zero when the token is already expired.
+    At(std::time::Duration),
+}
+
+impl Expiry {
+    /// Get the remaining lifetime, or `None` if the token never expires.
+    pub fn duration(&self) -> Option<std::time::Duration> {
+        match self {
+            Expiry::Never => None,
+            Expiry::At(d) => Some(*d),
+        }
+    }
+
+    /// Returns whether or not the token never expires.
+    pub fn is_never(&self) -> bool { matches!(self, Expiry::Never) }
+
+    /// Returns whether or not the remaining lifetime is zero.
+    pub fn is_elapsed(&self) -> bool { matches!(self, Expiry::At(d) if d.is_zero()) }
+
+    /// Get the point in time the token expires, counting from `now`.
+    ///
+    /// Returns `None` if the token never expires, or if the expiry can't be represented as a [`SystemTime`](std::time::SystemTime).
+    ///
+    /// ```rust
+    /// use std::time::{Duration, SystemTime};
+    /// use twitch_oauth2::tokens::Expiry;
+    ///
+    /// let now = SystemTime::now();
+    /// assert_eq!(Expiry::At(Duration::from_secs(10)).expires_at(now), Some(now + Duration::from_secs(10)));
+    /// assert_eq!(Expiry::Never.expires_at(now), None);
+    /// ```
+    pub fn expires_at(&self, now: std::time::SystemTime) -> Option<std::time::SystemTime> {
+        now.checked_add(self.duration()?)
+    }
+}
+
+impl From<Option<std::time::Duration>> for Expiry {
+    fn from(d: Option<std::time::Duration>) -> Self { d.map_or(Expiry::Never, Expiry::At) }
+}
+
 /// Trait for twitch tokens to get fields and generalize over [AppAccessToken] and [UserToken]
 #[cfg_attr(feature = "client", async_trait::async_trait)]
 pub trait TwitchToken {
@@ -64,8 +145,15 @@ pub trait TwitchToken {
         Self: Sized,
         C: Client;
     /// Get current lifetime of token.
+    ///
+    /// Tokens that never expire return [`Duration::MAX`](std::time::Duration::MAX), use [`TwitchToken::expiry`] to tell them apart.
     fn expires_in(&self) -> std::time::Duration;
 
+    /// Get current lifetime of token, or [`Expiry::Never`] if the token never expires.
+    ///
+    /// Prefer this over [`TwitchToken::expires_in`] when computing an expiry time, as it can't overflow.
+    fn expiry(&self) -> Expiry { Expiry::At(self.expires_in()) }
+
     /// Returns whether or not the token is expired.
     ///
     /// ```rust, no_run
@@ -86,6 +174,82 @@ pub trait TwitchToken {
     }
     /// Retrieve scopes attached to the token
     fn scopes(&self) -> &[Scope];
+
+    /// Get the [`Environment`](crate::Environment) this token was obtained from.
+    fn environment(&self) -> &crate::Environment { &PRODUCTION }
+
+    /// Get the scopes of this token sorted and without duplicates, to compare or hash them regardless of order.
+    ///
+    /// ```rust
+    /// # use twitch_oauth2::{UserToken, Scope};
+    /// # fn t() -> UserToken {todo!()}
+    /// # fn run() {
+    /// # let token = t();
+    /// use twitch_oauth2::TwitchToken;
+    /// let cache_key = (token.user_id.clone(), token.scopes_canonical());
+    /// # }
+    /// ```
+    fn scopes_canonical(&self) -> Vec<Scope> {
+        let mut scopes = self.scopes().to_vec();
+        scopes.sort();
+        scopes.dedup();
+        scopes
+    }
+
+    /// Returns whether or not the token has the given scope.
+    ///
+    /// This doesn't allocate, use it over [`TwitchToken::has_scopes`] when checking a single scope.
+    fn has_scope(&self, scope: &Scope) -> bool { self.scopes().contains(scope) }
+
+    /// Returns whether or not the token has all of the given scopes.
+    fn has_scopes(&self, scopes: &[Scope]) -> bool {
+        let token_scopes = self.scopes();
+        scopes.iter().all(|s| token_scopes.contains(s))
+    }
+
+    /// Returns whether or not the token has at least one of the given scopes.
+    ///
+    /// Some endpoints accept any one of several scopes, this models that requirement.
+    ///
+    /// ```rust
+    /// # use twitch_oauth2::{UserToken, Scope};
+    /// # fn t() -> UserToken {todo!()}
+    /// # fn run() {
+    /// # let user_token = t();
+    /// use twitch_oauth2::TwitchToken;
+    /// if user_token.has_any_scope(&[Scope::ModeratorReadChatters, Scope::ModeratorManageChatMessages]) {
+    ///     println!("can read chatters");
+    /// }
+    /// # }
+    /// ```
+    fn has_any_scope(&self, scopes: &[Scope]) -> bool {
+        let token_scopes = self.scopes();
+        scopes.iter().any(|s| token_scopes.contains(s))
+    }
+
+    /// Get the headers needed to authenticate a request to helix with this token, `Authorization: Bearer <token>` and `Client-Id: <client_id>`.
+    ///
+    /// The `Authorization` header is marked as [sensitive](http::HeaderValue::set_sensitive), so it is not shown when debug printed.
+    /// Fails if the token or client id contains characters that are not allowed in a header.
+    ///
+    /// ```rust
+    /// # use twitch_oauth2::UserToken;
+    /// # fn t() -> UserToken {todo!()}
+    /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let user_token = t();
+    /// use twitch_oauth2::TwitchToken;
+    /// let mut request = http::Request::get("https://api.twitch.tv/helix/users").body(())?;
+    /// request.headers_mut().extend(user_token.headers()?);
+    /// # Ok(()) }
+    /// ```
+    fn headers(&self) -> Result<http::HeaderMap, http::header::InvalidHeaderValue> {
+        let mut headers = http::HeaderMap::new();
+        let mut auth: http::HeaderValue = format!("Bearer {}", self.token().secret()).parse()?;
+        auth.set_sensitive(true);
+        headers.insert(http::header::AUTHORIZATION, auth);
+        headers.insert("Client-Id", self.client_id().as_str().parse()?);
+        Ok(headers)
+    }
     /// Validate this token. Should be checked on regularly, according to <https://dev.twitch.tv/docs/authentication#validating-requests>
     ///
     /// # Note
@@ -146,7 +310,124 @@ impl<T: TwitchToken + Send> TwitchToken for Box<T> {
 
     fn expires_in(&self) -> std::time::Duration { (**self).expires_in() }
 
+    fn expiry(&self) -> Expiry { (**self).expiry() }
+
     fn scopes(&self) -> &[Scope] { (**self).scopes() }
+
+    fn environment(&self) -> &crate::Environment { (**self).environment() }
+}
+
+static PRODUCTION: crate::Environment = crate::Environment::Production;
+
+/// Minimum interval between refresh attempts of a token
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RefreshThrottle {
+    min_interval: Option<std::time::Duration>,
+    #[cfg_attr(not(feature = "client"), allow(dead_code))]
+    last_attempt: Option<std::time::Instant>,
+}
+
+impl RefreshThrottle {
+    pub(crate) fn set_min_interval(&mut self, min_interval: std::time::Duration) {
+        self.min_interval = Some(min_interval).filter(|d| !d.is_zero());
+    }
+
+    /// Record a refresh attempt, or return how long to wait if the last attempt was too recent
+    #[cfg(feature = "client")]
+    pub(crate) fn attempt(&mut self) -> Result<(), std::time::Duration> {
+        let now = std::time::Instant::now();
+        if let (Some(min_interval), Some(last_attempt)) = (self.min_interval, self.last_attempt) {
+            let elapsed = now.saturating_duration_since(last_attempt);
+            if elapsed < min_interval {
+                return Err(min_interval - elapsed);
+            }
+        }
+        self.last_attempt = Some(now);
+        Ok(())
+    }
+}
+
+/// Revoke multiple tokens, doing at most `concurrency` requests at the same time.
+///
+/// The results are returned in the same order as the tokens were given.
+///
+/// See <https://dev.twitch.tv/docs/authentication#revoking-access-tokens>
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #[tokio::main]
+/// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+/// use twitch_oauth2::{AccessToken, ClientId};
+/// let client = reqwest::Client::builder()
+///     .redirect(reqwest::redirect::Policy::none())
+///     .build()?;
+/// let tokens = vec![
+///     (ClientId::from("myclientid"), AccessToken::from("token1")),
+///     (ClientId::from("myclientid"), AccessToken::from("token2")),
+/// ];
+/// for result in twitch_oauth2::tokens::revoke_tokens(&client, &tokens, 4).await {
+///     result?;
+/// }
+/// # Ok(())}
+/// # fn main() {run();}
+/// ```
+#[cfg(feature = "client")]
+pub async fn revoke_tokens<C>(
+    http_client: &C,
+    tokens: &[(ClientId, AccessToken)],
+    concurrency: usize,
+) -> Vec<Result<(), RevokeTokenError<<C as Client>::Error>>>
+where
+    C: Client,
+{
+    use futures_util::StreamExt as _;
+
+    futures_util::stream::iter(tokens)
+        .map(|(client_id, token)| token.revoke_token(http_client, client_id))
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+/// Validate multiple tokens, doing at most `concurrency` requests at the same time.
+///
+/// The results are returned in the same order as the tokens were given.
+///
+/// See <https://dev.twitch.tv/docs/authentication/validate-tokens/>
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #[tokio::main]
+/// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+/// use twitch_oauth2::AccessToken;
+/// let client = reqwest::Client::builder()
+///     .redirect(reqwest::redirect::Policy::none())
+///     .build()?;
+/// let tokens = vec![AccessToken::from("token1"), AccessToken::from("token2")];
+/// for validated in twitch_oauth2::tokens::validate_tokens(&client, &tokens, 8).await {
+///     println!("{:?}", validated?.login);
+/// }
+/// # Ok(())}
+/// # fn main() {run();}
+/// ```
+#[cfg(feature = "client")]
+pub async fn validate_tokens<C>(
+    http_client: &C,
+    tokens: &[AccessToken],
+    concurrency: usize,
+) -> Vec<Result<ValidatedToken, ValidationError<<C as Client>::Error>>>
+where
+    C: Client,
+{
+    use futures_util::StreamExt as _;
+
+    futures_util::stream::iter(tokens)
+        .map(|token| token.validate_token(http_client))
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
 }
 
 /// Token validation returned from `https://id.twitch.tv/oauth2/validate`
@@ -161,10 +442,14 @@ pub struct ValidatedToken {
     /// User ID associated with the token
     pub user_id: Option<UserId>,
     /// Scopes attached to the token.
+    #[serde(default, deserialize_with = "scopes")]
     pub scopes: Option<Vec<Scope>>,
     /// Lifetime of the token
     #[serde(deserialize_with = "expires_in")]
     pub expires_in: Option<std::time::Duration>,
+    /// The response as returned by twitch
+    #[serde(skip)]
+    raw: Option<serde_json::Value>,
 }
 
 fn expires_in<'a, D: serde::de::Deserializer<'a>>(
@@ -178,15 +463,88 @@ fn expires_in<'a, D: serde::de::Deserializer<'a>>(
     }
 }
 
+/// Accept scopes as an array or as a space separated string, some endpoints imitating twitch return the latter.
+///
+/// Duplicate scopes are removed.
+fn scopes<'a, D: serde::de::Deserializer<'a>>(d: D) -> Result<Option<Vec<Scope>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Scopes {
+        List(Vec<Scope>),
+        String(String),
+    }
+
+    Ok(match Option::<Scopes>::deserialize(d)? {
+        Some(Scopes::List(scopes)) => Some(crate::scopes::dedup(scopes)),
+        Some(Scopes::String(scopes)) => Some(crate::scopes::dedup(
+            scopes
+                .split_whitespace()
+                .map(|s| Scope::parse(s.to_owned()))
+                .collect(),
+        )),
+        None => None,
+    })
+}
+
 impl ValidatedToken {
+    /// Get the response as returned by twitch, including fields that are not modeled by this type.
+    ///
+    /// This is only set for tokens parsed with [`ValidatedToken::from_response`], which is used by [`AccessTokenRef::validate_token`](crate::AccessTokenRef::validate_token).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::ValidatedToken;
+    /// let response = http::Response::builder()
+    ///     .status(200)
+    ///     .body(r#"{"client_id":"clientid","scopes":[],"expires_in":3600,"new_field":true}"#)?;
+    /// let validated = ValidatedToken::from_response(&response)?;
+    /// assert_eq!(validated.raw().unwrap()["new_field"], true);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn raw(&self) -> Option<&serde_json::Value> { self.raw.as_ref() }
+
+    /// Check that this token was issued for the given client id.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::{ClientIdRef, ValidatedToken};
+    /// let response = http::Response::builder()
+    ///     .status(200)
+    ///     .body(r#"{"client_id":"clientid","scopes":[],"expires_in":3600}"#)?;
+    /// let validated = ValidatedToken::from_response(&response)?;
+    /// assert!(validated.assert_client_id(ClientIdRef::from_static("clientid")).is_ok());
+    /// assert!(validated.assert_client_id(ClientIdRef::from_static("other")).is_err());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn assert_client_id(
+        &self,
+        client_id: &ClientIdRef,
+    ) -> Result<(), errors::ClientIdMismatch> {
+        if self.client_id == client_id {
+            Ok(())
+        } else {
+            Err(errors::ClientIdMismatch {
+                expected: client_id.to_owned(),
+                found: self.client_id.clone(),
+            })
+        }
+    }
+
     /// Assemble a a validated token from a response.
     ///
     /// Get the request that generates this response with [`AccessToken::validate_token_request`][crate::types::AccessTokenRef::validate_token_request]
     pub fn from_response<B: AsRef<[u8]>>(
         response: &http::Response<B>,
     ) -> Result<ValidatedToken, ValidationError<std::convert::Infallible>> {
-        match crate::parse_response(response) {
-            Ok(ok) => Ok(ok),
+        match crate::parse_response::<serde_json::Value, _>(response) {
+            Ok(raw) => {
+                let mut validated: ValidatedToken = serde_json::from_value(raw.clone())
+                    .map_err(RequestParseError::DeserializeError)?;
+                validated.raw = Some(raw);
+                Ok(validated)
+            }
             Err(err) => match err {
                 RequestParseError::TwitchError(TwitchTokenErrorResponse { status, .. })
                     if status == http::StatusCode::UNAUTHORIZED =>
@@ -201,10 +559,137 @@ impl ValidatedToken {
 
 #[cfg(test)]
 mod tests {
-    use crate::ValidatedToken;
+    use crate::{scopes::Scope, TwitchToken, UserToken, ValidatedToken};
 
     use super::errors::ValidationError;
 
+    #[test]
+    fn scopes_canonical() {
+        let a = UserToken::test_token(
+            false,
+            &[Scope::ChatRead, Scope::ChatEdit, Scope::UserReadEmail],
+            None,
+        );
+        let b = UserToken::test_token(
+            false,
+            &[
+                Scope::UserReadEmail,
+                Scope::ChatRead,
+                Scope::ChatEdit,
+                Scope::ChatRead,
+            ],
+            None,
+        );
+        assert_ne!(a.scopes(), b.scopes());
+        assert_eq!(a.scopes_canonical(), b.scopes_canonical());
+        assert_eq!(
+            a.scopes_canonical(),
+            [Scope::ChatEdit, Scope::ChatRead, Scope::UserReadEmail]
+        );
+        assert!(Scope::Other("a:b".into()) < Scope::ChatRead);
+    }
+
+    #[test]
+    fn has_scopes() {
+        let token = UserToken::test_token(false, &[Scope::ChatRead, Scope::ChatEdit], None);
+        assert!(token.has_scopes(&[]));
+        assert!(token.has_scopes(&[Scope::ChatRead]));
+        assert!(token.has_scopes(&[Scope::ChatEdit, Scope::ChatRead]));
+        assert!(!token.has_scopes(&[Scope::ChatRead, Scope::ChannelModerate]));
+    }
+
+    #[test]
+    fn headers() {
+        let token = UserToken::test_token(false, &[], None);
+        let headers = token.headers().unwrap();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[http::header::AUTHORIZATION], "Bearer accesstoken");
+        assert!(headers[http::header::AUTHORIZATION].is_sensitive());
+        assert_eq!(headers["Client-Id"], "clientid");
+        assert!(!format!("{headers:?}").contains("accesstoken"));
+    }
+
+    #[test]
+    fn has_any_scope() {
+        let token = UserToken::test_token(false, &[Scope::ChatRead, Scope::ChatEdit], None);
+        assert!(!token.has_any_scope(&[]));
+        assert!(token.has_any_scope(&[Scope::ChannelModerate, Scope::ChatRead]));
+        assert!(!token.has_any_scope(&[Scope::ChannelModerate, Scope::WhispersRead]));
+        assert!(!UserToken::test_token(false, &[], None).has_any_scope(&[Scope::ChatRead]));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn revoke_tokens() {
+        use crate::{AccessToken, ClientId};
+
+        let client = crate::client::MockClient::default()
+            .respond(200, "")
+            .respond(400, r#"{"status":400,"message":"Invalid token"}"#)
+            .respond(200, "");
+        let tokens = vec![
+            (ClientId::from("clientid"), AccessToken::from("token1")),
+            (ClientId::from("clientid"), AccessToken::from("token2")),
+            (ClientId::from("clientid"), AccessToken::from("token3")),
+        ];
+        let results = super::revoke_tokens(&client, &tokens, 2).await;
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            results[1],
+            Err(super::RevokeTokenError::RequestParseError(
+                crate::RequestParseError::TwitchError(_)
+            ))
+        ));
+        assert!(results[2].is_ok());
+        assert_eq!(client.requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn validate_tokens() {
+        use crate::AccessToken;
+
+        let client = crate::client::MockClient::default()
+            .respond(
+                200,
+                r#"{"client_id":"clientid","login":"user1","scopes":[],"user_id":"1","expires_in":3600}"#,
+            )
+            .respond(401, r#"{"status":401,"message":"invalid access token"}"#)
+            .respond(
+                200,
+                r#"{"client_id":"clientid","login":"user3","scopes":[],"user_id":"3","expires_in":3600}"#,
+            );
+        let tokens = vec![
+            AccessToken::from("token1"),
+            AccessToken::from("token2"),
+            AccessToken::from("token3"),
+        ];
+        let results = super::validate_tokens(&client, &tokens, 2).await;
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[0]
+                .as_ref()
+                .unwrap()
+                .login
+                .as_ref()
+                .unwrap()
+                .as_str(),
+            "user1"
+        );
+        assert!(matches!(results[1], Err(ValidationError::NotAuthorized)));
+        assert_eq!(
+            results[2]
+                .as_ref()
+                .unwrap()
+                .login
+                .as_ref()
+                .unwrap()
+                .as_str(),
+            "user3"
+        );
+    }
+
     #[test]
     fn validated_token() {
         let body = br#"
@@ -222,6 +707,132 @@ mod tests {
         ValidatedToken::from_response(&response).unwrap();
     }
 
+    #[test]
+    fn validated_token_scopes() {
+        let response = |scopes: &str| {
+            http::Response::builder()
+                .status(200)
+                .body(format!(
+                    r#"{{"client_id":"wbmytr93xzw8zbg0p1izqyzzc5mbiz","login":"twitchdev",{scopes}"user_id":"141981764","expires_in":5520838}}"#
+                ))
+                .unwrap()
+        };
+        let expected = Some(vec![Scope::ChatRead, Scope::ChatEdit]);
+        assert_eq!(
+            ValidatedToken::from_response(&response(r#""scopes":["chat:read","chat:edit"],"#))
+                .unwrap()
+                .scopes,
+            expected
+        );
+        assert_eq!(
+            ValidatedToken::from_response(&response(r#""scopes":"chat:read chat:edit","#))
+                .unwrap()
+                .scopes,
+            expected
+        );
+        assert_eq!(
+            ValidatedToken::from_response(&response(r#""scopes":"","#))
+                .unwrap()
+                .scopes,
+            Some(vec![])
+        );
+        assert_eq!(
+            ValidatedToken::from_response(&response(r#""scopes":null,"#))
+                .unwrap()
+                .scopes,
+            None
+        );
+        assert_eq!(
+            ValidatedToken::from_response(&response("")).unwrap().scopes,
+            None
+        );
+
+        // duplicates are removed, keeping the first occurrence
+        let validated = ValidatedToken::from_response(&response(
+            r#""scopes":["chat:edit","chat:read","chat:edit","chat:read"],"#,
+        ))
+        .unwrap();
+        let token = UserToken::new("accesstoken".into(), None, validated, None).unwrap();
+        assert_eq!(token.scopes(), [Scope::ChatEdit, Scope::ChatRead]);
+        assert_eq!(
+            ValidatedToken::from_response(&response(r#""scopes":"chat:read chat:read","#))
+                .unwrap()
+                .scopes,
+            Some(vec![Scope::ChatRead])
+        );
+    }
+
+    #[test]
+    fn validation_error_retryable() {
+        let response = |status: u16, content_type: &str, body: &'static str| {
+            http::Response::builder()
+                .status(status)
+                .header(http::header::CONTENT_TYPE, content_type)
+                .body(body)
+                .unwrap()
+        };
+        let json = "application/json";
+
+        let error = ValidatedToken::from_response(&response(
+            401,
+            json,
+            r#"{"status":401,"message":"invalid access token"}"#,
+        ))
+        .unwrap_err();
+        assert!(matches!(error, ValidationError::NotAuthorized));
+        assert!(error.is_fatal());
+
+        let error = ValidatedToken::from_response(&response(
+            400,
+            json,
+            r#"{"status":400,"message":"invalid client"}"#,
+        ))
+        .unwrap_err();
+        assert!(error.is_fatal());
+
+        for (status, content_type, body) in [
+            (
+                500,
+                json,
+                r#"{"status":500,"message":"internal server error"}"#,
+            ),
+            (429, json, r#"{"status":429,"message":"too many requests"}"#),
+            (503, "text/html", "<html>unavailable</html>"),
+            (200, "text/html", "<html>captive portal</html>"),
+        ] {
+            let error =
+                ValidatedToken::from_response(&response(status, content_type, body)).unwrap_err();
+            assert!(error.is_retryable(), "{status} {body}: {error:?}");
+        }
+
+        let error =
+            ValidationError::Request(std::io::Error::from(std::io::ErrorKind::ConnectionReset));
+        assert!(error.is_retryable());
+        assert!(!ValidationError::<std::io::Error>::NoLogin.is_retryable());
+    }
+
+    #[test]
+    fn validated_raw() {
+        let body = br#"
+        {
+            "client_id": "wbmytr93xzw8zbg0p1izqyzzc5mbiz",
+            "login": "twitchdev",
+            "scopes": [],
+            "user_id": "141981764",
+            "expires_in": 5520838,
+            "new_field": {"nested": 1}
+        }
+        "#;
+        let response = http::Response::builder().status(200).body(body).unwrap();
+        let token = ValidatedToken::from_response(&response).unwrap();
+        let raw = token.raw().unwrap();
+        assert_eq!(raw["new_field"]["nested"], 1);
+        assert_eq!(raw["login"], "twitchdev");
+
+        let token: ValidatedToken = serde_json::from_slice(body).unwrap();
+        assert!(token.raw().is_none());
+    }
+
     #[test]
     fn validated_non_expiring_token() {
         let body = br#"
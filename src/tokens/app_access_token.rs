@@ -18,6 +18,9 @@ use crate::{
 ///
 /// In some contexts (i.e [EventSub](https://dev.twitch.tv/docs/eventsub)) an App Access Token can be used in the context of users that have authenticated
 /// the specific Client ID
+///
+/// The token can be serialized to be stored across restarts. The serialized token contains the access token, refresh token and client secret, so store it securely.
+/// The expiry is stored as a unix timestamp, so the time passed while the token was stored is accounted for.
 #[derive(Clone)]
 pub struct AppAccessToken {
     /// The access token used to authenticate requests with
@@ -31,6 +34,10 @@ pub struct AppAccessToken {
     client_id: ClientId,
     client_secret: ClientSecret,
     scopes: Vec<Scope>,
+    environment: crate::Environment,
+    refresh_throttle: crate::tokens::RefreshThrottle,
+    /// Reject token responses that are not `bearer`, see [`AppAccessToken::set_strict_token_type`]
+    strict_token_type: bool,
 }
 
 impl std::fmt::Debug for AppAccessToken {
@@ -42,6 +49,7 @@ impl std::fmt::Debug for AppAccessToken {
             .field("client_secret", &self.client_secret)
             .field("expires_in", &self.expires_in())
             .field("scopes", &self.scopes)
+            .field("environment", &self.environment)
             .finish()
     }
 }
@@ -66,17 +74,27 @@ impl TwitchToken for AppAccessToken {
     where
         C: Client,
     {
-        let (access_token, expires_in, refresh_token) =
-            if let Some(token) = self.refresh_token.take() {
-                token
-                    .refresh_token(http_client, &self.client_id, &self.client_secret)
-                    .await?
-            } else {
-                return Err(RefreshTokenError::NoRefreshToken);
-            };
-        self.access_token = access_token;
-        self.expires_in = expires_in;
-        self.refresh_token = refresh_token;
+        self.refresh_throttle
+            .attempt()
+            .map_err(|retry_after| RefreshTokenError::TooSoon { retry_after })?;
+        let response = if let Some(token) = self.refresh_token.take() {
+            token
+                .refresh_token_response(
+                    http_client,
+                    &self.client_id,
+                    &self.client_secret,
+                    self.strict_token_type,
+                )
+                .await?
+        } else {
+            return Err(RefreshTokenError::NoRefreshToken);
+        };
+        self.expires_in = response
+            .expires_in()
+            .ok_or(RefreshTokenError::NoExpiration)?;
+        self.struct_created = std::time::Instant::now();
+        self.access_token = response.access_token;
+        self.refresh_token = response.refresh_token;
         Ok(())
     }
 
@@ -87,6 +105,8 @@ impl TwitchToken for AppAccessToken {
     }
 
     fn scopes(&self) -> &[Scope] { self.scopes.as_slice() }
+
+    fn environment(&self) -> &crate::Environment { &self.environment }
 }
 
 impl AppAccessToken {
@@ -109,9 +129,34 @@ impl AppAccessToken {
             expires_in: expires_in.unwrap_or_default(),
             struct_created: std::time::Instant::now(),
             scopes: scopes.unwrap_or_default(),
+            environment: crate::Environment::current(),
+            refresh_throttle: Default::default(),
+            strict_token_type: false,
         }
     }
 
+    /// Set the [`Environment`](crate::Environment) this token was obtained from.
+    ///
+    /// Defaults to [`Environment::current`](crate::Environment::current) when the token is created.
+    pub fn set_environment(&mut self, environment: crate::Environment) {
+        self.environment = environment
+    }
+
+    /// Set the minimum time between attempts to refresh this token, to avoid refreshing in a loop when refreshes keep failing.
+    ///
+    /// Refreshing sooner than `interval` after the last attempt, successful or not, returns [`RefreshTokenError::TooSoon`](crate::tokens::errors::RefreshTokenError::TooSoon)
+    /// without making a request. A zero `interval` disables this, which is the default.
+    pub fn set_min_refresh_interval(&mut self, interval: std::time::Duration) {
+        self.refresh_throttle.set_min_interval(interval)
+    }
+
+    /// Reject refresh responses with a `token_type` other than `bearer`, see [`TwitchTokenResponse::from_response_strict`](crate::id::TwitchTokenResponse::from_response_strict)
+    ///
+    /// Tokens from [`AppAccessToken::get_app_access_token_strict`] have this set.
+    pub fn set_strict_token_type(&mut self, strict_token_type: bool) {
+        self.strict_token_type = strict_token_type
+    }
+
     /// Assemble token and validate it. Retrieves [`client_id`](TwitchToken::client_id) and [`scopes`](TwitchToken::scopes).
     #[cfg(feature = "client")]
     pub async fn from_existing<RE, C>(
@@ -160,6 +205,36 @@ impl AppAccessToken {
         client_secret: ClientSecret,
         scopes: Vec<Scope>,
     ) -> Result<AppAccessToken, AppAccessTokenError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        Self::get_app_access_token_with(http_client, client_id, client_secret, scopes, false).await
+    }
+
+    /// Generate app access token like [`AppAccessToken::get_app_access_token`], rejecting responses with a `token_type` other than `bearer`
+    ///
+    /// The token keeps rejecting them when refreshed, see [`AppAccessToken::set_strict_token_type`].
+    #[cfg(feature = "client")]
+    pub async fn get_app_access_token_strict<C>(
+        http_client: &C,
+        client_id: ClientId,
+        client_secret: ClientSecret,
+        scopes: Vec<Scope>,
+    ) -> Result<AppAccessToken, AppAccessTokenError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        Self::get_app_access_token_with(http_client, client_id, client_secret, scopes, true).await
+    }
+
+    #[cfg(feature = "client")]
+    async fn get_app_access_token_with<C>(
+        http_client: &C,
+        client_id: ClientId,
+        client_secret: ClientSecret,
+        scopes: Vec<Scope>,
+        strict_token_type: bool,
+    ) -> Result<AppAccessToken, AppAccessTokenError<<C as Client>::Error>>
     where
         C: Client,
     {
@@ -170,8 +245,10 @@ impl AppAccessToken {
             .await
             .map_err(AppAccessTokenError::Request)?;
 
-        let response = crate::id::TwitchTokenResponse::from_response(&resp)?;
-        let app_access = AppAccessToken::from_response(response, client_id, client_secret);
+        let response =
+            crate::id::TwitchTokenResponse::from_response_with(&resp, strict_token_type)?;
+        let mut app_access = AppAccessToken::from_response(response, client_id, client_secret);
+        app_access.set_strict_token_type(strict_token_type);
 
         Ok(app_access)
     }
@@ -206,3 +283,242 @@ impl AppAccessToken {
         )
     }
 }
+
+/// Serialized form of [`AppAccessToken`]
+#[derive(serde::Deserialize, serde::Serialize)]
+struct SerializedAppAccessToken {
+    access_token: AccessToken,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    refresh_token: Option<RefreshToken>,
+    client_id: ClientId,
+    client_secret: ClientSecret,
+    #[serde(default)]
+    scopes: Vec<Scope>,
+    /// Unix timestamp in seconds
+    expires_at: u64,
+    /// See [`Environment::from_str`](std::str::FromStr::from_str), missing for tokens serialized before the environment was stored
+    #[serde(default)]
+    environment: Option<String>,
+}
+
+impl serde::Serialize for AppAccessToken {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let expires_at = std::time::SystemTime::now()
+            .checked_add(self.expires_in())
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .ok_or_else(|| serde::ser::Error::custom("expiry is out of range"))?;
+        SerializedAppAccessToken {
+            access_token: self.access_token.clone(),
+            refresh_token: self.refresh_token.clone(),
+            client_id: self.client_id.clone(),
+            client_secret: self.client_secret.clone(),
+            scopes: self.scopes.clone(),
+            expires_at: expires_at.as_secs(),
+            environment: match &self.environment {
+                crate::Environment::Production => Some("production".to_owned()),
+                crate::Environment::Mock => Some("mock".to_owned()),
+                crate::Environment::Custom(url) => Some(url.to_string()),
+            },
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for AppAccessToken {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let token = SerializedAppAccessToken::deserialize(deserializer)?;
+        let expires_in = (std::time::UNIX_EPOCH + std::time::Duration::from_secs(token.expires_at))
+            .duration_since(std::time::SystemTime::now())
+            .unwrap_or_default();
+        let mut app_token = AppAccessToken::from_existing_unchecked(
+            token.access_token,
+            token.refresh_token,
+            token.client_id,
+            token.client_secret,
+            Some(token.scopes),
+            Some(expires_in),
+        );
+        if let Some(environment) = token.environment {
+            app_token.set_environment(environment.parse().map_err(serde::de::Error::custom)?);
+        }
+        Ok(app_token)
+    }
+}
+
+#[cfg(test)]
+impl AppAccessToken {
+    /// Token for client id `clientid` and client secret `secret`, with the refresh token `refreshtoken` and the [`Scope::ChatRead`] scope
+    pub(crate) fn test_token(expires_in: std::time::Duration) -> AppAccessToken {
+        AppAccessToken::from_existing_unchecked(
+            "accesstoken".into(),
+            RefreshToken::from("refreshtoken"),
+            "clientid",
+            "secret",
+            Some(vec![Scope::ChatRead]),
+            Some(expires_in),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn min_refresh_interval() {
+        let client = crate::client::MockClient::default()
+            .respond(
+                200,
+                r#"{"access_token":"newaccesstoken","expires_in":14124,"refresh_token":"newrefreshtoken","token_type":"bearer"}"#,
+            )
+            .respond(
+                200,
+                r#"{"access_token":"newestaccesstoken","expires_in":14124,"refresh_token":"newestrefreshtoken","token_type":"bearer"}"#,
+            );
+        let mut token = AppAccessToken::test_token(std::time::Duration::from_secs(3600));
+        token.set_min_refresh_interval(std::time::Duration::from_secs(60));
+        token.refresh_token(&client).await.unwrap();
+        assert!(matches!(
+            token.refresh_token(&client).await,
+            Err(RefreshTokenError::TooSoon { retry_after }) if retry_after <= std::time::Duration::from_secs(60)
+        ));
+        assert_eq!(token.access_token.secret(), "newaccesstoken");
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+
+        // disabled again
+        token.set_min_refresh_interval(std::time::Duration::ZERO);
+        token.refresh_token(&client).await.unwrap();
+        assert_eq!(token.access_token.secret(), "newestaccesstoken");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn refresh_resets_clock() {
+        let client = crate::client::MockClient::default().respond(
+            200,
+            r#"{"access_token":"newaccesstoken","expires_in":60,"refresh_token":"newrefreshtoken","token_type":"bearer"}"#,
+        );
+        let mut token = AppAccessToken::test_token(std::time::Duration::from_secs(60));
+        token.struct_created = token
+            .struct_created
+            .checked_sub(std::time::Duration::from_secs(120))
+            .unwrap();
+        assert!(token.is_elapsed());
+
+        token.refresh_token(&client).await.unwrap();
+        assert!(!token.is_elapsed());
+        assert!(token.expires_in() > std::time::Duration::from_secs(50));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn strict_token_type() {
+        const MAC: &str = r#"{"access_token":"accesstoken","expires_in":14124,"refresh_token":"refreshtoken","token_type":"mac"}"#;
+        const BEARER: &str = r#"{"access_token":"accesstoken","expires_in":14124,"refresh_token":"refreshtoken","token_type":"bearer"}"#;
+        let client = crate::client::MockClient::default()
+            .respond(200, MAC)
+            .respond(200, MAC)
+            .respond(200, BEARER)
+            .respond(200, MAC);
+        let get = |strict: bool| {
+            let client = &client;
+            async move {
+                if strict {
+                    AppAccessToken::get_app_access_token_strict(
+                        client,
+                        "clientid".into(),
+                        "secret".into(),
+                        vec![],
+                    )
+                    .await
+                } else {
+                    AppAccessToken::get_app_access_token(
+                        client,
+                        "clientid".into(),
+                        "secret".into(),
+                        vec![],
+                    )
+                    .await
+                }
+            }
+        };
+
+        // lenient by default
+        let mut token = get(false).await.unwrap();
+        assert!(!token.strict_token_type);
+        assert!(matches!(
+            get(true).await,
+            Err(AppAccessTokenError::RequestParseError(
+                crate::RequestParseError::UnexpectedTokenType { found: Some(ref found) }
+            )) if found == "mac"
+        ));
+
+        token = get(true).await.unwrap();
+        assert!(matches!(
+            token.refresh_token(&client).await,
+            Err(RefreshTokenError::RequestParseError(
+                crate::RequestParseError::UnexpectedTokenType { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn serde_roundtrip() {
+        let mut token = AppAccessToken::test_token(std::time::Duration::from_secs(3600));
+        token.set_environment(crate::Environment::Mock);
+        let json = serde_json::to_value(&token).unwrap();
+        assert_eq!(json["access_token"], "accesstoken");
+        assert_eq!(json["client_secret"], "secret");
+        assert!(json["expires_at"].is_u64());
+
+        let mut token: AppAccessToken = serde_json::from_value(json).unwrap();
+        assert_eq!(token.access_token.secret(), "accesstoken");
+        assert_eq!(
+            token.refresh_token.as_ref().unwrap().secret(),
+            "refreshtoken"
+        );
+        assert_eq!(token.client_id().as_str(), "clientid");
+        assert_eq!(token.client_secret.secret(), "secret");
+        assert_eq!(token.scopes(), [Scope::ChatRead]);
+        assert_eq!(token.environment(), &crate::Environment::Mock);
+
+        token.set_environment(crate::Environment::Production);
+        let json = serde_json::to_value(&token).unwrap();
+        assert_eq!(json["environment"], "production");
+        let token: AppAccessToken = serde_json::from_value(json).unwrap();
+        assert_eq!(token.environment(), &crate::Environment::Production);
+        let expires_in = token.expires_in().as_secs();
+        assert!((3598..=3600).contains(&expires_in), "{expires_in}");
+    }
+
+    #[test]
+    fn serde_expired() {
+        let token: AppAccessToken = serde_json::from_str(
+            r#"{"access_token":"accesstoken","client_id":"clientid","client_secret":"secret","expires_at":1}"#,
+        )
+        .unwrap();
+        assert!(token.is_elapsed());
+        assert_eq!(token.environment(), &crate::Environment::current());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn refresh_deserialized() {
+        let client = crate::client::MockClient::default().respond(
+            200,
+            r#"{"access_token":"newaccesstoken","expires_in":14124,"refresh_token":"newrefreshtoken","scope":["chat:read"],"token_type":"bearer"}"#,
+        );
+        let json = serde_json::to_string(&AppAccessToken::test_token(
+            std::time::Duration::from_secs(3600),
+        ))
+        .unwrap();
+        let mut token: AppAccessToken = serde_json::from_str(&json).unwrap();
+        token.refresh_token(&client).await.unwrap();
+        assert_eq!(token.access_token.secret(), "newaccesstoken");
+        assert_eq!(
+            token.refresh_token.as_ref().unwrap().secret(),
+            "newrefreshtoken"
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::errors::ValidationError;
+use super::{EventSink, TokenEvent, ValidatedToken};
+use crate::client::Client;
+use crate::types::{AccessToken, AccessTokenRef};
+
+/// Cache for [`ValidatedToken`]s, to avoid validating the same token again within a short time.
+///
+/// Validations are stored by the access token, which is redacted when the cache is debug printed.
+/// Twitch requires tokens to be validated [at least once every hour](https://dev.twitch.tv/docs/authentication/validate-tokens/),
+/// so the time to live is capped at [`CachedValidator::MAX_TTL`].
+/// Expired validations are removed whenever a new one is cached, and the `expires_in` of a cached validation counts down while it's cached.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #[tokio::main]
+/// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+/// use twitch_oauth2::{tokens::CachedValidator, AccessToken};
+/// let validator = CachedValidator::new(std::time::Duration::from_secs(5 * 60));
+/// let client = reqwest::Client::builder()
+///     .redirect(reqwest::redirect::Policy::none())
+///     .build()?;
+/// let token = AccessToken::from("my_access_token");
+/// // only the first call sends a request to twitch
+/// let validated = validator.validate_cached(&client, &token).await?;
+/// let validated = validator.validate_cached(&client, &token).await?;
+/// # Ok(())}
+/// # fn main() {run();}
+/// ```
+pub struct CachedValidator {
+    ttl: Duration,
+    /// Validations by the token, with the time they were made
+    cache: Mutex<HashMap<AccessToken, (ValidatedToken, Instant)>>,
+    sink: Option<std::sync::Arc<dyn EventSink>>,
+}
+
+impl std::fmt::Debug for CachedValidator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CachedValidator")
+            .field("ttl", &self.ttl)
+            .field("cache", &self.cache)
+            .field("sink", &self.sink.is_some())
+            .finish()
+    }
+}
+
+impl CachedValidator {
+    /// The longest a validation is cached for.
+    pub const MAX_TTL: Duration = Duration::from_secs(60 * 60);
+
+    /// Create a new cache, where validations are valid for `ttl`. The ttl is capped at [`CachedValidator::MAX_TTL`].
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl: ttl.min(Self::MAX_TTL),
+            cache: Mutex::default(),
+            sink: None,
+        }
+    }
+
+    /// Send [`TokenEvent::Validated`] to `sink` when a token is validated with twitch, cached validations do not send events
+    pub fn with_event_sink(mut self, sink: impl EventSink + 'static) -> Self {
+        self.sink = Some(std::sync::Arc::new(sink));
+        self
+    }
+
+    /// The time a validation is cached for.
+    pub fn ttl(&self) -> Duration { self.ttl }
+
+    /// Validate the token, or return the cached validation if it was validated within the time to live.
+    ///
+    /// The `expires_in` of a cached validation is reduced by the time since it was validated. Errors are not cached. If the token is no longer authorized, it is removed from the cache.
+    pub async fn validate_cached<C>(
+        &self,
+        http_client: &C,
+        token: &AccessTokenRef,
+    ) -> Result<ValidatedToken, ValidationError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        if let Some(validated) = self.get(token) {
+            return Ok(validated);
+        }
+        match token.validate_token(http_client).await {
+            Ok(validated) => {
+                let now = Instant::now();
+                let mut cache = self.lock();
+                cache.retain(|_, (_, validated_at)| !self.is_expired(*validated_at, now));
+                cache.insert(token.to_owned(), (validated.clone(), now));
+                drop(cache);
+                if let Some(sink) = &self.sink {
+                    sink.event(&TokenEvent::Validated(validated.clone()));
+                }
+                Ok(validated)
+            }
+            Err(ValidationError::NotAuthorized) => {
+                self.invalidate(token);
+                Err(ValidationError::NotAuthorized)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Remove the cached validation for the token.
+    pub fn invalidate(&self, token: &AccessTokenRef) { self.lock().remove(token); }
+
+    /// Remove all cached validations.
+    pub fn clear(&self) { self.lock().clear() }
+
+    fn get(&self, token: &AccessTokenRef) -> Option<ValidatedToken> {
+        let now = Instant::now();
+        let mut cache = self.lock();
+        match cache.get(token) {
+            Some((validated, validated_at)) if !self.is_expired(*validated_at, now) => {
+                let mut validated = validated.clone();
+                let cached_for = now.saturating_duration_since(*validated_at);
+                validated.expires_in = validated
+                    .expires_in
+                    .map(|expires_in| expires_in.saturating_sub(cached_for));
+                Some(validated)
+            }
+            Some(_) => {
+                cache.remove(token);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn is_expired(&self, validated_at: Instant, now: Instant) -> bool {
+        now.saturating_duration_since(validated_at) >= self.ttl
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<AccessToken, (ValidatedToken, Instant)>> {
+        // a panic while holding the lock can't leave the map in an inconsistent state
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::MockClient;
+    use crate::AccessToken;
+
+    const VALIDATED: &str = r#"{"client_id":"wbmytr93xzw8zbg0p1izqyzzc5mbiz","login":"twitchdev","scopes":["channel:read:subscriptions"],"user_id":"141981764","expires_in":5520838}"#;
+
+    #[tokio::test]
+    async fn hit_and_miss() {
+        let client = MockClient::default()
+            .respond(200, VALIDATED)
+            .respond(200, VALIDATED);
+        let validator = CachedValidator::new(Duration::from_secs(60));
+        let token = AccessToken::from("token1");
+        let other = AccessToken::from("token2");
+
+        let validated = validator.validate_cached(&client, &token).await.unwrap();
+        assert_eq!(validated.login.unwrap().as_str(), "twitchdev");
+        validator.validate_cached(&client, &token).await.unwrap();
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+
+        validator.validate_cached(&client, &other).await.unwrap();
+        assert_eq!(client.requests.lock().unwrap().len(), 2);
+        assert!(!format!("{validator:?}").contains("token1"));
+    }
+
+    #[tokio::test]
+    async fn expiry() {
+        let client = MockClient::default()
+            .respond(200, VALIDATED)
+            .respond(200, VALIDATED)
+            .respond(401, r#"{"status":401,"message":"invalid access token"}"#)
+            .respond(200, VALIDATED);
+        let validator = CachedValidator::new(Duration::ZERO);
+        let token = AccessToken::from("token");
+
+        validator.validate_cached(&client, &token).await.unwrap();
+        validator.validate_cached(&client, &token).await.unwrap();
+        assert_eq!(client.requests.lock().unwrap().len(), 2);
+
+        assert!(matches!(
+            validator.validate_cached(&client, &token).await,
+            Err(ValidationError::NotAuthorized)
+        ));
+        validator.validate_cached(&client, &token).await.unwrap();
+        assert_eq!(client.requests.lock().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn events() {
+        let client = MockClient::default().respond(200, VALIDATED);
+        let events = std::sync::Arc::new(Mutex::new(vec![]));
+        let validator = CachedValidator::new(Duration::from_secs(60)).with_event_sink({
+            let events = events.clone();
+            move |event: &TokenEvent| events.lock().unwrap().push(event.clone())
+        });
+        let token = AccessToken::from("token");
+        validator.validate_cached(&client, &token).await.unwrap();
+        validator.validate_cached(&client, &token).await.unwrap();
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(
+            matches!(&events[0], TokenEvent::Validated(v) if v.login.as_ref().unwrap().as_str() == "twitchdev")
+        );
+    }
+
+    #[tokio::test]
+    async fn sweep_and_expires_in() {
+        let client = MockClient::default()
+            .respond(200, VALIDATED)
+            .respond(200, VALIDATED);
+        let validator = CachedValidator::new(Duration::from_secs(60));
+        let token = AccessToken::from("token1");
+        validator.validate_cached(&client, &token).await.unwrap();
+
+        // pretend the validation was made a while ago
+        let validated_at = Instant::now() - Duration::from_secs(30);
+        validator
+            .lock()
+            .values_mut()
+            .for_each(|(_, at)| *at = validated_at);
+        let validated = validator.validate_cached(&client, &token).await.unwrap();
+        assert!(validated.expires_in.unwrap() <= Duration::from_secs(5520838 - 30));
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+
+        // expired validations of other tokens are removed when caching a new one
+        let validated_at = Instant::now() - Duration::from_secs(60);
+        validator
+            .lock()
+            .values_mut()
+            .for_each(|(_, at)| *at = validated_at);
+        validator
+            .validate_cached(&client, &AccessToken::from("token2"))
+            .await
+            .unwrap();
+        assert_eq!(validator.lock().len(), 1);
+        assert!(validator.get(&token).is_none());
+    }
+
+    #[test]
+    fn ttl_capped() {
+        assert_eq!(
+            CachedValidator::new(Duration::from_secs(60 * 60 * 24)).ttl(),
+            CachedValidator::MAX_TTL
+        );
+    }
+}
@@ -23,6 +23,86 @@ pub enum ValidationError<RE: std::error::Error + Send + Sync + 'static> {
     // TODO: This should be in it's own error enum specifically for UserToken validation
     /// validation did not return a login when it was expected
     NoLogin,
+    /// {0}
+    ClientIdMismatch(#[from] ClientIdMismatch),
+}
+
+/// Error for [AuthorizationCode::parse][crate::AuthorizationCode::parse]
+#[derive(thiserror::Error, Debug, displaydoc::Display, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InvalidAuthorizationCode {
+    /// authorization code is empty
+    Empty,
+    /// authorization code contains an invalid character {0:?}
+    InvalidCharacter(char),
+}
+
+/// Errors for [UserTokenBuilder::verify_hmac_state][crate::tokens::UserTokenBuilder::verify_hmac_state]
+#[derive(thiserror::Error, Debug, displaydoc::Display, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+#[cfg(feature = "hmac_state")]
+pub enum HmacStateError {
+    /// state is not a valid hmac state
+    Malformed,
+    /// state was not signed with this key, or has been tampered with
+    InvalidMac,
+    /// state has expired
+    Expired,
+}
+
+/// Errors for importing tokens with [ImportedToken][crate::tokens::import::ImportedToken]
+#[derive(thiserror::Error, Debug, displaydoc::Display)]
+#[non_exhaustive]
+#[cfg(feature = "import")]
+pub enum ImportError {
+    /// could not parse export
+    Json(#[from] serde_json::Error),
+    /// export is for provider `{0}`, not twitch
+    NotTwitch(String),
+    /// export does not contain an access token
+    MissingAccessToken,
+}
+
+/// Error for [ValidatedToken::assert_client_id][crate::ValidatedToken::assert_client_id]
+#[derive(thiserror::Error, Debug, displaydoc::Display, Clone, PartialEq, Eq)]
+/// token was issued for client id `{found}`, expected `{expected}`
+pub struct ClientIdMismatch {
+    /// The client id that was expected
+    pub expected: crate::types::ClientId,
+    /// The client id the token was issued for
+    pub found: crate::types::ClientId,
+}
+
+impl<RE: std::error::Error + Send + Sync + 'static> ValidationError<RE> {
+    /// Returns whether or not validation could succeed if retried later, for example after a network error.
+    ///
+    /// The token should be kept when this is `true`, see [`RequestParseError::is_retryable`](crate::RequestParseError::is_retryable).
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ValidationError::Request(_) => true,
+            ValidationError::RequestParseError(e) => e.is_retryable(),
+            ValidationError::NotAuthorized
+            | ValidationError::NoLogin
+            | ValidationError::ClientIdMismatch(_) => false,
+        }
+    }
+
+    /// Returns whether or not the token is unusable, and the user needs to authorize again.
+    pub fn is_fatal(&self) -> bool { !self.is_retryable() }
+
+    /// Get a status code to respond with when this error happens in a web handler.
+    ///
+    /// An unauthorized token, or a token issued for another client, is [`401 Unauthorized`](http::StatusCode::UNAUTHORIZED), see [`RequestParseError::http_status`](crate::RequestParseError::http_status) for the rest.
+    pub fn http_status(&self) -> http::StatusCode {
+        match self {
+            ValidationError::NotAuthorized | ValidationError::ClientIdMismatch(_) => {
+                http::StatusCode::UNAUTHORIZED
+            }
+            ValidationError::RequestParseError(e) => e.http_status(),
+            ValidationError::Request(_) => http::StatusCode::BAD_GATEWAY,
+            ValidationError::NoLogin => http::StatusCode::BAD_REQUEST,
+        }
+    }
 }
 
 impl ValidationError<std::convert::Infallible> {
@@ -32,11 +112,39 @@ impl ValidationError<std::convert::Infallible> {
             ValidationError::NotAuthorized => ValidationError::NotAuthorized,
             ValidationError::RequestParseError(e) => ValidationError::RequestParseError(e),
             ValidationError::NoLogin => ValidationError::NoLogin,
+            ValidationError::ClientIdMismatch(e) => ValidationError::ClientIdMismatch(e),
             ValidationError::Request(_) => unreachable!(),
         }
     }
 }
 
+/// Errors for [UserToken::get_user_info][crate::UserToken::get_user_info] and [UserInfo::from_response][crate::tokens::UserInfo::from_response]
+#[derive(thiserror::Error, Debug, displaydoc::Display)]
+#[cfg(feature = "helix")]
+pub enum UserInfoError<RE: std::error::Error + Send + Sync + 'static> {
+    /// could not parse response when getting user info
+    RequestParseError(#[from] crate::RequestParseError),
+    /// failed to request user info
+    Request(#[source] RE),
+    /// twitch did not return a user for the token
+    NoUser,
+    /// token or client id can't be sent in a header
+    InvalidHeader(#[from] http::header::InvalidHeaderValue),
+}
+
+#[cfg(feature = "helix")]
+impl UserInfoError<std::convert::Infallible> {
+    /// Convert this error from a infallible to another
+    pub fn into_other<RE: std::error::Error + Send + Sync + 'static>(self) -> UserInfoError<RE> {
+        match self {
+            UserInfoError::RequestParseError(e) => UserInfoError::RequestParseError(e),
+            UserInfoError::NoUser => UserInfoError::NoUser,
+            UserInfoError::InvalidHeader(e) => UserInfoError::InvalidHeader(e),
+            UserInfoError::Request(_) => unreachable!(),
+        }
+    }
+}
+
 /// Errors for [AccessToken::revoke_token][crate::AccessTokenRef::revoke_token]
 #[allow(missing_docs)]
 #[derive(thiserror::Error, Debug, displaydoc::Display)]
@@ -65,6 +173,45 @@ pub enum RefreshTokenError<RE: std::error::Error + Send + Sync + 'static> {
     NoRefreshToken,
     /// no expiration found on new token
     NoExpiration,
+    /// token is from the implicit flow and can not be refreshed
+    Implicit,
+    /// token was refreshed too recently, retry after {retry_after:?}
+    TooSoon {
+        /// Time until the token can be refreshed again
+        retry_after: std::time::Duration,
+    },
+    /// twitch rate limited the refresh, retry after {retry_after:?}
+    RateLimited {
+        /// Time to wait before retrying, from the `Retry-After` header
+        retry_after: std::time::Duration,
+    },
+    /// could not validate token
+    ValidationError(#[from] ValidationError<RE>),
+}
+
+#[cfg(feature = "client")]
+impl<RE: std::error::Error + Send + Sync + 'static> RefreshTokenError<RE> {
+    /// Get a status code to respond with when this error happens in a web handler.
+    ///
+    /// A token that can't be refreshed is [`401 Unauthorized`](http::StatusCode::UNAUTHORIZED), as the user needs to authorize again.
+    /// A missing client secret is [`500 Internal Server Error`](http::StatusCode::INTERNAL_SERVER_ERROR),
+    /// and a [throttled](crate::UserToken::set_min_refresh_interval) or rate limited refresh is [`503 Service Unavailable`](http::StatusCode::SERVICE_UNAVAILABLE), see [`RequestParseError::http_status`](crate::RequestParseError::http_status) for the rest.
+    pub fn http_status(&self) -> http::StatusCode {
+        match self {
+            RefreshTokenError::RequestError(_) | RefreshTokenError::NoExpiration => {
+                http::StatusCode::BAD_GATEWAY
+            }
+            RefreshTokenError::RequestParseError(e) => e.http_status(),
+            RefreshTokenError::NoClientSecretFound => http::StatusCode::INTERNAL_SERVER_ERROR,
+            RefreshTokenError::NoRefreshToken | RefreshTokenError::Implicit => {
+                http::StatusCode::UNAUTHORIZED
+            }
+            RefreshTokenError::TooSoon { .. } | RefreshTokenError::RateLimited { .. } => {
+                http::StatusCode::SERVICE_UNAVAILABLE
+            }
+            RefreshTokenError::ValidationError(e) => e.http_status(),
+        }
+    }
 }
 
 /// Errors for [`UserTokenBuilder::get_user_token`](crate::tokens::UserTokenBuilder::get_user_token) and [`UserToken::mock_token`](crate::tokens::UserToken::mock_token)
@@ -77,8 +224,60 @@ pub enum UserTokenExchangeError<RE: std::error::Error + Send + Sync + 'static> {
     RequestParseError(#[from] crate::RequestParseError),
     /// state CSRF does not match when exchanging user token
     StateMismatch,
+    /// code or state is not valid percent-encoded UTF-8
+    InvalidEncoding(#[source] std::str::Utf8Error),
     /// could not get validation for user token
     ValidationError(#[from] ValidationError<RE>),
+    /// twitch returned an error: {error:?} - {description:?}
+    TwitchError {
+        /// Error type
+        error: Option<String>,
+        /// Description of error
+        description: Option<String>,
+    },
+    /// no code was returned by twitch
+    MissingCode,
+    /// the user denied access: {description:?}
+    AccessDenied {
+        /// Description of error
+        description: Option<String>,
+    },
+    /// the user has to log in or authorize, retry without [`Prompt::None`](crate::tokens::Prompt::None): {error} - {description:?}
+    InteractionRequired {
+        /// Error type, e.g. `interaction_required` or `login_required`
+        error: String,
+        /// Description of error
+        description: Option<String>,
+    },
+}
+
+#[cfg(feature = "client")]
+impl<RE: std::error::Error + Send + Sync + 'static> UserTokenExchangeError<RE> {
+    /// Get a status code to respond with when this error happens in a web handler.
+    ///
+    /// A wrong state or missing code is [`400 Bad Request`](http::StatusCode::BAD_REQUEST), the user denying access is [`403 Forbidden`](http::StatusCode::FORBIDDEN)
+    /// and a failed request to twitch is [`502 Bad Gateway`](http::StatusCode::BAD_GATEWAY), see [`RequestParseError::http_status`](crate::RequestParseError::http_status) for the rest.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::tokens::errors::UserTokenExchangeError;
+    /// let error = UserTokenExchangeError::<std::io::Error>::StateMismatch;
+    /// assert_eq!(error.http_status(), http::StatusCode::BAD_REQUEST);
+    /// ```
+    pub fn http_status(&self) -> http::StatusCode {
+        match self {
+            UserTokenExchangeError::RequestError(_) => http::StatusCode::BAD_GATEWAY,
+            UserTokenExchangeError::RequestParseError(e) => e.http_status(),
+            UserTokenExchangeError::StateMismatch
+            | UserTokenExchangeError::InvalidEncoding(_)
+            | UserTokenExchangeError::MissingCode
+            | UserTokenExchangeError::TwitchError { .. } => http::StatusCode::BAD_REQUEST,
+            UserTokenExchangeError::ValidationError(e) => e.http_status(),
+            UserTokenExchangeError::AccessDenied { .. } => http::StatusCode::FORBIDDEN,
+            UserTokenExchangeError::InteractionRequired { .. } => http::StatusCode::UNAUTHORIZED,
+        }
+    }
 }
 
 /// Errors for [ImplicitUserTokenBuilder::get_user_token][crate::tokens::ImplicitUserTokenBuilder::get_user_token]
@@ -93,8 +292,234 @@ pub enum ImplicitUserTokenExchangeError<RE: std::error::Error + Send + Sync + 's
         /// Description of error
         description: Option<String>,
     },
+    /// the user denied access: {description:?}
+    AccessDenied {
+        /// Description of error
+        description: Option<String>,
+    },
+    /// the user has to log in or authorize, retry without [`Prompt::None`](crate::tokens::Prompt::None): {error} - {description:?}
+    InteractionRequired {
+        /// Error type, e.g. `interaction_required` or `login_required`
+        error: String,
+        /// Description of error
+        description: Option<String>,
+    },
     /// state CSRF does not match
     StateMismatch,
+    /// an id token was requested, but twitch did not return one
+    MissingIdToken,
     /// could not get validation for token
     ValidationError(#[from] ValidationError<RE>),
 }
+
+#[cfg(feature = "client")]
+impl<RE: std::error::Error + Send + Sync + 'static> ImplicitUserTokenExchangeError<RE> {
+    /// Get a status code to respond with when this error happens in a web handler, see [`UserTokenExchangeError::http_status`]
+    pub fn http_status(&self) -> http::StatusCode {
+        match self {
+            ImplicitUserTokenExchangeError::AccessDenied { .. } => http::StatusCode::FORBIDDEN,
+            ImplicitUserTokenExchangeError::InteractionRequired { .. } => {
+                http::StatusCode::UNAUTHORIZED
+            }
+            ImplicitUserTokenExchangeError::TwitchError { .. }
+            | ImplicitUserTokenExchangeError::StateMismatch
+            | ImplicitUserTokenExchangeError::MissingIdToken => http::StatusCode::BAD_REQUEST,
+            ImplicitUserTokenExchangeError::ValidationError(e) => e.http_status(),
+        }
+    }
+}
+
+/// Error for [`UserTokenBuilder::try_generate_url`](crate::tokens::UserTokenBuilder::try_generate_url)
+#[derive(thiserror::Error, Debug, displaydoc::Display)]
+/// scopes were rejected by the scope validator: {0}
+pub struct ScopesRejected(pub String);
+
+/// Error for [`UserTokenBuilder::with_correlation_id`](crate::tokens::UserTokenBuilder::with_correlation_id)
+#[derive(thiserror::Error, Debug, displaydoc::Display)]
+/// the CSRF token contains `:`, which separates it from the correlation id
+pub struct CsrfContainsSeparator;
+
+/// Errors for [`UserTokenBuilder::try_new`](crate::tokens::UserTokenBuilder::try_new), [`ImplicitUserTokenBuilder::try_new`](crate::tokens::ImplicitUserTokenBuilder::try_new), [`UserTokenBuilder::check_redirect`](crate::tokens::UserTokenBuilder::check_redirect) and [`UserTokenBuilder::validate_redirect`](crate::tokens::UserTokenBuilder::validate_redirect)
+#[derive(thiserror::Error, Debug, displaydoc::Display)]
+#[non_exhaustive]
+pub enum RedirectUrlError {
+    /// could not parse redirect url
+    Parse(#[from] url::ParseError),
+    /// redirect url `{given}` has an empty path and would be sent as `{sent}`, register `{sent}` as the redirect url or use a non-empty path like `{given}/twitch/register`
+    TrailingSlash {
+        /// The redirect url as given
+        given: String,
+        /// The redirect url as it will be sent to twitch
+        sent: String,
+    },
+    /// redirect url `{url}` has a fragment, which twitch does not allow
+    Fragment {
+        /// The redirect url as given
+        url: String,
+    },
+    /// redirect url `{found}` does not match the redirect url `{expected}` used to authorize
+    Mismatch {
+        /// The redirect url used to authorize
+        expected: String,
+        /// The redirect url that was checked
+        found: String,
+    },
+    /// redirect url `{url}` is not in the allowlist
+    NotAllowed {
+        /// The redirect url that was checked
+        url: String,
+    },
+}
+
+#[cfg(test)]
+#[cfg(feature = "client")]
+mod tests {
+    use super::*;
+    use http::StatusCode;
+
+    type Error = std::io::Error;
+
+    fn request_error() -> Error { std::io::Error::from(std::io::ErrorKind::ConnectionReset) }
+
+    fn twitch_error(status: u16) -> crate::RequestParseError {
+        crate::RequestParseError::TwitchError(crate::id::TwitchTokenErrorResponse {
+            status: StatusCode::from_u16(status).unwrap(),
+            message: "message".to_owned(),
+            error: None,
+        })
+    }
+
+    #[test]
+    fn request_parse_error_http_status() {
+        assert_eq!(twitch_error(400).http_status(), StatusCode::BAD_REQUEST);
+        assert_eq!(twitch_error(429).http_status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(twitch_error(500).http_status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(
+            crate::RequestParseError::ServiceUnavailable(StatusCode::BAD_GATEWAY).http_status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            crate::RequestParseError::Other(StatusCode::NOT_FOUND).http_status(),
+            StatusCode::BAD_GATEWAY
+        );
+        assert_eq!(
+            crate::RequestParseError::NotJson {
+                found: "text/html".to_owned()
+            }
+            .http_status(),
+            StatusCode::BAD_GATEWAY
+        );
+    }
+
+    #[test]
+    fn validation_error_http_status() {
+        for (error, status) in [
+            (ValidationError::NotAuthorized, StatusCode::UNAUTHORIZED),
+            (
+                ValidationError::RequestParseError(twitch_error(400)),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                ValidationError::Request(request_error()),
+                StatusCode::BAD_GATEWAY,
+            ),
+            (ValidationError::NoLogin, StatusCode::BAD_REQUEST),
+        ] {
+            assert_eq!(error.http_status(), status, "{error:?}");
+        }
+    }
+
+    #[test]
+    fn refresh_token_error_http_status() {
+        for (error, status) in [
+            (
+                RefreshTokenError::<Error>::RequestError(request_error()),
+                StatusCode::BAD_GATEWAY,
+            ),
+            (
+                RefreshTokenError::RequestParseError(twitch_error(400)),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                RefreshTokenError::NoClientSecretFound,
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            (RefreshTokenError::NoRefreshToken, StatusCode::UNAUTHORIZED),
+            (RefreshTokenError::NoExpiration, StatusCode::BAD_GATEWAY),
+            (RefreshTokenError::Implicit, StatusCode::UNAUTHORIZED),
+            (
+                RefreshTokenError::ValidationError(ValidationError::NotAuthorized),
+                StatusCode::UNAUTHORIZED,
+            ),
+        ] {
+            assert_eq!(error.http_status(), status, "{error:?}");
+        }
+    }
+
+    #[test]
+    fn user_token_exchange_error_http_status() {
+        let twitch = |error: &str| UserTokenExchangeError::<Error>::TwitchError {
+            error: Some(error.to_owned()),
+            description: None,
+        };
+        for (error, status) in [
+            (
+                UserTokenExchangeError::RequestError(request_error()),
+                StatusCode::BAD_GATEWAY,
+            ),
+            (
+                UserTokenExchangeError::RequestParseError(twitch_error(400)),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                UserTokenExchangeError::StateMismatch,
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                UserTokenExchangeError::ValidationError(ValidationError::NotAuthorized),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                UserTokenExchangeError::AccessDenied { description: None },
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                UserTokenExchangeError::InteractionRequired {
+                    error: "login_required".to_owned(),
+                    description: None,
+                },
+                StatusCode::UNAUTHORIZED,
+            ),
+            (twitch("invalid_scope"), StatusCode::BAD_REQUEST),
+            (UserTokenExchangeError::MissingCode, StatusCode::BAD_REQUEST),
+        ] {
+            assert_eq!(error.http_status(), status, "{error:?}");
+        }
+    }
+
+    #[test]
+    fn implicit_user_token_exchange_error_http_status() {
+        for (error, status) in [
+            (
+                ImplicitUserTokenExchangeError::<Error>::AccessDenied { description: None },
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                ImplicitUserTokenExchangeError::StateMismatch,
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                ImplicitUserTokenExchangeError::MissingIdToken,
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                ImplicitUserTokenExchangeError::ValidationError(ValidationError::Request(
+                    request_error(),
+                )),
+                StatusCode::BAD_GATEWAY,
+            ),
+        ] {
+            assert_eq!(error.http_status(), status, "{error:?}");
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use twitch_types::UserId;
+
+use super::ValidatedToken;
+
+/// Events from the token helpers, delivered to an [`EventSink`]
+///
+/// See [`SingleFlightRefresher::with_event_sink`](super::SingleFlightRefresher::with_event_sink) and [`CachedValidator::with_event_sink`](super::CachedValidator::with_event_sink)
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum TokenEvent {
+    /// A token was refreshed
+    Refreshed {
+        /// User id of the token, if any
+        user_id: Option<UserId>,
+    },
+    /// Refreshing a token failed
+    RefreshFailed {
+        /// User id of the token, if any
+        user_id: Option<UserId>,
+        /// The error that occurred
+        error: String,
+    },
+    /// A token was validated with twitch
+    Validated(ValidatedToken),
+    /// A token was revoked
+    Revoked {
+        /// User id of the token, if any
+        user_id: Option<UserId>,
+    },
+}
+
+/// Receiver of [`TokenEvent`]s, for example for logging or metrics.
+///
+/// Implemented for closures taking a `&TokenEvent`.
+///
+/// # Examples
+///
+/// ```rust
+/// use twitch_oauth2::tokens::{EventSink, TokenEvent};
+/// let sink = |event: &TokenEvent| println!("token event: {event:?}");
+/// sink.event(&TokenEvent::Refreshed { user_id: None });
+/// ```
+pub trait EventSink: Send + Sync {
+    /// Handle an event
+    fn event(&self, event: &TokenEvent);
+}
+
+impl<F> EventSink for F
+where F: Fn(&TokenEvent) + Send + Sync
+{
+    fn event(&self, event: &TokenEvent) { self(event) }
+}
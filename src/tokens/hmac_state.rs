@@ -0,0 +1,226 @@
+//! Stateless `state` parameters authenticated with a HMAC, see [`UserTokenBuilder::with_hmac_state`]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::Engine as _;
+use hmac::Mac as _;
+
+use super::errors::HmacStateError;
+use super::UserTokenBuilder;
+use crate::types::CsrfToken;
+
+type HmacSha256 = hmac::Hmac<sha2::Sha256>;
+
+const NONCE_LEN: usize = 16;
+const TIMESTAMP_LEN: usize = 8;
+const MAC_LEN: usize = 32;
+
+fn mac(key: &[u8], payload: &[u8], nonce: &[u8], timestamp: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("hmac accepts keys of any length");
+    mac.update(payload);
+    mac.update(nonce);
+    mac.update(timestamp);
+    mac
+}
+
+fn sign(key: &[u8], payload: &[u8], now: SystemTime) -> CsrfToken {
+    use rand::Rng as _;
+
+    let nonce: [u8; NONCE_LEN] = rand::thread_rng().gen();
+    let timestamp = now
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        .to_be_bytes();
+    let tag = mac(key, payload, &nonce, &timestamp)
+        .finalize()
+        .into_bytes();
+
+    let mut state = Vec::with_capacity(NONCE_LEN + TIMESTAMP_LEN + payload.len() + MAC_LEN);
+    state.extend_from_slice(&nonce);
+    state.extend_from_slice(&timestamp);
+    state.extend_from_slice(payload);
+    state.extend_from_slice(&tag);
+    CsrfToken::new(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(state))
+}
+
+fn verify(
+    key: &[u8],
+    state: &str,
+    max_age: Duration,
+    now: SystemTime,
+) -> Result<Vec<u8>, HmacStateError> {
+    let state = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(state)
+        .map_err(|_| HmacStateError::Malformed)?;
+    if state.len() < NONCE_LEN + TIMESTAMP_LEN + MAC_LEN {
+        return Err(HmacStateError::Malformed);
+    }
+    let (nonce, rest) = state.split_at(NONCE_LEN);
+    let (timestamp, rest) = rest.split_at(TIMESTAMP_LEN);
+    let (payload, tag) = rest.split_at(rest.len() - MAC_LEN);
+
+    // constant-time comparison
+    mac(key, payload, nonce, timestamp)
+        .verify_slice(tag)
+        .map_err(|_| HmacStateError::InvalidMac)?;
+
+    let issued = UNIX_EPOCH
+        + Duration::from_secs(u64::from_be_bytes(
+            timestamp.try_into().expect("timestamp should be 8 bytes"),
+        ));
+    // a state issued in the future is only possible with clock skew between servers
+    if now.duration_since(issued).unwrap_or_default() > max_age {
+        return Err(HmacStateError::Expired);
+    }
+    Ok(payload.to_vec())
+}
+
+impl UserTokenBuilder {
+    /// Use a state that is authenticated with a HMAC instead of a random [CSRF token](UserTokenBuilder::set_csrf), so it can be verified without storing it.
+    ///
+    /// The state is `HMAC-SHA256(key, payload || nonce || timestamp)` together with the `payload`, a random nonce and the current time,
+    /// and is verified with [`UserTokenBuilder::verify_hmac_state`] when twitch redirects the user back.
+    ///
+    /// The payload is not encrypted, anyone can read it. Keep it small, as it's sent in the url.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use twitch_oauth2::tokens::UserTokenBuilder;
+    ///
+    /// let key = b"a secret key only known by the server";
+    /// let mut builder = UserTokenBuilder::new(
+    ///     "myclientid",
+    ///     "myclientsecret",
+    ///     url::Url::parse("http://localhost/twitch/register")?,
+    /// )
+    /// .with_hmac_state(key, b"/dashboard");
+    /// let url = builder.generate_url();
+    ///
+    /// // later, in the handler for the redirect, possibly on another server
+    /// # let state = url.query_pairs().find(|(k, _)| k == "state").unwrap().1.into_owned();
+    /// let payload = UserTokenBuilder::verify_hmac_state(key, &state, Duration::from_secs(600))?;
+    /// assert_eq!(payload, b"/dashboard");
+    /// let builder = UserTokenBuilder::with_csrf(
+    ///     "myclientid",
+    ///     "myclientsecret",
+    ///     url::Url::parse("http://localhost/twitch/register")?,
+    ///     state,
+    /// );
+    /// // complete the flow with `builder.complete_from_query`
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_hmac_state(self, key: &[u8], payload: &[u8]) -> Self {
+        self.set_csrf(Some(sign(key, payload, SystemTime::now())))
+    }
+
+    /// Verify a state made with [`UserTokenBuilder::with_hmac_state`], returning its payload.
+    ///
+    /// States older than `max_age` are rejected with [`HmacStateError::Expired`].
+    /// Within `max_age`, a state can be replayed, use a short `max_age` or store the used states if that's a concern.
+    pub fn verify_hmac_state(
+        key: &[u8],
+        state: &str,
+        max_age: Duration,
+    ) -> Result<Vec<u8>, HmacStateError> {
+        verify(key, state, max_age, SystemTime::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"key";
+
+    #[test]
+    fn valid() {
+        let now = SystemTime::now();
+        let state = sign(KEY, b"payload", now);
+        assert_eq!(
+            verify(KEY, state.secret(), Duration::from_secs(60), now).unwrap(),
+            b"payload"
+        );
+        // nonces make every state unique
+        assert_ne!(state, sign(KEY, b"payload", now));
+        // empty payloads are fine
+        let state = sign(KEY, b"", now);
+        assert_eq!(
+            verify(KEY, state.secret(), Duration::from_secs(60), now).unwrap(),
+            b""
+        );
+    }
+
+    #[test]
+    fn tampered() {
+        let now = SystemTime::now();
+        let state = sign(KEY, b"payload", now);
+        let max_age = Duration::from_secs(60);
+
+        assert_eq!(
+            verify(b"other key", state.secret(), max_age, now),
+            Err(HmacStateError::InvalidMac)
+        );
+
+        let mut bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(state.secret())
+            .unwrap();
+        bytes[NONCE_LEN + TIMESTAMP_LEN] ^= 1;
+        let tampered = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&bytes);
+        assert_eq!(
+            verify(KEY, &tampered, max_age, now),
+            Err(HmacStateError::InvalidMac)
+        );
+
+        assert_eq!(
+            verify(KEY, "not a state", max_age, now),
+            Err(HmacStateError::Malformed)
+        );
+        assert_eq!(
+            verify(KEY, "c2hvcnQ", max_age, now),
+            Err(HmacStateError::Malformed)
+        );
+    }
+
+    #[test]
+    fn replayed() {
+        let issued = SystemTime::now();
+        let state = sign(KEY, b"payload", issued);
+        let max_age = Duration::from_secs(600);
+
+        assert!(verify(
+            KEY,
+            state.secret(),
+            max_age,
+            issued + Duration::from_secs(599)
+        )
+        .is_ok());
+        assert_eq!(
+            verify(
+                KEY,
+                state.secret(),
+                max_age,
+                issued + Duration::from_secs(601)
+            ),
+            Err(HmacStateError::Expired)
+        );
+    }
+
+    #[test]
+    fn builder() {
+        let builder = UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        )
+        .with_hmac_state(KEY, b"payload");
+        let state = builder.csrf.clone().unwrap();
+        assert!(builder.csrf_is_valid(state.secret()));
+        assert_eq!(
+            UserTokenBuilder::verify_hmac_state(KEY, state.secret(), Duration::from_secs(60))
+                .unwrap(),
+            b"payload"
+        );
+    }
+}
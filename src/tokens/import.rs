@@ -0,0 +1,193 @@
+//! Import tokens exported from other tools, to migrate to this crate
+//!
+//! Parse the export with one of the constructors on [`ImportedToken`], and turn it into a [`UserToken`](crate::UserToken) with [`ImportedToken::into_user_token`].
+use super::errors::ImportError;
+#[cfg(feature = "client")]
+use super::{errors::ValidationError, UserToken};
+#[cfg(feature = "client")]
+use crate::{client::Client, ClientSecret};
+use crate::{AccessToken, RefreshToken};
+
+/// A token imported from another tool
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ImportedToken {
+    /// The access token
+    pub access_token: AccessToken,
+    /// The refresh token, if the export contained one
+    pub refresh_token: Option<RefreshToken>,
+}
+
+#[derive(serde::Deserialize)]
+struct StreamlabsExport {
+    platforms: StreamlabsPlatforms,
+}
+
+#[derive(serde::Deserialize)]
+struct StreamlabsPlatforms {
+    twitch: StreamlabsPlatform,
+}
+
+#[derive(serde::Deserialize)]
+struct StreamlabsPlatform {
+    token: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StreamElementsExport {
+    provider: String,
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+}
+
+impl ImportedToken {
+    /// Parse the twitch token from a Streamlabs export
+    ///
+    /// ```json
+    /// { "platforms": { "twitch": { "token": "...", "username": "...", "id": "..." } } }
+    /// ```
+    ///
+    /// Streamlabs does not export a refresh token.
+    pub fn from_streamlabs(json: &str) -> Result<Self, ImportError> {
+        let export: StreamlabsExport = serde_json::from_str(json)?;
+        Ok(Self {
+            access_token: access_token(export.platforms.twitch.token)?,
+            refresh_token: None,
+        })
+    }
+
+    /// Parse the token from a StreamElements export
+    ///
+    /// ```json
+    /// { "provider": "twitch", "accessToken": "...", "refreshToken": "..." }
+    /// ```
+    pub fn from_streamelements(json: &str) -> Result<Self, ImportError> {
+        let export: StreamElementsExport = serde_json::from_str(json)?;
+        if !export.provider.eq_ignore_ascii_case("twitch") {
+            return Err(ImportError::NotTwitch(export.provider));
+        }
+        Ok(Self {
+            access_token: access_token(export.access_token)?,
+            refresh_token: export
+                .refresh_token
+                .filter(|t| !t.is_empty())
+                .map(RefreshToken::new),
+        })
+    }
+
+    /// Validate the imported token and create a [`UserToken`] with [`UserToken::from_existing`]
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # #[tokio::main]
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+    /// use twitch_oauth2::tokens::import::ImportedToken;
+    ///
+    /// let client = reqwest::Client::builder()
+    ///     .redirect(reqwest::redirect::Policy::none())
+    ///     .build()?;
+    /// let export = std::fs::read_to_string("streamelements.json")?;
+    /// let token = ImportedToken::from_streamelements(&export)?
+    ///     .into_user_token(&client, None)
+    ///     .await?;
+    /// # Ok(())}
+    /// # fn main() {run();}
+    /// ```
+    #[cfg(feature = "client")]
+    pub async fn into_user_token<C>(
+        self,
+        http_client: &C,
+        client_secret: impl Into<Option<ClientSecret>>,
+    ) -> Result<UserToken, ValidationError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        UserToken::from_existing(
+            http_client,
+            self.access_token,
+            self.refresh_token,
+            client_secret,
+        )
+        .await
+    }
+}
+
+/// Other tools commonly store the token for IRC, with a `oauth:` prefix
+fn access_token(token: String) -> Result<AccessToken, ImportError> {
+    let token = match token.strip_prefix("oauth:") {
+        Some(token) => token.to_owned(),
+        None => token,
+    };
+    if token.is_empty() {
+        return Err(ImportError::MissingAccessToken);
+    }
+    Ok(AccessToken::new(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streamlabs() {
+        let token = ImportedToken::from_streamlabs(
+            r#"{"widgetToken":"abc","platforms":{"twitch":{"type":"twitch","username":"login","token":"oauth:accesstoken","id":"1234","channelId":"1234"}},"primary":"twitch"}"#,
+        )
+        .unwrap();
+        assert_eq!(token.access_token.secret(), "accesstoken");
+        assert!(token.refresh_token.is_none());
+
+        assert!(matches!(
+            ImportedToken::from_streamlabs(r#"{"platforms":{"youtube":{"token":"abc"}}}"#),
+            Err(ImportError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn streamelements() {
+        let token = ImportedToken::from_streamelements(
+            r#"{"_id":"5f1e","provider":"twitch","accessToken":"accesstoken","refreshToken":"refreshtoken","username":"login"}"#,
+        )
+        .unwrap();
+        assert_eq!(token.access_token.secret(), "accesstoken");
+        assert_eq!(token.refresh_token.unwrap().secret(), "refreshtoken");
+
+        let token = ImportedToken::from_streamelements(
+            r#"{"provider":"twitch","accessToken":"accesstoken","refreshToken":""}"#,
+        )
+        .unwrap();
+        assert!(token.refresh_token.is_none());
+
+        assert!(matches!(
+            ImportedToken::from_streamelements(
+                r#"{"provider":"youtube","accessToken":"accesstoken"}"#
+            ),
+            Err(ImportError::NotTwitch(p)) if p == "youtube"
+        ));
+        assert!(matches!(
+            ImportedToken::from_streamelements(r#"{"provider":"twitch","accessToken":"oauth:"}"#),
+            Err(ImportError::MissingAccessToken)
+        ));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn into_user_token() {
+        let client = crate::client::MockClient::default().respond(
+            200,
+            r#"{"client_id":"clientid","login":"login","scopes":["chat:read"],"user_id":"1234","expires_in":3600}"#,
+        );
+        let token = ImportedToken::from_streamelements(
+            r#"{"provider":"twitch","accessToken":"accesstoken","refreshToken":"refreshtoken"}"#,
+        )
+        .unwrap()
+        .into_user_token(&client, ClientSecret::from("secret"))
+        .await
+        .unwrap();
+        assert_eq!(token.login.as_str(), "login");
+        assert_eq!(token.refresh_token.unwrap().secret(), "refreshtoken");
+    }
+}
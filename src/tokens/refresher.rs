@@ -0,0 +1,134 @@
+//! Refreshing tokens with only the client credentials, see [`Refresher`]
+#[cfg(feature = "client")]
+use super::errors::RefreshTokenError;
+#[cfg(feature = "client")]
+use crate::client::Client;
+use crate::types::{ClientId, ClientSecret, RefreshTokenRef};
+
+/// Refreshes tokens with only the client id and client secret, for example in a background job that stores the refresh tokens.
+///
+/// Unlike [`TwitchToken::refresh_token`](crate::TwitchToken::refresh_token), this doesn't need a [`UserToken`](crate::UserToken), and returns the response from twitch as is.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #[tokio::main]
+/// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+/// use twitch_oauth2::{tokens::Refresher, RefreshToken};
+/// let refresher = Refresher::new("myclientid", "myclientsecret");
+/// let client = reqwest::Client::builder()
+///     .redirect(reqwest::redirect::Policy::none())
+///     .build()?;
+/// let response = refresher
+///     .refresh(&client, &RefreshToken::from("stored refresh token"))
+///     .await?;
+/// // store `response.refresh_token` and use `response.access_token`
+/// # Ok(())}
+/// # fn main() {run();}
+/// ```
+#[derive(Clone, Debug)]
+pub struct Refresher {
+    client_id: ClientId,
+    client_secret: ClientSecret,
+    strict_token_type: bool,
+}
+
+impl Refresher {
+    /// Create a new [`Refresher`]
+    pub fn new(client_id: impl Into<ClientId>, client_secret: impl Into<ClientSecret>) -> Self {
+        Self {
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+            strict_token_type: false,
+        }
+    }
+
+    /// Reject responses with a `token_type` other than `bearer`, see [`TwitchTokenResponse::from_response_strict`](crate::id::TwitchTokenResponse::from_response_strict)
+    pub fn strict_token_type(mut self, strict_token_type: bool) -> Self {
+        self.strict_token_type = strict_token_type;
+        self
+    }
+
+    /// Get the client id
+    pub fn client_id(&self) -> &ClientId { &self.client_id }
+
+    /// Get the request for refreshing `refresh_token`, see [`RefreshToken::refresh_token_request`](RefreshTokenRef::refresh_token_request)
+    ///
+    /// Parse the response with [TwitchTokenResponse::from_response](crate::id::TwitchTokenResponse::from_response)
+    pub fn refresh_request(&self, refresh_token: &RefreshTokenRef) -> http::Request<Vec<u8>> {
+        refresh_token.refresh_token_request(&self.client_id, &self.client_secret)
+    }
+
+    /// Refresh `refresh_token`, returning the response from twitch.
+    ///
+    /// Twitch may rotate the refresh token, store [`TwitchTokenResponse::refresh_token`](crate::id::TwitchTokenResponse::refresh_token) if it's returned.
+    #[cfg(feature = "client")]
+    pub async fn refresh<C>(
+        &self,
+        http_client: &C,
+        refresh_token: &RefreshTokenRef,
+    ) -> Result<crate::id::TwitchTokenResponse, RefreshTokenError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        refresh_token
+            .refresh_token_response(
+                http_client,
+                &self.client_id,
+                &self.client_secret,
+                self.strict_token_type,
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RefreshToken;
+
+    #[test]
+    fn refresh_request() {
+        let request = Refresher::new("clientid", "secret")
+            .refresh_request(&RefreshToken::from("refreshtoken"));
+        assert_eq!(request.method(), http::Method::POST);
+        let params: std::collections::HashMap<_, _> =
+            url::form_urlencoded::parse(request.uri().query().unwrap().as_bytes()).collect();
+        assert_eq!(params["client_id"], "clientid");
+        assert_eq!(params["client_secret"], "secret");
+        assert_eq!(params["grant_type"], "refresh_token");
+        assert_eq!(params["refresh_token"], "refreshtoken");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn refresh() {
+        let client = crate::client::MockClient::default()
+            .respond(
+                200,
+                r#"{"access_token":"newaccesstoken","expires_in":14124,"refresh_token":"newrefreshtoken","scope":["chat:read"],"token_type":"bearer"}"#,
+            )
+            .respond(
+                400,
+                r#"{"status":400,"message":"Invalid refresh token"}"#,
+            );
+        let refresher = Refresher::new("clientid", "secret");
+
+        let response = refresher
+            .refresh(&client, &RefreshToken::from("refreshtoken"))
+            .await
+            .unwrap();
+        assert_eq!(response.access_token.secret(), "newaccesstoken");
+        assert_eq!(
+            response.refresh_token().map(|t| t.secret()),
+            Some("newrefreshtoken")
+        );
+
+        assert!(matches!(
+            refresher
+                .refresh(&client, &RefreshToken::from("refreshtoken"))
+                .await,
+            Err(RefreshTokenError::RequestParseError(_))
+        ));
+    }
+}
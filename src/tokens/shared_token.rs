@@ -0,0 +1,142 @@
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+use futures_util::lock::Mutex;
+
+use super::errors::RefreshTokenError;
+use super::{TwitchToken, UserToken};
+use crate::client::Client;
+
+/// Token that can be shared between tasks and is refreshed when needed.
+///
+/// Cloning a `SharedToken` is cheap and gives a handle to the same token.
+/// Only one task refreshes the token at a time, tasks that need a refresh while another task is refreshing wait for it and
+/// use the new token, so the refresh token is never used twice.
+///
+/// The refresh is done on a copy of the token, readers are only blocked while the refreshed token is stored.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use twitch_oauth2::UserToken;
+/// # fn t() -> UserToken {todo!()}
+/// # #[tokio::main]
+/// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+/// use twitch_oauth2::{tokens::SharedToken, TwitchToken};
+/// let token = SharedToken::new(t());
+/// let client = reqwest::Client::builder()
+///     .redirect(reqwest::redirect::Policy::none())
+///     .build()?;
+/// // in any number of tasks
+/// let access_token = token
+///     .with_refresh(&client, |token| token.token().clone())
+///     .await?;
+/// # Ok(())}
+/// # fn main() {run();}
+/// ```
+#[derive(Debug)]
+pub struct SharedToken<T = UserToken> {
+    inner: Arc<Inner<T>>,
+}
+
+#[derive(Debug)]
+struct Inner<T> {
+    token: RwLock<T>,
+    refresh: Mutex<()>,
+}
+
+impl<T> Clone for SharedToken<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: TwitchToken + Clone + Send + Sync> SharedToken<T> {
+    /// Share a token
+    pub fn new(token: T) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                token: RwLock::new(token),
+                refresh: Mutex::new(()),
+            }),
+        }
+    }
+
+    /// Get read access to the token, without refreshing it.
+    ///
+    /// Storing a refreshed token waits until the guard is dropped, so don't hold it across an `.await`.
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        // the token is only replaced as a whole, so a panic can't leave it in an inconsistent state
+        self.inner.token.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Get a copy of the token, without refreshing it
+    pub fn get(&self) -> T { self.read().clone() }
+
+    /// Replace the token
+    pub fn set(&self, token: T) {
+        *self.inner.token.write().unwrap_or_else(|e| e.into_inner()) = token;
+    }
+
+    /// Refresh the token if it has expired, then call `f` with the token.
+    pub async fn with_refresh<C, F, R>(
+        &self,
+        http_client: &C,
+        f: F,
+    ) -> Result<R, RefreshTokenError<<C as Client>::Error>>
+    where
+        C: Client,
+        F: FnOnce(&T) -> R,
+    {
+        if self.read().is_elapsed() {
+            let _refresh = self.inner.refresh.lock().await;
+            // check again, the token may have been refreshed while waiting
+            if self.read().is_elapsed() {
+                let mut token = self.get();
+                token.refresh_token(http_client).await?;
+                self.set(token);
+            }
+        }
+        Ok(f(&self.read()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::MockClient;
+
+    #[tokio::test]
+    async fn concurrent_with_refresh() {
+        let client = Arc::new(MockClient::default().respond(
+            200,
+            r#"{"access_token":"newaccesstoken","expires_in":14124,"refresh_token":"newrefreshtoken","scope":["chat:read"],"token_type":"bearer"}"#,
+        ));
+        let token = SharedToken::new(UserToken::test_token(
+            true,
+            &[],
+            Some(std::time::Duration::ZERO),
+        ));
+
+        let tasks: Vec<_> = (0..10)
+            .map(|_| {
+                let client = client.clone();
+                let token = token.clone();
+                tokio::spawn(async move {
+                    token
+                        .with_refresh(&*client, |token| token.access_token.secret().to_owned())
+                        .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            assert_eq!(task.await.unwrap().unwrap(), "newaccesstoken");
+        }
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+        assert_eq!(
+            token.read().refresh_token.as_ref().unwrap().secret(),
+            "newrefreshtoken"
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use futures_util::lock::{Mutex, MutexGuard};
+
+use super::errors::{RefreshTokenError, RevokeTokenError};
+use super::{EventSink, TokenEvent, TwitchToken};
+use crate::client::Client;
+
+/// Shared token that is refreshed at most once when multiple tasks want to refresh it at the same time.
+///
+/// Twitch can invalidate a refresh token when it is used more than once, so concurrent refreshes of the same token can leave it unusable.
+/// With this type, the first task to call [`SingleFlightRefresher::refresh`] refreshes the token, and the tasks that called it
+/// while that refresh was in flight wait for it to finish instead of refreshing again.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use twitch_oauth2::UserToken;
+/// # fn t() -> UserToken {todo!()}
+/// # #[tokio::main]
+/// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+/// use twitch_oauth2::{tokens::SingleFlightRefresher, TwitchToken};
+/// let refresher = std::sync::Arc::new(SingleFlightRefresher::new(t()));
+/// let client = reqwest::Client::builder()
+///     .redirect(reqwest::redirect::Policy::none())
+///     .build()?;
+/// // in any number of tasks
+/// refresher.refresh_if_expired(&client).await?;
+/// let token = refresher.lock().await.token().clone();
+/// # Ok(())}
+/// # fn main() {run();}
+/// ```
+pub struct SingleFlightRefresher<T> {
+    token: Mutex<T>,
+    generation: AtomicU64,
+    sink: Option<std::sync::Arc<dyn EventSink>>,
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for SingleFlightRefresher<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SingleFlightRefresher")
+            .field("token", &self.token)
+            .field("generation", &self.generation)
+            .field("sink", &self.sink.is_some())
+            .finish()
+    }
+}
+
+impl<T: TwitchToken + Send> SingleFlightRefresher<T> {
+    /// Wrap a token
+    pub fn new(token: T) -> Self {
+        Self {
+            token: Mutex::new(token),
+            generation: AtomicU64::new(0),
+            sink: None,
+        }
+    }
+
+    /// Send [`TokenEvent::Refreshed`], [`TokenEvent::RefreshFailed`] and [`TokenEvent::Revoked`] to `sink`
+    pub fn with_event_sink(mut self, sink: impl EventSink + 'static) -> Self {
+        self.sink = Some(std::sync::Arc::new(sink));
+        self
+    }
+
+    /// Get exclusive access to the token. Refreshes wait until the guard is dropped.
+    pub async fn lock(&self) -> MutexGuard<'_, T> { self.token.lock().await }
+
+    /// Consume the refresher, returning the token
+    pub fn into_inner(self) -> T { self.token.into_inner() }
+
+    /// Refresh the token, unless it was refreshed while waiting for another refresh to finish.
+    ///
+    /// If the refresh that was waited on failed, the token is refreshed again.
+    pub async fn refresh<C>(
+        &self,
+        http_client: &C,
+    ) -> Result<(), RefreshTokenError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        // the generation is only changed while holding the lock
+        let generation = self.generation.load(Ordering::Acquire);
+        let mut token = self.token.lock().await;
+        if self.generation.load(Ordering::Acquire) != generation {
+            return Ok(());
+        }
+        self.refresh_locked(&mut token, http_client).await
+    }
+
+    /// Refresh the token if it has expired, see [`SingleFlightRefresher::refresh`]
+    ///
+    /// Returns `true` if the token was refreshed by this call.
+    pub async fn refresh_if_expired<C>(
+        &self,
+        http_client: &C,
+    ) -> Result<bool, RefreshTokenError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        let mut token = self.token.lock().await;
+        if !token.is_elapsed() {
+            return Ok(false);
+        }
+        self.refresh_locked(&mut token, http_client).await?;
+        Ok(true)
+    }
+
+    /// Revoke the token
+    pub async fn revoke<C>(
+        &self,
+        http_client: &C,
+    ) -> Result<(), RevokeTokenError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        let token = self.token.lock().await;
+        token
+            .token()
+            .revoke_token(http_client, token.client_id())
+            .await?;
+        self.send(|| TokenEvent::Revoked {
+            user_id: token.user_id().map(ToOwned::to_owned),
+        });
+        Ok(())
+    }
+
+    async fn refresh_locked<C>(
+        &self,
+        token: &mut T,
+        http_client: &C,
+    ) -> Result<(), RefreshTokenError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        match token.refresh_token(http_client).await {
+            Ok(()) => {
+                self.generation.fetch_add(1, Ordering::AcqRel);
+                self.send(|| TokenEvent::Refreshed {
+                    user_id: token.user_id().map(ToOwned::to_owned),
+                });
+                Ok(())
+            }
+            Err(e) => {
+                self.send(|| TokenEvent::RefreshFailed {
+                    user_id: token.user_id().map(ToOwned::to_owned),
+                    error: e.to_string(),
+                });
+                Err(e)
+            }
+        }
+    }
+
+    /// Only creates the event if there is a sink
+    fn send(&self, event: impl FnOnce() -> TokenEvent) {
+        if let Some(sink) = &self.sink {
+            sink.event(&event());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::MockClient;
+    use crate::UserToken;
+    use std::sync::Arc;
+
+    const REFRESHED: &str = r#"{"access_token":"newaccesstoken","expires_in":14124,"refresh_token":"newrefreshtoken","scope":["chat:read"],"token_type":"bearer"}"#;
+
+    #[tokio::test]
+    async fn concurrent_refresh() {
+        let client = Arc::new(MockClient::default().respond(200, REFRESHED));
+        let refresher = Arc::new(SingleFlightRefresher::new(UserToken::test_token(
+            true,
+            &[],
+            Some(std::time::Duration::ZERO),
+        )));
+
+        // hold the lock so that all tasks start waiting on the same refresh
+        let guard = refresher.lock().await;
+        let tasks: Vec<_> = (0..10)
+            .map(|_| {
+                let client = client.clone();
+                let refresher = refresher.clone();
+                tokio::spawn(async move { refresher.refresh(&*client).await })
+            })
+            .collect();
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        drop(guard);
+
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+        let refresher = Arc::try_unwrap(refresher).unwrap();
+        assert_eq!(
+            refresher.into_inner().access_token.secret(),
+            "newaccesstoken"
+        );
+    }
+
+    #[tokio::test]
+    async fn events() {
+        let client = MockClient::default()
+            .respond(200, REFRESHED)
+            .respond(400, r#"{"status":400,"message":"Invalid refresh token"}"#)
+            .respond(200, "");
+        let events = Arc::new(std::sync::Mutex::new(vec![]));
+        let refresher = SingleFlightRefresher::new(UserToken::test_token(
+            true,
+            &[],
+            Some(std::time::Duration::ZERO),
+        ))
+        .with_event_sink({
+            let events = events.clone();
+            move |event: &TokenEvent| events.lock().unwrap().push(event.clone())
+        });
+        refresher.refresh(&client).await.unwrap();
+        assert!(refresher.refresh(&client).await.is_err());
+        refresher.revoke(&client).await.unwrap();
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 3);
+        assert!(
+            matches!(&events[0], TokenEvent::Refreshed { user_id: Some(id) } if id.as_str() == "1234")
+        );
+        assert!(matches!(&events[1], TokenEvent::RefreshFailed { error, .. } if !error.is_empty()));
+        assert!(matches!(&events[2], TokenEvent::Revoked { .. }));
+    }
+
+    #[tokio::test]
+    async fn concurrent_refresh_if_expired() {
+        let client = Arc::new(MockClient::default().respond(200, REFRESHED));
+        let refresher = Arc::new(SingleFlightRefresher::new(UserToken::test_token(
+            true,
+            &[],
+            Some(std::time::Duration::ZERO),
+        )));
+
+        let tasks: Vec<_> = (0..10)
+            .map(|_| {
+                let client = client.clone();
+                let refresher = refresher.clone();
+                tokio::spawn(async move { refresher.refresh_if_expired(&*client).await })
+            })
+            .collect();
+        let mut refreshed = 0;
+        for task in tasks {
+            if task.await.unwrap().unwrap() {
+                refreshed += 1;
+            }
+        }
+        assert_eq!(refreshed, 1);
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+    }
+}
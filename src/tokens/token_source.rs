@@ -0,0 +1,311 @@
+use futures_util::lock::Mutex;
+
+use super::errors::RefreshTokenError;
+use super::{SharedToken, SingleFlightRefresher, TwitchToken};
+use crate::client::Client;
+use crate::AccessToken;
+
+/// Something that gives out valid access tokens, refreshing them as needed.
+///
+/// Use this to depend on a token without caring about what kind of token it is, or how it's refreshed.
+///
+/// Implemented for
+///
+/// * [`Mutex<T>`](futures_util::lock::Mutex) of a [`UserToken`](crate::UserToken) or [`AppAccessToken`](crate::AppAccessToken), which need exclusive access to refresh
+/// * [`SharedToken`]
+/// * [`SingleFlightRefresher`]
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use twitch_oauth2::UserToken;
+/// # fn t() -> UserToken {todo!()}
+/// # #[tokio::main]
+/// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+/// use twitch_oauth2::tokens::{SharedToken, TokenSource};
+///
+/// async fn helix_request(source: &impl TokenSource, client: &reqwest::Client) -> Result<(), Box<dyn std::error::Error>> {
+///     let token = source.token(client).await?;
+///     // make the request with `token`
+///     Ok(())
+/// }
+///
+/// let client = reqwest::Client::builder()
+///     .redirect(reqwest::redirect::Policy::none())
+///     .build()?;
+/// helix_request(&SharedToken::new(t()), &client).await?;
+/// # Ok(())}
+/// # fn main() {run();}
+/// ```
+#[async_trait::async_trait]
+pub trait TokenSource: Send + Sync {
+    /// Whether the tokens from this source are already [validated](crate::AccessTokenRef::validate_token), so that [`FallbackTokenSource`] doesn't validate them again.
+    const VALIDATES: bool = false;
+
+    /// Get an access token that is currently valid, refreshing the token first if it has expired.
+    async fn token<C>(
+        &self,
+        http_client: &C,
+    ) -> Result<AccessToken, RefreshTokenError<<C as Client>::Error>>
+    where
+        C: Client;
+}
+
+#[async_trait::async_trait]
+impl<T: TwitchToken + Send> TokenSource for Mutex<T> {
+    async fn token<C>(
+        &self,
+        http_client: &C,
+    ) -> Result<AccessToken, RefreshTokenError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        let mut token = self.lock().await;
+        if token.is_elapsed() {
+            token.refresh_token(http_client).await?;
+        }
+        Ok(token.token().clone())
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: TwitchToken + Clone + Send + Sync> TokenSource for SharedToken<T> {
+    async fn token<C>(
+        &self,
+        http_client: &C,
+    ) -> Result<AccessToken, RefreshTokenError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        self.with_refresh(http_client, |token| token.token().clone())
+            .await
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: TwitchToken + Send> TokenSource for SingleFlightRefresher<T> {
+    async fn token<C>(
+        &self,
+        http_client: &C,
+    ) -> Result<AccessToken, RefreshTokenError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        self.refresh_if_expired(http_client).await?;
+        Ok(self.lock().await.token().clone())
+    }
+}
+
+/// A [`TokenSource`] that falls back to another source when the token of the primary source is not valid.
+///
+/// Every source is tried in order, and the first token that [validates](crate::AccessTokenRef::validate_token) is returned.
+/// If no token is valid, the error of the last source is returned.
+/// Chain more sources with [`FallbackTokenSource::or`].
+///
+/// # Notes
+///
+/// Every call validates the token, making at least one request to twitch. Use this for resilience, not for every request.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use twitch_oauth2::{UserToken, AppAccessToken};
+/// # fn t() -> UserToken {todo!()}
+/// # fn u() -> UserToken {todo!()}
+/// # fn a() -> AppAccessToken {todo!()}
+/// # #[tokio::main]
+/// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+/// use futures_util::lock::Mutex;
+/// use twitch_oauth2::tokens::{FallbackTokenSource, TokenSource};
+///
+/// let source = FallbackTokenSource::new(Mutex::new(t()), Mutex::new(u())).or(Mutex::new(a()));
+/// let client = reqwest::Client::builder()
+///     .redirect(reqwest::redirect::Policy::none())
+///     .build()?;
+/// let token = source.token(&client).await?;
+/// # Ok(())}
+/// # fn main() {run();}
+/// ```
+#[derive(Debug)]
+pub struct FallbackTokenSource<P, S> {
+    primary: P,
+    secondary: S,
+}
+
+impl<P: TokenSource, S: TokenSource> FallbackTokenSource<P, S> {
+    /// Create a source that tries `primary` first, then `secondary`
+    pub fn new(primary: P, secondary: S) -> Self { Self { primary, secondary } }
+
+    /// Add another source to try after all others
+    pub fn or<T: TokenSource>(self, fallback: T) -> FallbackTokenSource<Self, T> {
+        FallbackTokenSource::new(self, fallback)
+    }
+}
+
+/// Get a token from `source` and make sure it's valid
+async fn valid_token<S, C>(
+    source: &S,
+    http_client: &C,
+) -> Result<AccessToken, RefreshTokenError<<C as Client>::Error>>
+where
+    S: TokenSource,
+    C: Client,
+{
+    let token = source.token(http_client).await?;
+    if !S::VALIDATES {
+        token.validate_token(http_client).await?;
+    }
+    Ok(token)
+}
+
+#[async_trait::async_trait]
+impl<P: TokenSource, S: TokenSource> TokenSource for FallbackTokenSource<P, S> {
+    const VALIDATES: bool = true;
+
+    async fn token<C>(
+        &self,
+        http_client: &C,
+    ) -> Result<AccessToken, RefreshTokenError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        match valid_token(&self.primary, http_client).await {
+            Ok(token) => Ok(token),
+            Err(_) => valid_token(&self.secondary, http_client).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::MockClient;
+    use crate::{AppAccessToken, UserToken};
+
+    const REFRESHED: &str = r#"{"access_token":"newaccesstoken","expires_in":14124,"refresh_token":"newrefreshtoken","scope":["chat:read"],"token_type":"bearer"}"#;
+
+    async fn assert_source(source: &impl TokenSource, client: &MockClient, expected: &str) {
+        assert_eq!(source.token(client).await.unwrap().secret(), expected);
+    }
+
+    #[tokio::test]
+    async fn user_token() {
+        let client = MockClient::default().respond(200, REFRESHED);
+        assert_source(
+            &Mutex::new(UserToken::test_token(
+                true,
+                &[],
+                Some(std::time::Duration::from_secs(3600)),
+            )),
+            &client,
+            "accesstoken",
+        )
+        .await;
+        assert!(client.requests.lock().unwrap().is_empty());
+
+        let source = Mutex::new(UserToken::test_token(
+            true,
+            &[],
+            Some(std::time::Duration::ZERO),
+        ));
+        assert_source(&source, &client, "newaccesstoken").await;
+        // not expired anymore
+        assert_source(&source, &client, "newaccesstoken").await;
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn app_access_token() {
+        let client = MockClient::default().respond(200, REFRESHED);
+        let source = Mutex::new(AppAccessToken::test_token(std::time::Duration::ZERO));
+        assert_source(&source, &client, "newaccesstoken").await;
+        assert_source(&source, &client, "newaccesstoken").await;
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn shared_token() {
+        let client = MockClient::default().respond(200, REFRESHED);
+        let source = SharedToken::new(UserToken::test_token(
+            true,
+            &[],
+            Some(std::time::Duration::ZERO),
+        ));
+        assert_source(&source, &client, "newaccesstoken").await;
+        assert_source(&source.clone(), &client, "newaccesstoken").await;
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn fallback() {
+        const VALID: &str = r#"{"client_id":"clientid","login":"login","scopes":[],"user_id":"1234","expires_in":3600}"#;
+        const INVALID: &str = r#"{"status":401,"message":"invalid access token"}"#;
+        let app_token = || {
+            let mut token = AppAccessToken::test_token(std::time::Duration::from_secs(3600));
+            token.access_token = "apptoken".into();
+            Mutex::new(token)
+        };
+
+        let client = MockClient::default()
+            .respond(401, INVALID)
+            .respond(200, VALID);
+        let source = FallbackTokenSource::new(
+            Mutex::new(UserToken::test_token(
+                true,
+                &[],
+                Some(std::time::Duration::from_secs(3600)),
+            )),
+            app_token(),
+        );
+        assert_source(&source, &client, "apptoken").await;
+        {
+            let requests = client.requests.lock().unwrap();
+            assert_eq!(requests.len(), 2);
+            assert_eq!(requests[0].headers()["Authorization"], "OAuth accesstoken");
+            assert_eq!(requests[1].headers()["Authorization"], "OAuth apptoken");
+        }
+
+        // the primary is used while it's valid
+        let client = MockClient::default().respond(200, VALID);
+        assert_source(&source, &client, "accesstoken").await;
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+
+        // the token of a chained source is only validated once
+        let client = MockClient::default().respond(200, VALID);
+        let source = source.or(app_token());
+        assert_source(&source, &client, "accesstoken").await;
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+
+        // the error of the last source is returned
+        let client = MockClient::default()
+            .respond(401, INVALID)
+            .respond(401, INVALID)
+            .respond(401, INVALID)
+            .respond(401, INVALID);
+        let source = source.or(Mutex::new(UserToken::test_token(
+            true,
+            &[],
+            Some(std::time::Duration::from_secs(3600)),
+        )));
+        assert!(matches!(
+            source.token(&client).await,
+            Err(RefreshTokenError::ValidationError(
+                crate::tokens::errors::ValidationError::NotAuthorized
+            ))
+        ));
+        assert_eq!(client.requests.lock().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn single_flight_refresher() {
+        let client = MockClient::default().respond(200, REFRESHED);
+        let source = SingleFlightRefresher::new(UserToken::test_token(
+            true,
+            &[],
+            Some(std::time::Duration::ZERO),
+        ));
+        assert_source(&source, &client, "newaccesstoken").await;
+        assert_source(&source, &client, "newaccesstoken").await;
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+    }
+}
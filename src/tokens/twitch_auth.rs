@@ -0,0 +1,299 @@
+#[cfg(feature = "client")]
+use super::errors::{
+    AppAccessTokenError, RefreshTokenError, RevokeTokenError, UserTokenExchangeError,
+    ValidationError,
+};
+#[cfg(feature = "client")]
+use crate::client::Client;
+use crate::tokens::{AppAccessToken, ImplicitUserTokenBuilder, Scope, UserTokenBuilder};
+#[cfg(feature = "client")]
+use crate::tokens::{TwitchToken, UserToken};
+#[cfg(feature = "client")]
+use crate::types::{AccessToken, AccessTokenRef, RefreshToken};
+use crate::types::{ClientId, ClientSecret};
+
+/// Client credentials shared between [`AppAccessToken`]s and [`UserToken`](super::UserToken)s
+///
+/// Use this to get app access tokens and user token builders from the same configuration.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #[tokio::main]
+/// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+/// use twitch_oauth2::tokens::TwitchAuth;
+/// let auth = TwitchAuth::new("myclientid", "myclientsecret");
+/// let client = reqwest::Client::builder()
+///     .redirect(reqwest::redirect::Policy::none())
+///     .build()?;
+/// let app_token = auth.app_token(&client, vec![]).await?;
+/// let mut builder = auth.user_token_builder(url::Url::parse("http://localhost/twitch/register")?);
+/// let url = builder.generate_url();
+/// # Ok(())}
+/// # fn main() {run();}
+/// ```
+#[derive(Clone, Debug)]
+pub struct TwitchAuth {
+    client_id: ClientId,
+    client_secret: ClientSecret,
+}
+
+impl TwitchAuth {
+    /// Create a new [`TwitchAuth`]
+    pub fn new(client_id: impl Into<ClientId>, client_secret: impl Into<ClientSecret>) -> Self {
+        Self {
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+        }
+    }
+
+    /// Get the client id
+    pub fn client_id(&self) -> &ClientId { &self.client_id }
+
+    /// Get the client secret
+    pub fn client_secret(&self) -> &ClientSecret { &self.client_secret }
+
+    /// Get the request for getting an app access token, see [`AppAccessToken::get_app_access_token_request`]
+    pub fn app_token_request(&self, scopes: Vec<Scope>) -> http::Request<Vec<u8>> {
+        AppAccessToken::get_app_access_token_request(&self.client_id, &self.client_secret, scopes)
+    }
+
+    /// Generate an app access token, see [`AppAccessToken::get_app_access_token`]
+    #[cfg(feature = "client")]
+    pub async fn app_token<C>(
+        &self,
+        http_client: &C,
+        scopes: Vec<Scope>,
+    ) -> Result<AppAccessToken, AppAccessTokenError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        AppAccessToken::get_app_access_token(
+            http_client,
+            self.client_id.clone(),
+            self.client_secret.clone(),
+            scopes,
+        )
+        .await
+    }
+
+    /// Create a [`UserTokenBuilder`] for the [OAuth authorization code flow](https://dev.twitch.tv/docs/authentication/getting-tokens-oauth/#oauth-authorization-code-flow)
+    ///
+    /// See [`UserTokenBuilder::new`] for notes on the redirect url.
+    pub fn user_token_builder(&self, redirect_url: url::Url) -> UserTokenBuilder {
+        UserTokenBuilder::new(
+            self.client_id.clone(),
+            self.client_secret.clone(),
+            redirect_url,
+        )
+    }
+
+    /// Create a [`Refresher`](super::Refresher) to refresh tokens with these credentials
+    pub fn refresher(&self) -> super::Refresher {
+        super::Refresher::new(self.client_id.clone(), self.client_secret.clone())
+    }
+
+    /// Create a [`ImplicitUserTokenBuilder`] for the [OAuth implicit code flow](https://dev.twitch.tv/docs/authentication/getting-tokens-oauth/#oauth-implicit-code-flow)
+    ///
+    /// See [`ImplicitUserTokenBuilder::new`] for notes on the redirect url.
+    pub fn implicit_user_token_builder(&self, redirect_url: url::Url) -> ImplicitUserTokenBuilder {
+        ImplicitUserTokenBuilder::new(self.client_id.clone(), redirect_url)
+    }
+
+    /// Bind a http client to these credentials, so that all token operations go through the same client and reuse its connections.
+    ///
+    /// See [`TwitchAuthClient`]
+    #[cfg(feature = "client")]
+    pub fn with_client<C: Client>(self, http_client: C) -> TwitchAuthClient<C> {
+        TwitchAuthClient {
+            auth: self,
+            http_client,
+        }
+    }
+}
+
+/// Client credentials with a http client, see [`TwitchAuth::with_client`]
+///
+/// Creating a new http client for every request opens a new connection each time.
+/// Routing all token operations through this type makes sure a single client, and its connection pool, is reused.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # #[tokio::main]
+/// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+/// use twitch_oauth2::tokens::TwitchAuth;
+/// let client = reqwest::Client::builder()
+///     .redirect(reqwest::redirect::Policy::none())
+///     .build()?;
+/// let auth = TwitchAuth::new("myclientid", "myclientsecret").with_client(client);
+/// let mut app_token = auth.app_token(vec![]).await?;
+/// // later
+/// auth.refresh(&mut app_token).await?;
+/// # Ok(())}
+/// # fn main() {run();}
+/// ```
+#[cfg(feature = "client")]
+#[derive(Clone, Debug)]
+pub struct TwitchAuthClient<C> {
+    auth: TwitchAuth,
+    http_client: C,
+}
+
+#[cfg(feature = "client")]
+impl<C: Client> TwitchAuthClient<C> {
+    /// Get the credentials
+    pub fn auth(&self) -> &TwitchAuth { &self.auth }
+
+    /// Get the http client
+    pub fn http_client(&self) -> &C { &self.http_client }
+
+    /// Generate an app access token, see [`AppAccessToken::get_app_access_token`]
+    pub async fn app_token(
+        &self,
+        scopes: Vec<Scope>,
+    ) -> Result<AppAccessToken, AppAccessTokenError<<C as Client>::Error>> {
+        self.auth.app_token(&self.http_client, scopes).await
+    }
+
+    /// Exchange the code from the redirect for a [`UserToken`], see [`UserTokenBuilder::get_user_token`]
+    ///
+    /// The builder should come from [`TwitchAuth::user_token_builder`] on [`TwitchAuthClient::auth`].
+    pub async fn exchange_code(
+        &self,
+        builder: UserTokenBuilder,
+        code: impl AsRef<str>,
+    ) -> Result<UserToken, UserTokenExchangeError<<C as Client>::Error>> {
+        builder.get_user_token(&self.http_client, code).await
+    }
+
+    /// Create a [`UserToken`] from an existing token, see [`UserToken::from_existing`]
+    pub async fn user_token_from_existing(
+        &self,
+        access_token: AccessToken,
+        refresh_token: impl Into<Option<RefreshToken>>,
+    ) -> Result<UserToken, ValidationError<<C as Client>::Error>> {
+        UserToken::from_existing(
+            &self.http_client,
+            access_token,
+            refresh_token,
+            self.auth.client_secret.clone(),
+        )
+        .await
+    }
+
+    /// Refresh a token, see [`TwitchToken::refresh_token`]
+    pub async fn refresh<T: TwitchToken + Send>(
+        &self,
+        token: &mut T,
+    ) -> Result<(), RefreshTokenError<<C as Client>::Error>> {
+        token.refresh_token(&self.http_client).await
+    }
+
+    /// Validate a token, see [`AccessTokenRef::validate_token`]
+    pub async fn validate(
+        &self,
+        token: &AccessTokenRef,
+    ) -> Result<crate::ValidatedToken, ValidationError<<C as Client>::Error>> {
+        token.validate_token(&self.http_client).await
+    }
+
+    /// Revoke a token, see [`AccessTokenRef::revoke_token`]
+    pub async fn revoke(
+        &self,
+        token: &AccessTokenRef,
+    ) -> Result<(), RevokeTokenError<<C as Client>::Error>> {
+        token
+            .revoke_token(&self.http_client, &self.auth.client_id)
+            .await
+    }
+}
+
+impl From<crate::types::ClientCredentials> for TwitchAuth {
+    fn from(credentials: crate::types::ClientCredentials) -> Self {
+        Self::new(credentials.client_id, credentials.client_secret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_token_builder() {
+        let auth = TwitchAuth::new("clientid", "secret");
+        let url = auth
+            .user_token_builder(url::Url::parse("http://localhost/twitch/register").unwrap())
+            .generate_url();
+        let params: std::collections::HashMap<_, _> = url.query_pairs().collect();
+        assert_eq!(params["client_id"], "clientid");
+        assert_eq!(params["response_type"], "code");
+
+        let (url, _) = auth
+            .implicit_user_token_builder(
+                url::Url::parse("http://localhost/twitch/register").unwrap(),
+            )
+            .generate_url();
+        let params: std::collections::HashMap<_, _> = url.query_pairs().collect();
+        assert_eq!(params["client_id"], "clientid");
+        assert_eq!(params["response_type"], "token");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn app_token() {
+        use crate::TwitchToken;
+
+        let client = crate::client::MockClient::default().respond(
+            200,
+            r#"{"access_token":"apptoken","expires_in":5011271,"token_type":"bearer"}"#,
+        );
+        let auth = TwitchAuth::new("clientid", "secret");
+        let token = auth.app_token(&client, vec![]).await.unwrap();
+        assert_eq!(token.token().secret(), "apptoken");
+        assert_eq!(token.client_id().as_str(), "clientid");
+
+        let requests = client.requests.lock().unwrap();
+        let params: std::collections::HashMap<_, _> =
+            url::form_urlencoded::parse(requests[0].uri().query().unwrap().as_bytes()).collect();
+        assert_eq!(params["client_id"], "clientid");
+        assert_eq!(params["client_secret"], "secret");
+        assert_eq!(params["grant_type"], "client_credentials");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn with_client() {
+        let client = crate::client::MockClient::default()
+            .respond(
+                200,
+                r#"{"access_token":"apptoken","expires_in":5011271,"token_type":"bearer"}"#,
+            )
+            .respond(
+                200,
+                r#"{"client_id":"clientid","scopes":[],"expires_in":5011271}"#,
+            )
+            .respond(
+                200,
+                r#"{"access_token":"accesstoken","expires_in":14124,"refresh_token":"refreshtoken","scope":["chat:read"],"token_type":"bearer"}"#,
+            )
+            .respond(
+                200,
+                r#"{"client_id":"clientid","login":"login","scopes":["chat:read"],"user_id":"1234","expires_in":14124}"#,
+            )
+            .respond(200, "");
+        let auth = TwitchAuth::new("clientid", "secret").with_client(client);
+
+        let mut app_token = auth.app_token(vec![]).await.unwrap();
+        auth.validate(app_token.token()).await.unwrap();
+        let builder = auth
+            .auth()
+            .user_token_builder(url::Url::parse("http://localhost/twitch/register").unwrap());
+        let user_token = auth.exchange_code(builder, "code").await.unwrap();
+        auth.revoke(&user_token.access_token).await.unwrap();
+
+        // all requests went through the same client, which is now out of responses
+        assert_eq!(auth.http_client().requests.lock().unwrap().len(), 5);
+        assert!(auth.refresh(&mut app_token).await.is_err());
+    }
+}
@@ -0,0 +1,170 @@
+//! Basic profile of the user a [`UserToken`] belongs to, from Helix
+use twitch_types::{DisplayName, UserId, UserName};
+
+use super::errors::UserInfoError;
+#[cfg(feature = "client")]
+use crate::client::Client;
+use crate::tokens::{TwitchToken, UserToken};
+
+/// Basic profile of a user, as returned by [Get Users](https://dev.twitch.tv/docs/api/reference/#get-users)
+///
+/// See [`UserToken::get_user_info`]
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[non_exhaustive]
+pub struct UserInfo {
+    /// Id of the user
+    pub id: UserId,
+    /// Login of the user
+    pub login: UserName,
+    /// Display name of the user
+    pub display_name: DisplayName,
+    /// Description of the user's channel
+    #[serde(default)]
+    pub description: String,
+    /// Url to the user's profile image
+    pub profile_image_url: String,
+    /// Verified email address of the user
+    ///
+    /// Only returned if the token has the [`user:read:email`](crate::Scope::UserReadEmail) scope.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct HelixResponse {
+    data: Vec<UserInfo>,
+}
+
+impl UserInfo {
+    /// Parse the [`UserInfo`] from a response to [`UserToken::get_user_info_request`]
+    pub fn from_response<B: AsRef<[u8]>>(
+        response: &http::Response<B>,
+    ) -> Result<UserInfo, UserInfoError<std::convert::Infallible>> {
+        let body = crate::parse_token_response_raw(response)?.body().as_ref();
+        let response: HelixResponse =
+            serde_json::from_slice(body).map_err(crate::RequestParseError::DeserializeError)?;
+        response
+            .data
+            .into_iter()
+            .next()
+            .ok_or(UserInfoError::NoUser)
+    }
+}
+
+impl UserToken {
+    /// Get the request for getting the [`UserInfo`] of the user this token belongs to.
+    ///
+    /// The request is authenticated with [`TwitchToken::headers`]. Parse the response with [`UserInfo::from_response`]
+    pub fn get_user_info_request(
+        &self,
+    ) -> Result<http::Request<Vec<u8>>, UserInfoError<std::convert::Infallible>> {
+        Ok(crate::construct_request::<&[(String, String)], _, _>(
+            &crate::HELIX_URL
+                .join("users")
+                .expect("should be a valid url"),
+            &[],
+            self.headers()?,
+            http::Method::GET,
+            vec![],
+        ))
+    }
+
+    /// Get the basic profile of the user this token belongs to.
+    ///
+    /// The email is only included if the token has the [`user:read:email`](crate::Scope::UserReadEmail) scope.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use twitch_oauth2::UserToken;
+    /// # fn t() -> UserToken {todo!()}
+    /// # #[tokio::main]
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+    /// # let user_token = t();
+    /// let client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build()?;
+    /// let info = user_token.get_user_info(&client).await?;
+    /// println!("hello {}!", info.display_name);
+    /// # Ok(()) }
+    /// # fn main() {run();}
+    /// ```
+    #[cfg(feature = "client")]
+    pub async fn get_user_info<C>(
+        &self,
+        http_client: &C,
+    ) -> Result<UserInfo, UserInfoError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        let req = self.get_user_info_request().map_err(|e| e.into_other())?;
+        let resp = http_client.req(req).await.map_err(UserInfoError::Request)?;
+        UserInfo::from_response(&resp).map_err(|e| e.into_other())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "client")]
+    use crate::Scope;
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn get_user_info() {
+        let client = crate::client::MockClient::default()
+            .respond(
+                200,
+                r#"{"data":[{"id":"1234","login":"login","display_name":"Login","type":"","broadcaster_type":"","description":"hi","profile_image_url":"https://example.com/image.png","offline_image_url":"","view_count":0,"email":"login@example.com","created_at":"2016-12-14T20:32:28Z"}]}"#,
+            )
+            .respond(
+                401,
+                r#"{"error":"Unauthorized","status":401,"message":"Invalid OAuth token"}"#,
+            )
+            .respond(200, r#"{"data":[]}"#);
+        let token = UserToken::test_token(false, &[Scope::UserReadEmail], None);
+
+        let info = token.get_user_info(&client).await.unwrap();
+        assert_eq!(info.id.as_str(), "1234");
+        assert_eq!(info.login.as_str(), "login");
+        assert_eq!(info.display_name.as_str(), "Login");
+        assert_eq!(info.profile_image_url, "https://example.com/image.png");
+        assert_eq!(info.email.as_deref(), Some("login@example.com"));
+        {
+            let requests = client.requests.lock().unwrap();
+            assert_eq!(requests[0].uri(), "https://api.twitch.tv/helix/users");
+            assert_eq!(requests[0].headers()["Authorization"], "Bearer accesstoken");
+            assert_eq!(requests[0].headers()["Client-Id"], "clientid");
+        }
+
+        let err = token.get_user_info(&client).await.unwrap_err();
+        assert!(matches!(
+            err,
+            UserInfoError::RequestParseError(crate::RequestParseError::TwitchError(
+                crate::id::TwitchTokenErrorResponse { status, .. }
+            )) if status == http::StatusCode::UNAUTHORIZED
+        ));
+
+        let err = token.get_user_info(&client).await.unwrap_err();
+        assert!(matches!(err, UserInfoError::NoUser));
+    }
+
+    #[test]
+    fn invalid_header() {
+        let mut token = UserToken::test_token(false, &[], None);
+        token.access_token = "access\ntoken".into();
+        assert!(matches!(
+            token.get_user_info_request(),
+            Err(UserInfoError::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn user_info_without_email() {
+        let response = http::Response::builder()
+            .status(200)
+            .body(r#"{"data":[{"id":"1234","login":"login","display_name":"Login","profile_image_url":""}]}"#)
+            .unwrap();
+        let info = UserInfo::from_response(&response).unwrap();
+        assert_eq!(info.email, None);
+        assert_eq!(info.description, "");
+    }
+}
@@ -1,6 +1,6 @@
 use twitch_types::{UserId, UserIdRef, UserName, UserNameRef};
 
-use super::errors::ValidationError;
+use super::errors::{CsrfContainsSeparator, RedirectUrlError, ScopesRejected, ValidationError};
 #[cfg(feature = "client")]
 use super::errors::{ImplicitUserTokenExchangeError, RefreshTokenError, UserTokenExchangeError};
 #[cfg(feature = "client")]
@@ -9,7 +9,9 @@ use crate::client::Client;
 use crate::tokens::{Scope, TwitchToken};
 use crate::{ClientSecret, ValidatedToken};
 
-use crate::types::{AccessToken, ClientId, RefreshToken};
+use crate::types::{
+    AccessToken, AccessTokenRef, ClientId, ClientIdRef, CsrfTokenRef, RefreshToken,
+};
 
 /// An User Token from the [OAuth implicit code flow](https://dev.twitch.tv/docs/authentication/getting-tokens-oauth#oauth-implicit-code-flow) or [OAuth authorization code flow](https://dev.twitch.tv/docs/authentication/getting-tokens-oauth#oauth-authorization-code-flow)
 ///
@@ -32,11 +34,44 @@ pub struct UserToken {
     expires_in: std::time::Duration,
     /// When this struct was created, not when token was created.
     struct_created: std::time::Instant,
-    scopes: Vec<Scope>,
+    /// Wall-clock time of `struct_created`
+    created_at: std::time::SystemTime,
+    /// When the token was last validated, see [`UserToken::next_validation_due`]
+    last_validated: std::time::SystemTime,
+    /// Shared between clones, so that cloning a token doesn't copy the scopes
+    scopes: std::sync::Arc<[Scope]>,
     /// Token will never expire
     ///
     /// This is only true for old client IDs, like <https://twitchapps.com/tmi> and others
     pub never_expiring: bool,
+    environment: crate::Environment,
+    /// Token was created without a refresh token or client secret, as with the implicit flow
+    implicit: bool,
+    /// Where `expires_in` came from
+    expiry_source: ExpirySource,
+    /// Scopes that were granted but not requested when exchanging
+    extra_granted_scopes: Vec<Scope>,
+    /// Redacted response from the token endpoint, see [`UserToken::raw_token_response`]
+    raw_token_response: Option<serde_json::Value>,
+    refresh_throttle: crate::tokens::RefreshThrottle,
+    /// Reject token responses that are not `bearer`, see [`UserToken::set_strict_token_type`]
+    strict_token_type: bool,
+    #[cfg(feature = "client")]
+    token_exchanger: Option<std::sync::Arc<dyn crate::client::TokenExchanger>>,
+    #[cfg(feature = "client")]
+    on_request: Option<crate::client::RequestHook>,
+}
+
+/// Where the expiry of a [`UserToken`] came from, see [`UserToken::expiry_source`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExpirySource {
+    /// The `expires_in` from [validating](crate::AccessTokenRef::validate_token) the token
+    Validation,
+    /// The `expires_in` from the token response, when getting or refreshing the token
+    TokenResponse,
+    /// The `expires_in` given to [`UserToken::from_existing_unchecked`]
+    Given,
 }
 
 impl std::fmt::Debug for UserToken {
@@ -50,12 +85,15 @@ impl std::fmt::Debug for UserToken {
             .field("refresh_token", &self.refresh_token)
             .field("expires_in", &self.expires_in())
             .field("scopes", &self.scopes)
+            .field("environment", &self.environment)
             .finish()
     }
 }
 
 impl UserToken {
     /// Create a new token
+    ///
+    /// The expiry is taken from `validated`, see [`UserToken::expiry_source`].
     pub fn new(
         access_token: AccessToken,
         refresh_token: Option<RefreshToken>,
@@ -71,11 +109,14 @@ impl UserToken {
             validated.user_id.ok_or(ValidationError::NoLogin)?,
             validated.scopes,
             validated.expires_in,
-        ))
+        )
+        .with_expiry_source(ExpirySource::Validation))
     }
 
     /// Assemble token and validate it. Retrieves [`login`](TwitchToken::login), [`client_id`](TwitchToken::client_id) and [`scopes`](TwitchToken::scopes)
     ///
+    /// The expiry is taken from the validation, see [`UserToken::expiry_source`].
+    ///
     /// If the token is already expired, this function will fail to produce a [`UserToken`] and return [`ValidationError::NotAuthorized`]
     #[cfg(feature = "client")]
     pub async fn from_existing<C>(
@@ -92,6 +133,26 @@ impl UserToken {
             .map_err(|e| e.into_other())
     }
 
+    /// Create a [UserToken] from an existing active user token, making sure it was issued for `client_id`.
+    ///
+    /// Returns [`ValidationError::ClientIdMismatch`] if the token belongs to another application.
+    #[cfg(feature = "client")]
+    pub async fn from_existing_for_client<C>(
+        http_client: &C,
+        client_id: &ClientIdRef,
+        access_token: AccessToken,
+        refresh_token: impl Into<Option<RefreshToken>>,
+        client_secret: impl Into<Option<ClientSecret>>,
+    ) -> Result<UserToken, ValidationError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        let validated = access_token.validate_token(http_client).await?;
+        validated.assert_client_id(client_id)?;
+        Self::new(access_token, refresh_token.into(), validated, client_secret)
+            .map_err(|e| e.into_other())
+    }
+
     /// Assemble token without checks.
     ///
     /// If `expires_in` is `None`, we'll assume `token.is_elapsed` is always false
@@ -106,6 +167,7 @@ impl UserToken {
         scopes: Option<Vec<Scope>>,
         expires_in: Option<std::time::Duration>,
     ) -> UserToken {
+        let created_at = std::time::SystemTime::now();
         UserToken {
             access_token: access_token.into(),
             client_id: client_id.into(),
@@ -113,28 +175,80 @@ impl UserToken {
             login,
             user_id,
             refresh_token: refresh_token.into(),
-            expires_in: expires_in.unwrap_or_else(|| {
-                // TODO: Use Duration::MAX
-                std::time::Duration::new(u64::MAX, 1_000_000_000 - 1)
-            }),
+            expires_in: expires_in.unwrap_or(std::time::Duration::MAX),
             struct_created: std::time::Instant::now(),
-            scopes: scopes.unwrap_or_default(),
+            created_at,
+            last_validated: created_at,
+            refresh_throttle: Default::default(),
+            strict_token_type: false,
+            #[cfg(feature = "client")]
+            token_exchanger: None,
+            #[cfg(feature = "client")]
+            on_request: None,
+            scopes: scopes.unwrap_or_default().into(),
             never_expiring: expires_in.is_none(),
+            environment: crate::Environment::current(),
+            implicit: false,
+            expiry_source: ExpirySource::Given,
+            extra_granted_scopes: vec![],
+            raw_token_response: None,
         }
+        .with_implicit()
+    }
+
+    fn with_implicit(mut self) -> Self {
+        self.implicit = self.refresh_token.is_none() && self.client_secret.is_none();
+        self
+    }
+
+    fn with_expiry_source(mut self, source: ExpirySource) -> Self {
+        self.expiry_source = source;
+        self
+    }
+
+    /// Where the expiry of this token came from.
+    ///
+    /// The validation and the token response can disagree on when a token expires.
+    /// [`UserToken::new`] and [`UserToken::from_existing`] use the validation,
+    /// while [`UserToken::from_response`] and [`refreshing`](TwitchToken::refresh_token) use the token response.
+    pub fn expiry_source(&self) -> ExpirySource { self.expiry_source }
+
+    /// Returns whether or not this token can be refreshed with [`TwitchToken::refresh_token`]
+    ///
+    /// Tokens from the [implicit flow](ImplicitUserTokenBuilder) have no refresh token, and can't be refreshed.
+    pub fn can_refresh(&self) -> bool {
+        self.refresh_token.is_some() && self.client_secret.is_some()
     }
 
+    /// Returns whether or not this token has the [`user:read:email`](Scope::UserReadEmail) scope, needed to get the email of the user.
+    pub fn can_read_email(&self) -> bool { self.has_scope(&Scope::UserReadEmail) }
+
     /// Assemble token from twitch responses.
+    ///
+    /// The expiry is taken from `response`, or from `validated` if the response has none, see [`UserToken::expiry_source`].
     pub fn from_response(
         response: crate::id::TwitchTokenResponse,
         validated: ValidatedToken,
         client_secret: impl Into<Option<ClientSecret>>,
     ) -> Result<UserToken, ValidationError<std::convert::Infallible>> {
-        Self::new(
-            response.access_token,
-            response.refresh_token,
-            validated,
-            client_secret,
-        )
+        let token = UserToken {
+            raw_token_response: response.raw,
+            ..Self::new(
+                response.access_token,
+                response.refresh_token,
+                validated,
+                client_secret,
+            )?
+        };
+        Ok(match response.expires_in {
+            Some(expires_in) => UserToken {
+                expires_in: std::time::Duration::from_secs(expires_in),
+                never_expiring: false,
+                ..token
+            }
+            .with_expiry_source(ExpirySource::TokenResponse),
+            None => token,
+        })
     }
 
     #[doc(hidden)]
@@ -143,6 +257,338 @@ impl UserToken {
     /// Hidden because it's not expected to be used.
     pub fn never_expires(&self) -> bool { self.never_expiring }
 
+    /// Validate this token without changing it, returning the fresh expiry, scopes and login.
+    ///
+    /// Use [`UserToken::revalidate`] to update the token with the validation.
+    /// As this doesn't change the token, [`UserToken::next_validation_due`] is not updated.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use twitch_oauth2::UserToken;
+    /// # fn t() -> UserToken {todo!()}
+    /// # #[tokio::main]
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+    /// # let user_token = t();
+    /// let client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build()?;
+    /// let validated = user_token.validate(&client).await?;
+    /// println!("token expires in {:?}", validated.expires_in);
+    /// # Ok(()) }
+    /// # fn main() {run();}
+    /// ```
+    #[cfg(feature = "client")]
+    pub async fn validate<C>(
+        &self,
+        http_client: &C,
+    ) -> Result<ValidatedToken, ValidationError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        let http_client = &crate::client::HookedClient::new(http_client, self.on_request.as_ref());
+        self.access_token.validate_token(http_client).await
+    }
+
+    /// Validate this token, and update [`login`](UserToken::login) if it has changed since the token was issued.
+    ///
+    /// Twitch users can change their login, while their user id stays the same.
+    /// Returns [`LoginChanged`] if the login was changed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use twitch_oauth2::UserToken;
+    /// # fn t() -> UserToken {todo!()}
+    /// # #[tokio::main]
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+    /// # let mut user_token = t();
+    /// let client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build()?;
+    /// if let Some(changed) = user_token.revalidate(&client).await? {
+    ///     println!("{} is now known as {}", changed.old, changed.new);
+    /// }
+    /// # Ok(()) }
+    /// # fn main() {run();}
+    /// ```
+    #[cfg(feature = "client")]
+    pub async fn revalidate<C>(
+        &mut self,
+        http_client: &C,
+    ) -> Result<Option<LoginChanged>, ValidationError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        let validated = self.validate(http_client).await?;
+        Ok(self.update_login(&validated))
+    }
+
+    /// Update [`login`](UserToken::login) from a validation of this token, returning [`LoginChanged`] if the login was changed.
+    ///
+    /// A validation for another user is ignored. See [`UserToken::revalidate`]
+    pub fn update_login(&mut self, validated: &ValidatedToken) -> Option<LoginChanged> {
+        if validated.user_id.as_ref() != Some(&self.user_id) {
+            return None;
+        }
+        self.last_validated = std::time::SystemTime::now();
+        match &validated.login {
+            Some(login) if *login != self.login => {
+                let old = std::mem::replace(&mut self.login, login.clone());
+                Some(LoginChanged {
+                    old,
+                    new: login.clone(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Refresh this token like [`TwitchToken::refresh_token`], returning a [`RefreshOutcome`] describing what changed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use twitch_oauth2::UserToken;
+    /// # fn t() -> UserToken {todo!()}
+    /// # #[tokio::main]
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+    /// # let mut user_token = t();
+    /// let client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build()?;
+    /// let outcome = user_token.refresh_token_with_outcome(&client).await?;
+    /// println!("refresh token rotated: {}", outcome.refresh_token_rotated);
+    /// # Ok(()) }
+    /// # fn main() {run();}
+    /// ```
+    #[cfg(feature = "client")]
+    pub async fn refresh_token_with_outcome<C>(
+        &mut self,
+        http_client: &C,
+    ) -> Result<RefreshOutcome, RefreshTokenError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        #[cfg(feature = "fingerprint")]
+        let old_fingerprint = self.access_token.fingerprint();
+        let old_access_token = self.access_token.clone();
+        let old_expires_in = self.expires_in();
+        let old_refresh_token = self.refresh_token.clone();
+
+        let response_scopes = self.refresh_inner(http_client).await?;
+
+        let (scopes_added, scopes_removed) = match response_scopes {
+            Some(scopes) => (
+                scopes
+                    .iter()
+                    .filter(|s| !self.scopes.contains(s))
+                    .cloned()
+                    .collect(),
+                self.scopes
+                    .iter()
+                    .filter(|s| !scopes.contains(s))
+                    .cloned()
+                    .collect(),
+            ),
+            None => (vec![], vec![]),
+        };
+        Ok(RefreshOutcome {
+            access_token_changed: old_access_token != self.access_token,
+            #[cfg(feature = "fingerprint")]
+            old_fingerprint,
+            #[cfg(feature = "fingerprint")]
+            new_fingerprint: self.access_token.fingerprint(),
+            old_expires_in,
+            new_expires_in: self.expires_in(),
+            refresh_token_rotated: old_refresh_token != self.refresh_token,
+            scopes_added,
+            scopes_removed,
+        })
+    }
+
+    /// Refresh the token, returning the scopes from the response
+    #[cfg(feature = "client")]
+    async fn refresh_inner<C>(
+        &mut self,
+        http_client: &C,
+    ) -> Result<Option<Vec<Scope>>, RefreshTokenError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        if self.implicit && self.refresh_token.is_none() {
+            return Err(RefreshTokenError::Implicit);
+        }
+        self.refresh_throttle
+            .attempt()
+            .map_err(|retry_after| RefreshTokenError::TooSoon { retry_after })?;
+        let http_client = &crate::client::HookedClient::new(http_client, self.on_request.as_ref())
+            .exchanger(self.token_exchanger.as_deref());
+        if let Some(client_secret) = self.client_secret.clone() {
+            let response = if let Some(token) = &self.refresh_token {
+                token
+                    .refresh_token_response(
+                        http_client,
+                        &self.client_id,
+                        &client_secret,
+                        self.strict_token_type,
+                    )
+                    .await?
+            } else {
+                return Err(RefreshTokenError::NoRefreshToken);
+            };
+            let expires = response
+                .expires_in()
+                .ok_or(RefreshTokenError::NoExpiration)?;
+            self.access_token = response.access_token;
+            self.expires_in = expires;
+            self.struct_created = std::time::Instant::now();
+            self.created_at = std::time::SystemTime::now();
+            self.expiry_source = ExpirySource::TokenResponse;
+            self.raw_token_response = response.raw;
+            // Twitch doesn't always rotate the refresh token, keep the old one if no new one was returned
+            if let Some(refresh_token) = response.refresh_token {
+                self.refresh_token = Some(refresh_token);
+            }
+            Ok(response.scopes)
+        } else {
+            Err(RefreshTokenError::NoClientSecretFound)
+        }
+    }
+
+    /// Get the scopes this token has that are not in `baseline`, for example the scopes the token had before it was refreshed or reauthorized.
+    ///
+    /// A token gaining scopes unexpectedly can be a sign of misconfiguration or an attack.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use twitch_oauth2::{UserToken, Scope};
+    /// # fn t() -> UserToken {todo!()}
+    /// # fn run() {
+    /// # let token = t();
+    /// let escalated = token.scope_escalation(&[Scope::ChatRead, Scope::ChatEdit]);
+    /// if !escalated.is_empty() {
+    ///     eprintln!("token gained unexpected scopes: {escalated:?}");
+    /// }
+    /// # }
+    /// ```
+    pub fn scope_escalation(&self, baseline: &[Scope]) -> Vec<Scope> {
+        self.scopes
+            .iter()
+            .filter(|s| !baseline.contains(s))
+            .cloned()
+            .collect()
+    }
+
+    /// Get the scopes that were granted but not requested when this token was created by [`UserTokenBuilder`] or [`ImplicitUserTokenBuilder`].
+    ///
+    /// Twitch can grant scopes that were not requested, for example scopes the user granted to the application before.
+    /// This is empty if all granted scopes were requested, or if the token was not created by a builder.
+    pub fn extra_granted_scopes(&self) -> &[Scope] { &self.extra_granted_scopes }
+
+    /// Get the JSON the token endpoint returned when this token was created or last refreshed, with the access token, refresh token and id token redacted.
+    ///
+    /// Useful for auditing and for debugging discrepancies with twitch. This is `None` for tokens not created from a token response,
+    /// like tokens from [`UserToken::from_existing`], see [`TwitchTokenResponse::raw`](crate::id::TwitchTokenResponse::raw).
+    pub fn raw_token_response(&self) -> Option<&serde_json::Value> {
+        self.raw_token_response.as_ref()
+    }
+
+    /// Send refreshes of this token through a [`TokenExchanger`](crate::client::TokenExchanger), for example to an internal token broker.
+    ///
+    /// Tokens from [`UserTokenBuilder::get_user_token`] use the exchanger set with [`UserTokenBuilder::token_exchanger`].
+    #[cfg(feature = "client")]
+    pub fn set_token_exchanger(&mut self, exchanger: impl crate::client::TokenExchanger + 'static) {
+        self.token_exchanger = Some(std::sync::Arc::new(exchanger));
+    }
+
+    /// Set a function to call with every request made when refreshing or validating this token, right before it is sent.
+    ///
+    /// The request is redacted like in [`UserTokenBuilder::on_request`], which is kept by tokens from [`UserTokenBuilder::get_user_token`].
+    #[cfg(feature = "client")]
+    pub fn set_on_request(
+        &mut self,
+        f: impl Fn(&mut http::Request<Vec<u8>>) + Send + Sync + 'static,
+    ) {
+        self.on_request = Some(crate::client::RequestHook::new(f, true));
+    }
+
+    /// Set a function to call with every request made when refreshing or validating this token, right before it is sent.
+    ///
+    /// # Notes
+    ///
+    /// Unlike [`UserToken::set_on_request`], the request is not redacted and contains secrets, take care to not log them.
+    #[cfg(feature = "client")]
+    pub fn set_on_request_with_secrets(
+        &mut self,
+        f: impl Fn(&mut http::Request<Vec<u8>>) + Send + Sync + 'static,
+    ) {
+        self.on_request = Some(crate::client::RequestHook::new(f, false));
+    }
+
+    #[cfg(feature = "client")]
+    fn with_requested_scopes(mut self, requested: &[Scope]) -> Self {
+        self.extra_granted_scopes = self.scope_escalation(requested);
+        self
+    }
+
+    /// How long this struct has existed, not how long the token has existed.
+    pub fn age(&self) -> std::time::Duration { self.struct_created.elapsed() }
+
+    /// Get when this token expires in wall-clock time.
+    ///
+    /// Returns `None` if the token never expires.
+    pub fn expires_at(&self) -> Option<std::time::SystemTime> {
+        if self.never_expiring {
+            return None;
+        }
+        self.created_at.checked_add(self.expires_in)
+    }
+
+    /// Get when this token should be validated next.
+    ///
+    /// Twitch requires that tokens are [validated](https://dev.twitch.tv/docs/authentication/validate-tokens/#how-to-validate-a-token) at least once an hour,
+    /// so this is one hour after the last validation with [`UserToken::revalidate`] or [`UserToken::update_login`].
+    /// A token is assumed to be validated when it's created.
+    pub fn next_validation_due(&self) -> std::time::SystemTime {
+        self.last_validated + std::time::Duration::from_secs(60 * 60)
+    }
+
+    /// Returns whether or not the token is expired at the given time.
+    ///
+    /// This is the same as [`TwitchToken::is_elapsed`], but uses the wall-clock time `now` instead of the monotonic clock,
+    /// which makes it possible to inject the time, for example in tests.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::time::{Duration, SystemTime};
+    /// use twitch_oauth2::UserToken;
+    /// let token = UserToken::from_existing_unchecked(
+    ///     "accesstoken",
+    ///     None,
+    ///     "clientid",
+    ///     None,
+    ///     "login".into(),
+    ///     "1234".into(),
+    ///     None,
+    ///     Some(Duration::from_secs(3600)),
+    /// );
+    /// assert!(!token.is_expired_at(SystemTime::now()));
+    /// assert!(token.is_expired_at(SystemTime::now() + Duration::from_secs(7200)));
+    /// ```
+    pub fn is_expired_at(&self, now: std::time::SystemTime) -> bool {
+        self.expires_at().map(|at| now >= at).unwrap_or_default()
+    }
+
+    /// Get when this token expires as a [`tokio::time::Instant`], for use with e.g [`tokio::time::sleep_until`].
+    ///
+    /// Returns `None` if the token never expires.
+    #[cfg(feature = "tokio")]
+    pub fn expires_at_tokio(&self) -> Option<tokio::time::Instant> {
+        if self.never_expiring {
+            return None;
+        }
+        self.struct_created
+            .checked_add(self.expires_in)
+            .map(tokio::time::Instant::from_std)
+    }
+
     /// Create a [`UserTokenBuilder`] to get a token with the [OAuth Authorization Code](https://dev.twitch.tv/docs/authentication/getting-tokens-oauth/#oauth-authorization-code-flow)
     pub fn builder(
         client_id: ClientId,
@@ -209,18 +655,154 @@ impl UserToken {
             .map_err(UserTokenExchangeError::RequestError)?;
         let response = crate::id::TwitchTokenResponse::from_response(&resp)?;
 
-        UserToken::from_existing(
+        let mut token = UserToken::from_existing(
             http_client,
             response.access_token,
             response.refresh_token,
             client_secret,
         )
-        .await
-        .map_err(Into::into)
+        .await?;
+        token.set_environment(crate::Environment::Mock);
+        Ok(token)
     }
 
     /// Set the client secret
     pub fn set_secret(&mut self, secret: Option<ClientSecret>) { self.client_secret = secret }
+
+    /// Set the [`Environment`](crate::Environment) this token was obtained from.
+    ///
+    /// Defaults to [`Environment::current`](crate::Environment::current) when the token is created.
+    pub fn set_environment(&mut self, environment: crate::Environment) {
+        self.environment = environment
+    }
+
+    /// Set whether this token never expires, overriding what was determined when it was created.
+    ///
+    /// [`UserToken::from_existing_unchecked`] treats a token as never expiring only when `expires_in` is `None`.
+    pub fn set_never_expiring(&mut self, never_expiring: bool) {
+        self.never_expiring = never_expiring
+    }
+
+    /// Treat this token as never expiring if its original `expires_in` is at least `threshold`.
+    ///
+    /// Useful when a token store records never expiring tokens with a very large expiry instead of none.
+    /// Tokens below the threshold are left as they are.
+    pub fn set_never_expiring_threshold(&mut self, threshold: std::time::Duration) {
+        if self.expires_in >= threshold {
+            self.never_expiring = true;
+        }
+    }
+
+    /// Set the minimum time between attempts to refresh this token, to avoid refreshing in a loop when refreshes keep failing.
+    ///
+    /// Refreshing sooner than `interval` after the last attempt, successful or not, returns [`RefreshTokenError::TooSoon`](crate::tokens::errors::RefreshTokenError::TooSoon)
+    /// without making a request. A zero `interval` disables this, which is the default.
+    pub fn set_min_refresh_interval(&mut self, interval: std::time::Duration) {
+        self.refresh_throttle.set_min_interval(interval)
+    }
+
+    /// Reject refresh responses with a `token_type` other than `bearer`, see [`TwitchTokenResponse::from_response_strict`](crate::id::TwitchTokenResponse::from_response_strict)
+    ///
+    /// Tokens from a builder with [`UserTokenBuilder::strict_token_type`] have this set.
+    pub fn set_strict_token_type(&mut self, strict_token_type: bool) {
+        self.strict_token_type = strict_token_type
+    }
+
+    /// Refresh this token if it has expired.
+    ///
+    /// Returns `true` if the token was refreshed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use twitch_oauth2::UserToken;
+    /// # fn t() -> UserToken {todo!()}
+    /// # #[tokio::main]
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+    /// # let mut user_token = t();
+    /// let client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none()).build()?;
+    /// if user_token.refresh_if_expired(&client).await? {
+    ///     println!("token was refreshed");
+    /// }
+    /// # Ok(()) }
+    /// # fn main() {run();}
+    /// ```
+    #[cfg(feature = "client")]
+    pub async fn refresh_if_expired<C>(
+        &mut self,
+        http_client: &C,
+    ) -> Result<bool, RefreshTokenError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        if !self.is_elapsed() {
+            return Ok(false);
+        }
+        self.refresh_token(http_client).await?;
+        Ok(true)
+    }
+
+    /// Generate a url to have the user authorize this tokens client id again, requesting exactly `scopes`.
+    ///
+    /// This is used to reduce (or change) the scopes the user has granted. `force_verify` is set, so that the user is shown the consent screen.
+    /// The returned [`CsrfToken`](crate::CsrfToken) should be given to the [`UserTokenBuilder`] used to exchange the code, see [`UserTokenBuilder::set_csrf`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use twitch_oauth2::{UserToken, Scope};
+    /// # fn t() -> UserToken {todo!()}
+    /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let token = t();
+    /// let redirect_url = url::Url::parse("http://localhost/twitch/register")?;
+    /// let (url, csrf) = token.reconsent_url(redirect_url, vec![Scope::ChatRead]);
+    /// # Ok(())}
+    /// ```
+    pub fn reconsent_url(
+        &self,
+        redirect_url: url::Url,
+        scopes: Vec<Scope>,
+    ) -> (url::Url, crate::types::CsrfToken) {
+        let csrf = crate::types::CsrfToken::new_random();
+        let url = authorize_url(
+            "code",
+            &self.client_id,
+            &redirect_url,
+            Some(&csrf),
+            &scopes,
+            true,
+            None,
+        );
+        (url, csrf)
+    }
+
+    /// Serialize the token without any secrets, for passing token metadata to something that should not see the token itself.
+    ///
+    /// `access_token`, `refresh_token` and `client_secret` are always `null`. `expires_in` is the remaining lifetime in seconds, or `null` if the token never expires.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use twitch_oauth2::UserToken;
+    /// # fn t() -> UserToken {todo!()}
+    /// # fn run() {
+    /// # let token = t();
+    /// let public = token.to_public_json();
+    /// println!("{}", public["login"]);
+    /// # }
+    /// ```
+    pub fn to_public_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "access_token": null,
+            "refresh_token": null,
+            "client_secret": null,
+            "client_id": self.client_id,
+            "login": self.login,
+            "user_id": self.user_id,
+            "scopes": &*self.scopes,
+            "expires_in": (!self.never_expiring).then(|| self.expires_in().as_secs()),
+        })
+    }
 }
 
 #[cfg_attr(feature = "client", async_trait::async_trait)]
@@ -244,22 +826,19 @@ impl TwitchToken for UserToken {
         Self: Sized,
         C: Client,
     {
-        if let Some(client_secret) = self.client_secret.clone() {
-            let (access_token, expires, refresh_token) =
-                if let Some(token) = self.refresh_token.take() {
-                    token
-                        .refresh_token(http_client, &self.client_id, &client_secret)
-                        .await?
-                } else {
-                    return Err(RefreshTokenError::NoRefreshToken);
-                };
-            self.access_token = access_token;
-            self.expires_in = expires;
-            self.refresh_token = refresh_token;
-            Ok(())
-        } else {
-            return Err(RefreshTokenError::NoClientSecretFound);
-        }
+        self.refresh_inner(http_client).await.map(|_| ())
+    }
+
+    #[cfg(feature = "client")]
+    async fn validate_token<'a, C>(
+        &self,
+        http_client: &'a C,
+    ) -> Result<ValidatedToken, ValidationError<<C as Client>::Error>>
+    where
+        Self: Sized,
+        C: Client,
+    {
+        self.validate(http_client).await
     }
 
     fn expires_in(&self) -> std::time::Duration {
@@ -268,13 +847,59 @@ impl TwitchToken for UserToken {
                 .checked_sub(self.struct_created.elapsed())
                 .unwrap_or_default()
         } else {
-            // We don't return an option here because it's not expected to use this if the token is known to be unexpiring.
-            // TODO: Use Duration::MAX
-            std::time::Duration::new(u64::MAX, 1_000_000_000 - 1)
+            std::time::Duration::MAX
         }
     }
 
-    fn scopes(&self) -> &[Scope] { self.scopes.as_slice() }
+    fn expiry(&self) -> crate::tokens::Expiry {
+        if self.never_expiring {
+            crate::tokens::Expiry::Never
+        } else {
+            crate::tokens::Expiry::At(self.expires_in())
+        }
+    }
+
+    fn scopes(&self) -> &[Scope] { &self.scopes }
+
+    fn environment(&self) -> &crate::Environment { &self.environment }
+}
+
+/// What changed when refreshing a [`UserToken`], see [`UserToken::refresh_token_with_outcome`]
+///
+/// This doesn't contain any secrets, and is safe to log.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RefreshOutcome {
+    /// Whether or not twitch issued a new access token
+    pub access_token_changed: bool,
+    /// [Fingerprint](crate::AccessTokenRef::fingerprint) of the access token before the refresh
+    #[cfg(feature = "fingerprint")]
+    pub old_fingerprint: String,
+    /// [Fingerprint](crate::AccessTokenRef::fingerprint) of the access token after the refresh
+    #[cfg(feature = "fingerprint")]
+    pub new_fingerprint: String,
+    /// Remaining lifetime of the token before the refresh
+    pub old_expires_in: std::time::Duration,
+    /// Remaining lifetime of the token after the refresh
+    pub new_expires_in: std::time::Duration,
+    /// Whether or not twitch returned a new refresh token
+    pub refresh_token_rotated: bool,
+    /// Scopes in the refresh response that the token didn't have
+    ///
+    /// The scopes of the token are not changed by a refresh.
+    pub scopes_added: Vec<Scope>,
+    /// Scopes the token has that were missing from the refresh response
+    pub scopes_removed: Vec<Scope>,
+}
+
+/// The login of a [`UserToken`] changed, see [`UserToken::revalidate`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LoginChanged {
+    /// The previous login
+    pub old: UserName,
+    /// The new login
+    pub new: UserName,
 }
 
 /// Builder for [OAuth authorization code flow](https://dev.twitch.tv/docs/authentication/getting-tokens-oauth/#oauth-authorization-code-flow)
@@ -283,12 +908,32 @@ impl TwitchToken for UserToken {
 pub struct UserTokenBuilder {
     pub(crate) scopes: Vec<Scope>,
     pub(crate) csrf: Option<crate::types::CsrfToken>,
+    /// Whether [`csrf`](Self::csrf) ends with a correlation id, see [`UserTokenBuilder::with_correlation_id`]
+    pub(crate) correlated: bool,
     pub(crate) force_verify: bool,
+    pub(crate) prompt: Option<Prompt>,
+    pub(crate) scope_order: ScopeOrder,
+    pub(crate) redirect_match: RedirectMatch,
     pub(crate) redirect_url: url::Url,
+    pub(crate) body_encoding: crate::BodyEncoding,
+    pub(crate) fresh_connection: bool,
+    pub(crate) strict_token_type: bool,
+    pub(crate) environment: Option<crate::Environment>,
+    pub(crate) scope_validator: Option<std::sync::Arc<ScopeValidator>>,
+    /// Scopes already granted, see [`UserTokenBuilder::incremental_from`]
+    pub(crate) granted_scopes: Option<Vec<Scope>>,
+    /// Extra parameters for the code exchange, see [`UserTokenBuilder::extra_token_param`]
+    pub(crate) extra_token_params: Vec<(String, String)>,
+    #[cfg(feature = "client")]
+    pub(crate) on_request: Option<crate::client::RequestHook>,
+    #[cfg(feature = "client")]
+    pub(crate) token_exchanger: Option<std::sync::Arc<dyn crate::client::TokenExchanger>>,
     client_id: ClientId,
     client_secret: ClientSecret,
 }
 
+type ScopeValidator = dyn Fn(&[Scope]) -> Result<(), String> + Send + Sync;
+
 impl UserTokenBuilder {
     /// Create a [`UserTokenBuilder`]
     ///
@@ -306,15 +951,116 @@ impl UserTokenBuilder {
         UserTokenBuilder {
             scopes: vec![],
             csrf: Some(crate::types::CsrfToken::new_random()),
+            correlated: false,
             force_verify: false,
+            prompt: None,
+            scope_order: ScopeOrder::default(),
+            redirect_match: RedirectMatch::default(),
             redirect_url,
+            body_encoding: crate::BodyEncoding::default(),
+            fresh_connection: false,
+            strict_token_type: false,
+            environment: None,
+            scope_validator: None,
+            granted_scopes: None,
+            extra_token_params: vec![],
+            #[cfg(feature = "client")]
+            on_request: None,
+            #[cfg(feature = "client")]
+            token_exchanger: None,
             client_id: client_id.into(),
             client_secret: client_secret.into(),
         }
     }
 
-    /// Add scopes to the request
-    pub fn set_scopes(mut self, scopes: Vec<Scope>) -> Self {
+    /// Create a [`UserTokenBuilder`] from an [`OAuthConfig`](crate::types::OAuthConfig)
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::{tokens::UserTokenBuilder, types::OAuthConfig};
+    /// let config: OAuthConfig = serde_json::from_str(
+    ///     r#"{
+    ///         "client_id": "myclientid",
+    ///         "client_secret": "myclientsecret",
+    ///         "redirect_url": "http://localhost/twitch/register",
+    ///         "scopes": ["chat:read", "chat:edit"]
+    ///     }"#,
+    /// )?;
+    /// let builder = UserTokenBuilder::from_oauth_config(config)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_oauth_config(
+        config: crate::types::OAuthConfig,
+    ) -> Result<UserTokenBuilder, RedirectUrlError> {
+        Ok(
+            Self::try_new(config.client_id, config.client_secret, &config.redirect_url)?
+                .set_scopes(config.scopes)
+                .force_verify(config.force_verify),
+        )
+    }
+
+    /// Create a [`UserTokenBuilder`], checking that the redirect url is sent to twitch exactly as given.
+    ///
+    /// Returns [`RedirectUrlError::TrailingSlash`] if the `url` crate would add a trailing slash to the redirect url, see [`UserTokenBuilder::new`],
+    /// and [`RedirectUrlError::Fragment`] if the redirect url has a fragment, which twitch rejects.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::tokens::{errors::RedirectUrlError, UserTokenBuilder};
+    /// assert!(UserTokenBuilder::try_new("myclientid", "myclientsecret", "http://localhost/twitch/register").is_ok());
+    /// assert!(matches!(
+    ///     UserTokenBuilder::try_new("myclientid", "myclientsecret", "http://localhost"),
+    ///     Err(RedirectUrlError::TrailingSlash { .. })
+    /// ));
+    /// ```
+    pub fn try_new(
+        client_id: impl Into<ClientId>,
+        client_secret: impl Into<ClientSecret>,
+        redirect_url: &str,
+    ) -> Result<UserTokenBuilder, RedirectUrlError> {
+        Ok(Self::new(
+            client_id,
+            client_secret,
+            parse_redirect_url(redirect_url)?,
+        ))
+    }
+
+    /// Create a [`UserTokenBuilder`] with a known CSRF token.
+    ///
+    /// Useful for stateless servers, where the CSRF token is stored in e.g a cookie and the builder is recreated when handling the redirect.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::{tokens::UserTokenBuilder, CsrfToken};
+    /// # let csrf_from_cookie = "randomstate";
+    /// let builder = UserTokenBuilder::with_csrf(
+    ///     "myclientid",
+    ///     "myclientsecret",
+    ///     url::Url::parse("http://localhost/twitch/register")?,
+    ///     CsrfToken::from(csrf_from_cookie),
+    /// );
+    /// assert!(builder.csrf_is_valid("randomstate"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_csrf(
+        client_id: impl Into<ClientId>,
+        client_secret: impl Into<ClientSecret>,
+        redirect_url: url::Url,
+        csrf: impl Into<crate::types::CsrfToken>,
+    ) -> UserTokenBuilder {
+        Self::new(client_id, client_secret, redirect_url).set_csrf(Some(csrf.into()))
+    }
+
+    /// Get the redirect url exactly as it will be sent to twitch.
+    ///
+    /// This has to match one of the redirect urls registered in the [developer console](https://dev.twitch.tv/console).
+    pub fn redirect_url_as_registered(&self) -> &str { self.redirect_url.as_str() }
+
+    /// Add scopes to the request
+    pub fn set_scopes(mut self, scopes: Vec<Scope>) -> Self {
         self.scopes = scopes;
         self
     }
@@ -325,44 +1071,458 @@ impl UserTokenBuilder {
         self
     }
 
+    /// Add the scopes in [`presets::MODERATION`](crate::scopes::presets::MODERATION) to the request
+    pub fn with_moderation_scopes(self) -> Self {
+        self.add_preset(crate::scopes::presets::MODERATION)
+    }
+
+    /// Add the scopes in [`presets::CHAT_BOT`](crate::scopes::presets::CHAT_BOT) to the request
+    pub fn with_chat_bot_scopes(self) -> Self { self.add_preset(crate::scopes::presets::CHAT_BOT) }
+
+    /// Add the scopes in [`presets::SUBSCRIPTIONS`](crate::scopes::presets::SUBSCRIPTIONS) to the request
+    pub fn with_subscription_scopes(self) -> Self {
+        self.add_preset(crate::scopes::presets::SUBSCRIPTIONS)
+    }
+
+    /// Start an incremental authorization from an existing token, requesting the scopes it has and remembering them as granted.
+    ///
+    /// Add the new scopes to request with [`UserTokenBuilder::add_scope`], and check with [`UserTokenBuilder::scope_advisory`] that new scopes are requested.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::{tokens::{ScopeAdvisory, UserTokenBuilder}, Scope, UserToken};
+    /// # fn t() -> UserToken {todo!()}
+    /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let token = t();
+    /// let mut builder = UserTokenBuilder::new(
+    ///     "myclientid",
+    ///     "myclientsecret",
+    ///     url::Url::parse("http://localhost/twitch/register")?,
+    /// )
+    /// .incremental_from(&token)
+    /// .add_scope(Scope::ModeratorManageBannedUsers);
+    /// if let Some(ScopeAdvisory::NoNewScopes { .. }) = builder.scope_advisory() {
+    ///     eprintln!("the token already has all requested scopes");
+    /// }
+    /// let url = builder.generate_url();
+    /// # Ok(()) }
+    /// ```
+    pub fn incremental_from(self, token: &impl TwitchToken) -> Self {
+        let mut builder = self.add_preset(token.scopes());
+        builder.granted_scopes = Some(token.scopes().to_vec());
+        builder
+    }
+
+    /// Check the requested scopes against the scopes already granted with [`UserTokenBuilder::incremental_from`].
+    ///
+    /// Returns [`ScopeAdvisory::NoNewScopes`] if every requested scope is already granted, as authorizing again would not grant any new permission.
+    /// Returns `None` if new scopes are requested, or the builder was not made for an incremental authorization.
+    pub fn scope_advisory(&self) -> Option<ScopeAdvisory> {
+        let granted = self.granted_scopes.as_ref()?;
+        if self.scopes.iter().all(|s| granted.contains(s)) {
+            Some(ScopeAdvisory::NoNewScopes {
+                already_granted: self.scopes.clone(),
+            })
+        } else {
+            None
+        }
+    }
+
+    fn add_preset(mut self, preset: &[Scope]) -> Self {
+        for scope in preset {
+            if !self.scopes.contains(scope) {
+                self.scopes.push(scope.clone());
+            }
+        }
+        self
+    }
+
     /// Enable or disable function to make the user able to switch accounts if needed.
+    ///
+    /// This is sent as `force_verify`, independently of [`UserTokenBuilder::prompt`]. Setting this is similar to [`Prompt::Consent`].
     pub fn force_verify(mut self, b: bool) -> Self {
         self.force_verify = b;
         self
     }
 
+    /// Set the order of the scopes in the generated url, which is the order twitch shows them in on the consent screen, see [`ScopeOrder`].
+    ///
+    /// This only changes the url, not the scopes of the builder.
+    pub fn scope_order(mut self, order: ScopeOrder) -> Self {
+        self.scope_order = order;
+        self
+    }
+
+    /// Set the `prompt` parameter, see [`Prompt`].
+    pub fn prompt(mut self, prompt: impl Into<Option<Prompt>>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+
+    /// Set how [`UserTokenBuilder::check_redirect`] compares redirect urls, defaults to [`RedirectMatch::Exact`]
+    pub fn redirect_match(mut self, mode: RedirectMatch) -> Self {
+        self.redirect_match = mode;
+        self
+    }
+
+    /// Check that the url twitch redirected to matches the redirect url used to authorize.
+    ///
+    /// Twitch requires the redirect url to match exactly, this helps to catch a mismatch in your configuration,
+    /// for example a server listening on a different path than the one sent to twitch.
+    /// The parameters twitch adds to the redirect (`code`, `scope`, `state`, `error` and `error_description`) are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::tokens::{RedirectMatch, UserTokenBuilder};
+    /// let builder = UserTokenBuilder::new(
+    ///     "myclientid",
+    ///     "myclientsecret",
+    ///     url::Url::parse("http://localhost/twitch/register")?,
+    /// );
+    /// let received = url::Url::parse("http://localhost/twitch/register?code=code&scope=&state=state")?;
+    /// builder.check_redirect(&received)?;
+    /// let received = url::Url::parse("http://localhost/twitch/callback?code=code&scope=&state=state")?;
+    /// assert!(builder.check_redirect(&received).is_err());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn check_redirect(&self, url: &url::Url) -> Result<(), RedirectUrlError> {
+        if self.redirect_match.matches(&self.redirect_url, url) {
+            Ok(())
+        } else {
+            Err(RedirectUrlError::Mismatch {
+                expected: self.redirect_url.to_string(),
+                found: url.to_string(),
+            })
+        }
+    }
+
+    /// Check that the redirect url of this builder is in the allowlist.
+    ///
+    /// The url is compared as it will be sent to twitch. If an entry only differs by the trailing slash added to urls with an empty path,
+    /// [`RedirectUrlError::TrailingSlash`] is returned, as twitch would reject the redirect.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::tokens::{RedirectAllowlist, UserTokenBuilder};
+    /// let allowlist = RedirectAllowlist::new()
+    ///     .with("http://localhost/twitch/register")
+    ///     .with("https://example.com/twitch/register");
+    /// let builder = UserTokenBuilder::new(
+    ///     "myclientid",
+    ///     "myclientsecret",
+    ///     url::Url::parse("http://localhost/twitch/register")?,
+    /// );
+    /// builder.validate_redirect(&allowlist)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn validate_redirect(&self, allowlist: &RedirectAllowlist) -> Result<(), RedirectUrlError> {
+        allowlist.validate(&self.redirect_url)
+    }
+
+    /// Set the [`Environment`](crate::Environment) the token is labeled with, defaults to [`Environment::current`](crate::Environment::current)
+    ///
+    /// This only sets [`TwitchToken::environment`] of the token, the requests are still sent to [`TOKEN_URL`](crate::TOKEN_URL) and the other urls of this crate.
+    /// Point those at another environment with the `mock_api` feature, see [`AUTH_URL`](crate::AUTH_URL).
+    pub fn environment(mut self, environment: impl Into<Option<crate::Environment>>) -> Self {
+        self.environment = environment.into();
+        self
+    }
+
     /// Set the CSRF token.
     pub fn set_csrf(mut self, csrf: Option<crate::types::CsrfToken>) -> Self {
         self.csrf = csrf;
+        self.correlated = false;
+        self
+    }
+
+    /// Embed a correlation id in the state, as `<csrf>:<correlation id>`, to route the callback to the request it belongs to.
+    ///
+    /// The correlation id is not secret, and is readable by anyone with the url.
+    /// Find the builder for a callback with [`UserTokenBuilder::split_correlated_state`], and check the state with [`UserTokenBuilder::parse_correlated_state`].
+    ///
+    /// Calling this again replaces the correlation id. Fails if the CSRF token contains `:`, as it couldn't be told apart from the correlation id.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::tokens::UserTokenBuilder;
+    /// let mut builder = UserTokenBuilder::new(
+    ///     "myclientid",
+    ///     "myclientsecret",
+    ///     url::Url::parse("http://localhost/twitch/register")?,
+    /// )
+    /// .with_correlation_id("session-42")?;
+    /// let url = builder.generate_url();
+    ///
+    /// // in the callback
+    /// # let state = url.query_pairs().find(|(k, _)| k == "state").unwrap().1.into_owned();
+    /// let (_, correlation_id) = UserTokenBuilder::split_correlated_state(&state);
+    /// assert_eq!(correlation_id, Some("session-42"));
+    /// // look up the builder stored for `session-42`, then check the whole state
+    /// let (csrf_valid, _) = builder.parse_correlated_state(&state);
+    /// assert!(csrf_valid);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_correlation_id(
+        mut self,
+        correlation_id: &str,
+    ) -> Result<Self, CsrfContainsSeparator> {
+        let csrf = self
+            .csrf
+            .take()
+            .unwrap_or_else(crate::types::CsrfToken::new_random);
+        let csrf = if self.correlated {
+            Self::split_correlated_state(csrf.secret()).0
+        } else if csrf.secret().contains(':') {
+            return Err(CsrfContainsSeparator);
+        } else {
+            csrf.secret()
+        };
+        let state = crate::types::CsrfToken::new(format!("{csrf}:{correlation_id}"));
+        let mut builder = self.set_csrf(Some(state));
+        builder.correlated = true;
+        Ok(builder)
+    }
+
+    /// Split a state made with [`UserTokenBuilder::with_correlation_id`] into the CSRF token and the correlation id, to find the builder the callback belongs to.
+    ///
+    /// The correlation id is `None` if the state has none. This does not check the state, use [`UserTokenBuilder::parse_correlated_state`] on the builder for that.
+    pub fn split_correlated_state(state: &str) -> (&str, Option<&str>) {
+        match state.split_once(':') {
+            Some((csrf, correlation_id)) => (csrf, Some(correlation_id)),
+            None => (state, None),
+        }
+    }
+
+    /// Check a state made with [`UserTokenBuilder::with_correlation_id`] against this builder, and get its correlation id.
+    ///
+    /// The state is only valid if both the CSRF token and the correlation id match.
+    pub fn parse_correlated_state<'a>(&self, state: &'a str) -> (bool, Option<&'a str>) {
+        (
+            self.csrf_is_valid(state),
+            Self::split_correlated_state(state).1,
+        )
+    }
+
+    /// Set how the parameters are sent when exchanging the code, see [`BodyEncoding`](crate::BodyEncoding).
+    ///
+    /// Defaults to [`BodyEncoding::Query`](crate::BodyEncoding::Query), which is what Twitch expects.
+    pub fn body_encoding(mut self, encoding: crate::BodyEncoding) -> Self {
+        self.body_encoding = encoding;
+        self
+    }
+
+    /// Add a parameter to the request exchanging the code for a token, see [`UserTokenBuilder::get_user_token_request`].
+    ///
+    /// This is for parameters twitch supports that this crate doesn't know about.
+    /// The parameters this crate sends, like `client_id` or `code`, can't be overridden.
+    pub fn extra_token_param(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.extra_token_params.push((key.into(), value.into()));
+        self
+    }
+
+    /// Reject token responses with a `token_type` other than `bearer` in [`UserTokenBuilder::get_user_token`], see [`TwitchTokenResponse::from_response_strict`](crate::id::TwitchTokenResponse::from_response_strict)
+    ///
+    /// The token keeps rejecting them when refreshed, see [`UserToken::set_strict_token_type`].
+    pub fn strict_token_type(mut self, strict_token_type: bool) -> Self {
+        self.strict_token_type = strict_token_type;
+        self
+    }
+
+    /// Ask the http client to use a fresh connection for every request made by this builder, instead of reusing pooled connections.
+    ///
+    /// This adds a `Connection: close` header to the requests, which clients speaking HTTP/1.1 honor by not returning the connection to their pool.
+    /// Whether a new connection is actually made is up to the [`Client`](crate::client::Client), e.g. with [`reqwest`](https://docs.rs/reqwest) you can
+    /// also disable pooling entirely with [`ClientBuilder::pool_max_idle_per_host(0)`](https://docs.rs/reqwest/latest/reqwest/struct.ClientBuilder.html#method.pool_max_idle_per_host).
+    ///
+    /// Defaults to `false`, reusing connections is faster.
+    pub fn force_fresh_connection(mut self, force: bool) -> Self {
+        self.fresh_connection = force;
+        self
+    }
+
+    /// Set a function to call with every request made in [`UserTokenBuilder::get_user_token`], right before it is sent.
+    ///
+    /// The function can change the headers of the request. The returned token keeps the function, and calls it when refreshing or validating, see [`UserToken::set_on_request`].
+    ///
+    /// Secrets in the request, like the client secret, code and access token, are replaced with `[redacted]`.
+    /// Only changes to the headers are sent, a redacted `Authorization` header that is left as is will be sent with the secret.
+    /// Use [`UserTokenBuilder::on_request_with_secrets`] to see the request as sent.
+    #[cfg(feature = "client")]
+    pub fn on_request(
+        mut self,
+        f: impl Fn(&mut http::Request<Vec<u8>>) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_request = Some(crate::client::RequestHook::new(f, true));
+        self
+    }
+
+    /// Set a function to call with every request made in [`UserTokenBuilder::get_user_token`], right before it is sent.
+    ///
+    /// # Notes
+    ///
+    /// Unlike [`UserTokenBuilder::on_request`], the request is not redacted and contains secrets, take care to not log them. Any part of the request can be changed.
+    #[cfg(feature = "client")]
+    pub fn on_request_with_secrets(
+        mut self,
+        f: impl Fn(&mut http::Request<Vec<u8>>) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_request = Some(crate::client::RequestHook::new(f, false));
+        self
+    }
+
+    /// Send the requests made in [`UserTokenBuilder::get_user_token`] through a [`TokenExchanger`](crate::client::TokenExchanger), for example to an internal token broker.
+    ///
+    /// The exchanger sees every request, including the validation of the new token, and is kept for [refreshing](TwitchToken::refresh_token) the token,
+    /// see [`UserToken::set_token_exchanger`].
+    /// Defaults to [`DirectExchanger`](crate::client::DirectExchanger), sending requests to twitch directly.
+    #[cfg(feature = "client")]
+    pub fn token_exchanger(
+        mut self,
+        exchanger: impl crate::client::TokenExchanger + 'static,
+    ) -> Self {
+        self.token_exchanger = Some(std::sync::Arc::new(exchanger));
         self
     }
 
     /// Generate the URL to request a code.
     ///
     /// Step 1. in the [guide](https://dev.twitch.tv/docs/authentication/getting-tokens-oauth/#oauth-authorization-code-flow)
-    pub fn generate_url(&mut self) -> url::Url {
-        let mut url = crate::AUTH_URL.clone();
-        let mut auth = vec![
-            ("response_type", "code"),
-            ("client_id", self.client_id.as_str()),
-            ("redirect_uri", self.redirect_url.as_str()),
-        ];
+    ///
+    /// This does not run the [scope validator](UserTokenBuilder::scope_validator), use [`UserTokenBuilder::try_generate_url`] to enforce it.
+    pub fn generate_url(&mut self) -> url::Url { self.authorize_url_parts().to_url() }
+
+    /// Generate the URL to request a code, checking the scopes with the [scope validator](UserTokenBuilder::scope_validator) first.
+    pub fn try_generate_url(&mut self) -> Result<url::Url, ScopesRejected> {
+        Ok(self.url_parts()?.to_url())
+    }
 
-        if let Some(csrf) = &self.csrf {
-            auth.push(("state", csrf.secret()));
+    /// Check the scopes with the [scope validator](UserTokenBuilder::scope_validator), if any.
+    fn check_scopes(&self) -> Result<(), ScopesRejected> {
+        match &self.scope_validator {
+            Some(validator) => validator(&self.scopes).map_err(ScopesRejected),
+            None => Ok(()),
         }
+    }
 
-        url.query_pairs_mut().extend_pairs(auth);
+    /// Generate an URL for each of `logins`, to onboard many accounts at once.
+    ///
+    /// Every URL shares the client id, redirect url and scopes of this builder, forces the user to verify and has the login as `login_hint`,
+    /// so that the right account is used. Each URL gets its own random CSRF token, complete the flow for an account with a builder made with [`UserTokenBuilder::with_csrf`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::{tokens::UserTokenBuilder, Scope};
+    /// let builder = UserTokenBuilder::new(
+    ///     "myclientid",
+    ///     "myclientsecret",
+    ///     url::Url::parse("http://localhost/twitch/register")?,
+    /// )
+    /// .set_scopes(vec![Scope::ChannelReadSubscriptions]);
+    /// for account in builder.generate_urls_for_logins(["broadcaster_a".into(), "broadcaster_b".into()])? {
+    ///     println!("{}: {}", account.login, account.url);
+    ///     // store `account.csrf` to complete the flow
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn generate_urls_for_logins(
+        &self,
+        logins: impl IntoIterator<Item = UserName>,
+    ) -> Result<Vec<AccountAuthUrl>, ScopesRejected> {
+        self.check_scopes()?;
+        Ok(logins
+            .into_iter()
+            .map(|login| {
+                let csrf = crate::types::CsrfToken::new_random();
+                let mut parts = authorize_url_parts(
+                    "code",
+                    &self.client_id,
+                    &self.redirect_url,
+                    Some(&csrf),
+                    &self.scope_order.apply(&self.scopes),
+                    true,
+                    self.prompt.as_ref(),
+                );
+                parts.set("login_hint", login.as_str());
+                AccountAuthUrl {
+                    url: parts.to_url(),
+                    login,
+                    csrf,
+                }
+            })
+            .collect())
+    }
 
-        if !self.scopes.is_empty() {
-            url.query_pairs_mut()
-                .append_pair("scope", &self.scopes.as_slice().join(" "));
-        }
+    /// Set a function to check the scopes against when generating the url, to enforce which combinations of scopes can be requested.
+    ///
+    /// The function returns an error message when the scopes are not allowed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::{tokens::UserTokenBuilder, Scope};
+    /// let mut builder = UserTokenBuilder::new(
+    ///     "myclientid",
+    ///     "myclientsecret",
+    ///     url::Url::parse("http://localhost/twitch/register")?,
+    /// )
+    /// .set_scopes(vec![Scope::ChatEdit, Scope::ModeratorManageBannedUsers])
+    /// .scope_validator(|scopes| {
+    ///     if scopes.contains(&Scope::ChatEdit) && scopes.contains(&Scope::ModeratorManageBannedUsers) {
+    ///         return Err("chat bots can't moderate".to_owned());
+    ///     }
+    ///     Ok(())
+    /// });
+    /// assert!(builder.try_generate_url().is_err());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn scope_validator(
+        mut self,
+        f: impl Fn(&[Scope]) -> Result<(), String> + Send + Sync + 'static,
+    ) -> Self {
+        self.scope_validator = Some(std::sync::Arc::new(f));
+        self
+    }
 
-        if self.force_verify {
-            url.query_pairs_mut().append_pair("force_verify", "true");
-        };
-        url
+    /// Get the parts of the URL to request a code, to inspect or change the parameters before assembling it with [`AuthUrlParts::to_url`].
+    ///
+    /// Fails if the [scope validator](UserTokenBuilder::scope_validator) rejects the scopes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::{tokens::UserTokenBuilder, Scope};
+    /// let builder = UserTokenBuilder::new(
+    ///     "myclientid",
+    ///     "myclientsecret",
+    ///     url::Url::parse("http://localhost/twitch/register")?,
+    /// )
+    /// .set_scopes(vec![Scope::ChatRead]);
+    /// let mut parts = builder.url_parts()?;
+    /// assert_eq!(parts.get("scope"), Some("chat:read"));
+    /// parts.set("force_verify", "true");
+    /// let url = parts.to_url();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn url_parts(&self) -> Result<AuthUrlParts, ScopesRejected> {
+        self.check_scopes()?;
+        Ok(self.authorize_url_parts())
+    }
+
+    fn authorize_url_parts(&self) -> AuthUrlParts {
+        authorize_url_parts(
+            "code",
+            &self.client_id,
+            &self.redirect_url,
+            self.csrf.as_deref(),
+            &self.scope_order.apply(&self.scopes),
+            self.force_verify,
+            self.prompt.as_ref(),
+        )
     }
 
     /// Check if the CSRF is valid
@@ -383,18 +1543,19 @@ impl UserTokenBuilder {
     /// use url::Url;
     /// let callback_url = Url::parse("http://localhost/twitch/register")?;
     /// let mut builder = UserTokenBuilder::new("myclientid", "myclientsecret", callback_url);
-    /// let (url, _csrf_code) = builder.generate_url();
+    /// # let mut builder = builder.set_csrf(Some("xxxxxxx".into()));
+    /// let url = builder.generate_url();
     ///
     /// // Direct the user to this url.
     /// // Later when your server gets a response on `callback_url` with `?code=xxxxxxx&state=xxxxxxx&scope=aa%3Aaa+bb%3Abb`
     ///
     /// // validate the state
-    /// # let state_in_query = _csrf_code.secret();
+    /// # let state_in_query = "xxxxxxx";
     /// if !builder.csrf_is_valid(state_in_query) {
     ///     panic!("state mismatched")
     /// }
     /// // and then get your token
-    /// # let code_in_query = _csrf_code.secret();
+    /// # let code_in_query = "xxxxxxx";
     /// let request = builder.get_user_token_request(code_in_query);
     ///
     /// // use your favorite http client
@@ -421,76 +1582,324 @@ impl UserTokenBuilder {
         params.insert("code", code);
         params.insert("grant_type", "authorization_code");
         params.insert("redirect_uri", self.redirect_url.as_str());
+        for (key, value) in &self.extra_token_params {
+            params.entry(key.as_str()).or_insert(value.as_str());
+        }
 
-        crate::construct_request(
+        let mut headers = HeaderMap::new();
+        if self.fresh_connection {
+            headers.insert(
+                http::header::CONNECTION,
+                http::HeaderValue::from_static("close"),
+            );
+        }
+        crate::construct_request_encoded(
             &crate::TOKEN_URL,
             &params,
-            HeaderMap::new(),
+            headers,
             Method::POST,
             vec![],
+            self.body_encoding,
         )
     }
 
+    /// Get all requests made by [`UserTokenBuilder::get_user_token`], without sending them.
+    ///
+    /// `get_user_token` first exchanges the code for a token with [`exchange`](UserTokenRequests::exchange),
+    /// and then validates the returned token with [`validate`](UserTokenRequests::validate).
+    /// Since the token is only known after the exchange, pass the token you expect twitch to return as `access_token`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::{tokens::UserTokenBuilder, AccessToken};
+    /// let builder = UserTokenBuilder::new(
+    ///     "myclientid",
+    ///     "myclientsecret",
+    ///     url::Url::parse("http://localhost/twitch/register")?,
+    /// );
+    /// let requests = builder.get_user_token_requests("code", &AccessToken::from("token"));
+    /// assert_eq!(requests.exchange.method(), http::Method::POST);
+    /// assert_eq!(requests.validate.method(), http::Method::GET);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn get_user_token_requests(
+        &self,
+        code: &str,
+        access_token: &AccessTokenRef,
+    ) -> UserTokenRequests {
+        let mut validate = access_token.validate_token_request();
+        if self.fresh_connection {
+            validate.headers_mut().insert(
+                http::header::CONNECTION,
+                http::HeaderValue::from_static("close"),
+            );
+        }
+        UserTokenRequests {
+            exchange: self.get_user_token_request(code),
+            validate,
+        }
+    }
+
     /// Generate the code with the help of the authorization code
     ///
     /// Step 3. and 4. in the [guide](https://dev.twitch.tv/docs/authentication/getting-tokens-oauth/#oauth-authorization-code-flow)
     ///
     /// On failure to authenticate due to wrong redirect url or other errors, twitch redirects the user to `<redirect_url or first defined url in dev console>?error=<error type>&error_description=<description of error>`
+    ///
+    /// The code can be a `&str` or a [`AuthorizationCode`](crate::AuthorizationCode), use [`AuthorizationCode::parse`](crate::AuthorizationCode::parse) to check a code pasted by the user.
     #[cfg(feature = "client")]
-    pub async fn get_user_token<'a, C>(
+    pub async fn get_user_token<C>(
         self,
-        http_client: &'a C,
-        code: &str,
+        http_client: &C,
+        code: impl AsRef<str>,
     ) -> Result<UserToken, UserTokenExchangeError<<C as Client>::Error>>
     where
         C: Client,
     {
-        let req = self.get_user_token_request(code);
+        let http_client = &crate::client::HookedClient::new(http_client, self.on_request.as_ref())
+            .fresh_connection(self.fresh_connection)
+            .exchanger(self.token_exchanger.as_deref());
+        let req = self.get_user_token_request(code.as_ref());
 
         let resp = http_client
             .req(req)
             .await
             .map_err(UserTokenExchangeError::RequestError)?;
 
-        let response = crate::id::TwitchTokenResponse::from_response(&resp)?;
+        let response =
+            crate::id::TwitchTokenResponse::from_response_with(&resp, self.strict_token_type)?;
         let validated = response.access_token.validate_token(http_client).await?;
 
-        UserToken::from_response(response, validated, self.client_secret)
-            .map_err(|v| v.into_other().into())
+        let mut token = UserToken::from_response(response, validated, self.client_secret)
+            .map_err(|v| v.into_other())?
+            .with_requested_scopes(&self.scopes);
+        token.token_exchanger = self.token_exchanger.clone();
+        token.on_request = self.on_request.clone();
+        token.strict_token_type = self.strict_token_type;
+        if let Some(environment) = self.environment {
+            token.set_environment(environment);
+        }
+        Ok(token)
     }
-}
-
-/// Builder for [OAuth implicit code flow](https://dev.twitch.tv/docs/authentication/getting-tokens-oauth/#oauth-implicit-code-flow)
-///
-/// See [`UserTokenBuilder`] for the [OAuth authorization code flow](https://dev.twitch.tv/docs/authentication/getting-tokens-oauth/#oauth-authorization-code-flow) (requires Client Secret, generally more secure)
-pub struct ImplicitUserTokenBuilder {
-    pub(crate) scopes: Vec<Scope>,
-    pub(crate) csrf: Option<crate::types::CsrfToken>,
-    pub(crate) redirect_url: url::Url,
-    pub(crate) force_verify: bool,
-    client_id: ClientId,
-}
 
-impl ImplicitUserTokenBuilder {
-    /// Create a [`ImplicitUserTokenBuilder`]
+    /// Complete the flow with the query of the request twitch redirected the user to, exchanging the code for a [`UserToken`].
     ///
-    /// # Notes
+    /// The `code`, `state`, `error` and `error_description` parameters are read from the query, the state is checked and the code is exchanged with [`UserTokenBuilder::get_user_token`].
     ///
-    /// The `url` crate converts empty paths into "/" (such as `https://example.com` into `https://example.com/`),
-    /// which means that you'll need to add `https://example.com/` to your redirect URIs (note the "trailing" slash) if you want to use an empty path.
+    /// # Examples
     ///
-    /// To avoid this, use a path such as `https://example.com/twitch/register` or similar instead, where the `url` crate would not add a trailing `/`.
-    pub fn new(client_id: ClientId, redirect_url: url::Url) -> ImplicitUserTokenBuilder {
-        ImplicitUserTokenBuilder {
-            scopes: vec![],
-            redirect_url,
-            csrf: None,
-            force_verify: false,
-            client_id,
-        }
+    /// ```rust,no_run
+    /// # #[tokio::main]
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+    /// use twitch_oauth2::tokens::UserTokenBuilder;
+    /// let mut builder = UserTokenBuilder::new(
+    ///     "myclientid",
+    ///     "myclientsecret",
+    ///     url::Url::parse("http://localhost/twitch/register")?,
+    /// );
+    /// let url = builder.generate_url();
+    /// // redirect the user to the url, and get the query twitch redirected them back with
+    /// # let query = "";
+    /// let client = reqwest::Client::builder()
+    ///     .redirect(reqwest::redirect::Policy::none())
+    ///     .build()?;
+    /// let token = builder.complete_from_query(&client, query).await?;
+    /// # Ok(())}
+    /// # fn main() {run();}
+    /// ```
+    #[cfg(feature = "client")]
+    pub async fn complete_from_query<C>(
+        self,
+        http_client: &C,
+        query: &str,
+    ) -> Result<UserToken, UserTokenExchangeError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        self.complete(http_client, TwitchCallbackQuery::parse(query))
+            .await
     }
 
-    /// Add scopes to the request
+    /// Complete the flow with the query twitch redirected the user with, exchanging the code for a [`UserToken`].
+    ///
+    /// This is the same as [`UserTokenBuilder::complete_from_query`], but takes a [`TwitchCallbackQuery`],
+    /// which can be extracted from the request by web frameworks using serde.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # #[tokio::main]
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+    /// use twitch_oauth2::tokens::{TwitchCallbackQuery, UserTokenBuilder};
+    /// # let builder = UserTokenBuilder::new("myclientid", "myclientsecret", url::Url::parse("http://localhost/twitch/register")?);
+    /// // e.g. with axum, `Query(query): Query<TwitchCallbackQuery>`
+    /// # let query = TwitchCallbackQuery::default();
+    /// let client = reqwest::Client::builder()
+    ///     .redirect(reqwest::redirect::Policy::none())
+    ///     .build()?;
+    /// let token = builder.complete(&client, query).await?;
+    /// # Ok(())}
+    /// # fn main() {run();}
+    /// ```
+    #[cfg(feature = "client")]
+    pub async fn complete<C>(
+        self,
+        http_client: &C,
+        query: TwitchCallbackQuery,
+    ) -> Result<UserToken, UserTokenExchangeError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        let TwitchCallbackQuery {
+            code,
+            state,
+            error,
+            error_description: description,
+            ..
+        } = query;
+
+        if error.is_some() || description.is_some() {
+            // Twitch does not always include the state on errors, if it's there it should still match.
+            if state.map(|s| !self.csrf_is_valid(&s)).unwrap_or_default() {
+                return Err(UserTokenExchangeError::StateMismatch);
+            }
+            if error.as_deref() == Some("access_denied") {
+                return Err(UserTokenExchangeError::AccessDenied { description });
+            }
+            if let Some(error) = error.as_deref().filter(|e| is_interaction_required(e)) {
+                return Err(UserTokenExchangeError::InteractionRequired {
+                    error: error.to_owned(),
+                    description,
+                });
+            }
+            return Err(UserTokenExchangeError::TwitchError { error, description });
+        }
+        if !state.map(|s| self.csrf_is_valid(&s)).unwrap_or_default() {
+            return Err(UserTokenExchangeError::StateMismatch);
+        }
+        let code = code.ok_or(UserTokenExchangeError::MissingCode)?;
+        self.get_user_token(http_client, &code).await
+    }
+
+    /// Exchange a code the user copied manually for a [`UserToken`], for example in a CLI app using a loopback redirect.
+    ///
+    /// This is the same as [`UserTokenBuilder::get_user_token`], but the `state` is checked first and both the `code` and `state` are percent-decoded,
+    /// as they may be encoded when copied from the browser address bar. Returns [`UserTokenExchangeError::InvalidEncoding`] if they don't decode to UTF-8.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # #[tokio::main]
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error + 'static>>{
+    /// use twitch_oauth2::tokens::UserTokenBuilder;
+    /// let mut builder = UserTokenBuilder::new(
+    ///     "myclientid",
+    ///     "myclientsecret",
+    ///     url::Url::parse("http://localhost/twitch/register")?,
+    /// );
+    /// let url = builder.generate_url();
+    /// // let the user open the url and paste the code and state they were redirected with
+    /// # let (code, state) = ("", "");
+    /// let client = reqwest::Client::builder()
+    ///     .redirect(reqwest::redirect::Policy::none())
+    ///     .build()?;
+    /// let token = builder.exchange_pasted_code(&client, code, state).await?;
+    /// # Ok(())}
+    /// # fn main() {run();}
+    /// ```
+    #[cfg(feature = "client")]
+    pub async fn exchange_pasted_code<C>(
+        self,
+        http_client: &C,
+        code: &str,
+        state: &str,
+    ) -> Result<UserToken, UserTokenExchangeError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        let state = percent_encoding::percent_decode_str(state.trim())
+            .decode_utf8()
+            .map_err(UserTokenExchangeError::InvalidEncoding)?;
+        if !self.csrf_is_valid(&state) {
+            return Err(UserTokenExchangeError::StateMismatch);
+        }
+        let code = percent_encoding::percent_decode_str(code.trim())
+            .decode_utf8()
+            .map_err(UserTokenExchangeError::InvalidEncoding)?;
+        self.get_user_token(http_client, &code).await
+    }
+}
+
+/// Requests made when exchanging a code for a [`UserToken`], see [`UserTokenBuilder::get_user_token_requests`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct UserTokenRequests {
+    /// Request exchanging the code for a token, parse the response with [`TwitchTokenResponse::from_response`](crate::id::TwitchTokenResponse::from_response)
+    pub exchange: http::Request<Vec<u8>>,
+    /// Request validating the token, parse the response with [`ValidatedToken::from_response`]
+    pub validate: http::Request<Vec<u8>>,
+}
+
+/// Builder for [OAuth implicit code flow](https://dev.twitch.tv/docs/authentication/getting-tokens-oauth/#oauth-implicit-code-flow)
+///
+/// See [`UserTokenBuilder`] for the [OAuth authorization code flow](https://dev.twitch.tv/docs/authentication/getting-tokens-oauth/#oauth-authorization-code-flow) (requires Client Secret, generally more secure)
+pub struct ImplicitUserTokenBuilder {
+    pub(crate) scopes: Vec<Scope>,
+    pub(crate) csrf: Option<crate::types::CsrfToken>,
+    pub(crate) redirect_url: url::Url,
+    pub(crate) force_verify: bool,
+    pub(crate) scope_order: ScopeOrder,
+    pub(crate) response_types: Vec<String>,
+    /// Only read when validating the token in [`ImplicitUserTokenBuilder::get_user_token`]
+    #[cfg_attr(not(feature = "client"), allow(dead_code))]
+    pub(crate) fresh_connection: bool,
+    #[cfg(feature = "client")]
+    pub(crate) on_request: Option<crate::client::RequestHook>,
+    client_id: ClientId,
+}
+
+impl ImplicitUserTokenBuilder {
+    /// Create a [`ImplicitUserTokenBuilder`]
+    ///
+    /// # Notes
+    ///
+    /// The `url` crate converts empty paths into "/" (such as `https://example.com` into `https://example.com/`),
+    /// which means that you'll need to add `https://example.com/` to your redirect URIs (note the "trailing" slash) if you want to use an empty path.
+    ///
+    /// To avoid this, use a path such as `https://example.com/twitch/register` or similar instead, where the `url` crate would not add a trailing `/`.
+    pub fn new(client_id: ClientId, redirect_url: url::Url) -> ImplicitUserTokenBuilder {
+        ImplicitUserTokenBuilder {
+            scopes: vec![],
+            redirect_url,
+            csrf: None,
+            force_verify: false,
+            scope_order: ScopeOrder::default(),
+            response_types: vec!["token".to_owned()],
+            fresh_connection: false,
+            #[cfg(feature = "client")]
+            on_request: None,
+            client_id,
+        }
+    }
+
+    /// Create a [`ImplicitUserTokenBuilder`], checking that the redirect url is sent to twitch exactly as given.
+    ///
+    /// Returns [`RedirectUrlError::TrailingSlash`] if the `url` crate would add a trailing slash to the redirect url, see [`ImplicitUserTokenBuilder::new`],
+    /// and [`RedirectUrlError::Fragment`] if the redirect url has a fragment, which twitch rejects.
+    pub fn try_new(
+        client_id: ClientId,
+        redirect_url: &str,
+    ) -> Result<ImplicitUserTokenBuilder, RedirectUrlError> {
+        Ok(Self::new(client_id, parse_redirect_url(redirect_url)?))
+    }
+
+    /// Get the redirect url exactly as it will be sent to twitch.
+    ///
+    /// This has to match one of the redirect urls registered in the [developer console](https://dev.twitch.tv/console).
+    pub fn redirect_url_as_registered(&self) -> &str { self.redirect_url.as_str() }
+
+    /// Add scopes to the request
     pub fn set_scopes(mut self, scopes: Vec<Scope>) -> Self {
         self.scopes = scopes;
         self
@@ -505,32 +1914,90 @@ impl ImplicitUserTokenBuilder {
         self
     }
 
+    /// Set the order of the scopes in the generated url, see [`UserTokenBuilder::scope_order`].
+    pub fn scope_order(mut self, order: ScopeOrder) -> Self {
+        self.scope_order = order;
+        self
+    }
+
+    /// Ask the http client to use a fresh connection when validating the token in [`ImplicitUserTokenBuilder::get_user_token`],
+    /// see [`UserTokenBuilder::force_fresh_connection`].
+    ///
+    /// Defaults to `false`.
+    pub fn force_fresh_connection(mut self, force: bool) -> Self {
+        self.fresh_connection = force;
+        self
+    }
+
+    /// Set the response types to request, defaults to `["token"]`.
+    ///
+    /// Use `["token", "id_token"]` for the [OIDC implicit flow](https://dev.twitch.tv/docs/authentication/getting-tokens-oidc/#oidc-implicit-grant-flow),
+    /// which also needs the `openid` scope. The id token is found in [`FragmentParams::id_token`], it is not verified by this crate.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::{tokens::ImplicitUserTokenBuilder, Scope};
+    /// let mut builder = ImplicitUserTokenBuilder::new(
+    ///     "myclientid".into(),
+    ///     url::Url::parse("http://localhost/twitch/register")?,
+    /// )
+    /// .set_scopes(vec![Scope::Other("openid".into())])
+    /// .with_response_types(&["token", "id_token"]);
+    /// let (url, _csrf) = builder.generate_url();
+    /// assert!(url.as_str().contains("response_type=token+id_token"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_response_types(mut self, response_types: &[&str]) -> Self {
+        self.response_types = response_types.iter().map(|&s| s.to_owned()).collect();
+        self
+    }
+
+    /// Set a function to call with every request made in [`ImplicitUserTokenBuilder::get_user_token`], right before it is sent.
+    ///
+    /// The function can change the headers of the request. The returned token keeps the function, and calls it when refreshing or validating, see [`UserToken::set_on_request`].
+    ///
+    /// Secrets in the request, like the access token, are replaced with `[redacted]`.
+    /// Only changes to the headers are sent, a redacted `Authorization` header that is left as is will be sent with the secret.
+    /// Use [`ImplicitUserTokenBuilder::on_request_with_secrets`] to see the request as sent.
+    #[cfg(feature = "client")]
+    pub fn on_request(
+        mut self,
+        f: impl Fn(&mut http::Request<Vec<u8>>) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_request = Some(crate::client::RequestHook::new(f, true));
+        self
+    }
+
+    /// Set a function to call with every request made in [`ImplicitUserTokenBuilder::get_user_token`], right before it is sent.
+    ///
+    /// # Notes
+    ///
+    /// Unlike [`ImplicitUserTokenBuilder::on_request`], the request is not redacted and contains secrets, take care to not log them. Any part of the request can be changed.
+    #[cfg(feature = "client")]
+    pub fn on_request_with_secrets(
+        mut self,
+        f: impl Fn(&mut http::Request<Vec<u8>>) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_request = Some(crate::client::RequestHook::new(f, false));
+        self
+    }
+
     /// Generate the URL to request a token.
     ///
     /// Step 1. in the [guide](https://dev.twitch.tv/docs/authentication/getting-tokens-oauth/#auth-implicit-code-flow)
     pub fn generate_url(&mut self) -> (url::Url, crate::types::CsrfToken) {
         let csrf = crate::types::CsrfToken::new_random();
         self.csrf = Some(csrf.clone());
-        let mut url = crate::AUTH_URL.clone();
-
-        let auth = vec![
-            ("response_type", "token"),
-            ("client_id", self.client_id.as_str()),
-            ("redirect_uri", self.redirect_url.as_str()),
-            ("state", csrf.as_str()),
-        ];
-
-        url.query_pairs_mut().extend_pairs(auth);
-
-        if !self.scopes.is_empty() {
-            url.query_pairs_mut()
-                .append_pair("scope", &self.scopes.as_slice().join(" "));
-        }
-
-        if self.force_verify {
-            url.query_pairs_mut().append_pair("force_verify", "true");
-        };
-
+        let url = authorize_url(
+            &self.response_types.join(" "),
+            &self.client_id,
+            &self.redirect_url,
+            Some(&csrf),
+            &self.scope_order.apply(&self.scopes),
+            self.force_verify,
+            None,
+        );
         (url, csrf)
     }
 
@@ -556,6 +2023,8 @@ impl ImplicitUserTokenBuilder {
     /// On failure, they are sent to
     ///
     /// `<redirect_url or first defined url in dev console>?error=<error type>&error_description=<error description>&state=<csrf state>`
+    ///
+    /// The `state` may be missing on failure, in which case the error from twitch is returned. If the `state` is present, it's still checked.
     /// Get the hash of the url with javascript.
     ///
     /// ```js
@@ -636,9 +2105,9 @@ impl ImplicitUserTokenBuilder {
     ///
     ///
     #[cfg(feature = "client")]
-    pub async fn get_user_token<'a, C>(
+    pub async fn get_user_token<C>(
         self,
-        http_client: &'a C,
+        http_client: &C,
         state: Option<&str>,
         access_token: Option<&str>,
         error: Option<&str>,
@@ -647,102 +2116,2602 @@ impl ImplicitUserTokenBuilder {
     where
         C: Client,
     {
+        if error.is_some() || error_description.is_some() {
+            // Twitch does not always include the state on errors, if it's there it should still match.
+            if state.map(|s| !self.csrf_is_valid(s)).unwrap_or_default() {
+                return Err(ImplicitUserTokenExchangeError::StateMismatch);
+            }
+            if error == Some("access_denied") {
+                return Err(ImplicitUserTokenExchangeError::AccessDenied {
+                    description: error_description.map(|s| s.to_string()),
+                });
+            }
+            if let Some(error) = error.filter(|e| is_interaction_required(e)) {
+                return Err(ImplicitUserTokenExchangeError::InteractionRequired {
+                    error: error.to_string(),
+                    description: error_description.map(|s| s.to_string()),
+                });
+            }
+            return Err(ImplicitUserTokenExchangeError::TwitchError {
+                error: error.map(|s| s.to_string()),
+                description: error_description.map(|s| s.to_string()),
+            });
+        }
+
         if !state.map(|s| self.csrf_is_valid(s)).unwrap_or_default() {
             return Err(ImplicitUserTokenExchangeError::StateMismatch);
         }
+        let http_client = &crate::client::HookedClient::new(http_client, self.on_request.as_ref())
+            .fresh_connection(self.fresh_connection);
 
-        match (access_token, error, error_description) {
-            (Some(access_token), None, None) => UserToken::from_existing(
+        match access_token {
+            Some(access_token) => UserToken::from_existing(
                 http_client,
                 crate::types::AccessToken::from(access_token),
                 None,
                 None,
             )
             .await
+            .map(|mut token| {
+                token.on_request = self.on_request.clone();
+                token.with_requested_scopes(&self.scopes)
+            })
             .map_err(Into::into),
-            (_, error, description) => {
-                let (error, description) = (
-                    error.map(|s| s.to_string()),
-                    description.map(|s| s.to_string()),
-                );
-                Err(ImplicitUserTokenExchangeError::TwitchError { error, description })
-            }
+            None => Err(ImplicitUserTokenExchangeError::TwitchError {
+                error: None,
+                description: None,
+            }),
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::id::TwitchTokenResponse;
+    /// Parse the parameters Twitch sent to the redirect url.
+    ///
+    /// On success, these are found in the fragment of the url (`#access_token=...`), on failure they are found in the query (`?error=...`).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::tokens::ImplicitUserTokenBuilder;
+    /// let url = url::Url::parse("http://localhost/twitch/register#access_token=0123456789abcdefghijABCDEFGHIJ&scope=channel%3Amanage%3Aredemptions%20user%3Aread%3Aemail&state=c3ab8aa609ea11e793ae92361f002671&token_type=bearer")?;
+    /// let params = ImplicitUserTokenBuilder::parse_fragment(&url);
+    /// assert_eq!(params.state.as_deref(), Some("c3ab8aa609ea11e793ae92361f002671"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn parse_fragment(url: &url::Url) -> FragmentParams {
+        match url.fragment() {
+            Some(fragment) if !fragment.is_empty() => FragmentParams::parse(fragment),
+            _ => FragmentParams::parse(url.query().unwrap_or_default()),
+        }
+    }
 
-    pub use super::*;
+    /// Generate the code with the help of the full redirect url.
+    ///
+    /// This is the same as [`ImplicitUserTokenBuilder::get_user_token`], but parses the parameters with [`ImplicitUserTokenBuilder::parse_fragment`] first.
+    ///
+    /// If an id token was requested with [`ImplicitUserTokenBuilder::with_response_types`] but not returned, [`ImplicitUserTokenExchangeError::MissingIdToken`] is returned.
+    #[cfg(feature = "client")]
+    pub async fn get_user_token_from_url<C>(
+        self,
+        http_client: &C,
+        url: &url::Url,
+    ) -> Result<UserToken, ImplicitUserTokenExchangeError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        self.get_user_token_from_params(http_client, Self::parse_fragment(url))
+            .await
+    }
 
-    #[test]
-    fn from_validated_and_token() {
-        let body = br#"
+    /// Generate the code with the help of the fragment twitch redirected to.
+    ///
+    /// This is the string given by `document.location.hash.substr(1)` in the snippet shown in [`ImplicitUserTokenBuilder::get_user_token`],
+    /// for example `access_token=...&scope=...&state=...&token_type=bearer`. A leading `#` is ignored.
+    ///
+    /// This is the same as [`ImplicitUserTokenBuilder::get_user_token`], but parses the parameters with [`FragmentParams::parse`] first.
+    ///
+    /// If an id token was requested with [`ImplicitUserTokenBuilder::with_response_types`] but not returned, [`ImplicitUserTokenExchangeError::MissingIdToken`] is returned.
+    #[cfg(feature = "client")]
+    pub async fn get_user_token_from_fragment<C>(
+        self,
+        http_client: &C,
+        fragment: &str,
+    ) -> Result<UserToken, ImplicitUserTokenExchangeError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        let fragment = fragment.strip_prefix('#').unwrap_or(fragment);
+        self.get_user_token_from_params(http_client, FragmentParams::parse(fragment))
+            .await
+    }
+
+    /// Generate the code with already parsed parameters, for example from [`FragmentParams::from_json`].
+    ///
+    /// This is the same as [`ImplicitUserTokenBuilder::get_user_token`], but takes the parameters as [`FragmentParams`].
+    ///
+    /// If an id token was requested with [`ImplicitUserTokenBuilder::with_response_types`] but not returned, [`ImplicitUserTokenExchangeError::MissingIdToken`] is returned.
+    #[cfg(feature = "client")]
+    pub async fn get_user_token_from_params<C>(
+        self,
+        http_client: &C,
+        params: FragmentParams,
+    ) -> Result<UserToken, ImplicitUserTokenExchangeError<<C as Client>::Error>>
+    where
+        C: Client,
+    {
+        if params.error.is_none()
+            && params.id_token.is_none()
+            && self.response_types.iter().any(|t| t == "id_token")
+            && params
+                .state
+                .as_deref()
+                .map(|s| self.csrf_is_valid(s))
+                .unwrap_or_default()
         {
-            "client_id": "wbmytr93xzw8zbg0p1izqyzzc5mbiz",
-            "login": "twitchdev",
-            "scopes": [
-              "channel:read:subscriptions"
-            ],
-            "user_id": "141981764",
-            "expires_in": 5520838
+            return Err(ImplicitUserTokenExchangeError::MissingIdToken);
         }
-        "#;
-        let response = http::Response::builder().status(200).body(body).unwrap();
-        let validated = ValidatedToken::from_response(&response).unwrap();
-        let body = br#"
-        {
-            "access_token": "rfx2uswqe8l4g1mkagrvg5tv0ks3",
-            "expires_in": 14124,
-            "refresh_token": "5b93chm6hdve3mycz05zfzatkfdenfspp1h1ar2xxdalen01",
-            "scope": [
-                "channel:read:subscriptions"
-            ],
-            "token_type": "bearer"
-          }
-        "#;
-        let response = http::Response::builder().status(200).body(body).unwrap();
-        let response = TwitchTokenResponse::from_response(&response).unwrap();
+        self.get_user_token(
+            http_client,
+            params.state.as_deref(),
+            params.access_token.as_ref().map(|t| t.secret()),
+            params.error.as_deref(),
+            params.error_description.as_deref(),
+        )
+        .await
+    }
+}
 
-        UserToken::from_response(response, validated, None).unwrap();
+/// Parse a redirect url, making sure it's sent to twitch as given
+fn parse_redirect_url(redirect_url: &str) -> Result<url::Url, RedirectUrlError> {
+    let url = url::Url::parse(redirect_url)?;
+    if url.fragment().is_some() {
+        return Err(RedirectUrlError::Fragment {
+            url: redirect_url.to_owned(),
+        });
+    }
+    let without_query = redirect_url.split(['?', '#']).next().unwrap_or_default();
+    if url.path() == "/" && !without_query.ends_with('/') {
+        return Err(RedirectUrlError::TrailingSlash {
+            given: redirect_url.to_owned(),
+            sent: url.to_string(),
+        });
     }
+    Ok(url)
+}
 
-    #[test]
-    fn generate_url() {
-        UserTokenBuilder::new(
-            ClientId::from("random_client"),
-            ClientSecret::from("random_secret"),
-            url::Url::parse("https://localhost").unwrap(),
-        )
-        .force_verify(true)
-        .generate_url()
-        .0
-        .to_string();
+/// Assemble the url to `https://id.twitch.tv/oauth2/authorize`
+fn authorize_url(
+    response_type: &str,
+    client_id: &ClientIdRef,
+    redirect_url: &url::Url,
+    csrf: Option<&CsrfTokenRef>,
+    scopes: &[Scope],
+    force_verify: bool,
+    prompt: Option<&Prompt>,
+) -> url::Url {
+    authorize_url_parts(
+        response_type,
+        client_id,
+        redirect_url,
+        csrf,
+        scopes,
+        force_verify,
+        prompt,
+    )
+    .to_url()
+}
+
+/// Collect the parameters for the url to `https://id.twitch.tv/oauth2/authorize`
+fn authorize_url_parts(
+    response_type: &str,
+    client_id: &ClientIdRef,
+    redirect_url: &url::Url,
+    csrf: Option<&CsrfTokenRef>,
+    scopes: &[Scope],
+    force_verify: bool,
+    prompt: Option<&Prompt>,
+) -> AuthUrlParts {
+    let mut parts = AuthUrlParts {
+        base: crate::AUTH_URL.clone(),
+        params: vec![],
+    };
+    parts.set("response_type", response_type);
+    parts.set("client_id", client_id.as_str());
+    parts.set("redirect_uri", redirect_url.as_str());
+
+    if let Some(csrf) = csrf {
+        parts.set("state", csrf.secret());
     }
 
-    #[tokio::test]
-    #[ignore]
-    #[cfg(feature = "surf")]
-    async fn get_token() {
-        let mut t = UserTokenBuilder::new(
-            ClientId::new(
-                std::env::var("TWITCH_CLIENT_ID").expect("no env:TWITCH_CLIENT_ID provided"),
-            ),
-            ClientSecret::new(
-                std::env::var("TWITCH_CLIENT_SECRET")
-                    .expect("no env:TWITCH_CLIENT_SECRET provided"),
-            ),
-            url::Url::parse(r#"https://localhost"#).unwrap(),
-        )
-        .force_verify(true);
-        t.csrf = Some(crate::CsrfToken::from("random"));
+    if !scopes.is_empty() {
+        parts.set("scope", scopes.join(" "));
+    }
+
+    if force_verify {
+        parts.set("force_verify", "true");
+    };
+
+    if let Some(prompt) = prompt {
+        parts.set("prompt", prompt.as_str());
+    }
+    parts
+}
+
+/// Order of the scopes in the url to authorize, see [`UserTokenBuilder::scope_order`]
+///
+/// Twitch shows the scopes on the consent screen in the order they appear in the url.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ScopeOrder {
+    /// The order the scopes were added to the builder
+    #[default]
+    Insertion,
+    /// Grouped by [category](Scope::category), with categories and the scopes in them sorted alphabetically
+    Category,
+}
+
+impl ScopeOrder {
+    /// Get the scopes in this order
+    fn apply<'a>(&self, scopes: &'a [Scope]) -> std::borrow::Cow<'a, [Scope]> {
+        match self {
+            ScopeOrder::Insertion => std::borrow::Cow::Borrowed(scopes),
+            ScopeOrder::Category => {
+                let mut scopes = scopes.to_vec();
+                scopes.sort_by(|a, b| a.category().cmp(b.category()).then_with(|| a.cmp(b)));
+                std::borrow::Cow::Owned(scopes)
+            }
+        }
+    }
+}
+
+/// Advice about the requested scopes of an incremental authorization, see [`UserTokenBuilder::scope_advisory`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ScopeAdvisory {
+    /// All requested scopes are already granted, no new permission is requested
+    NoNewScopes {
+        /// The requested scopes, which are all already granted
+        already_granted: Vec<Scope>,
+    },
+}
+
+/// An authorization url for one account, see [`UserTokenBuilder::generate_urls_for_logins`]
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct AccountAuthUrl {
+    /// The login of the account
+    pub login: UserName,
+    /// The url to send the user to
+    pub url: url::Url,
+    /// The CSRF token in the url
+    pub csrf: crate::types::CsrfToken,
+}
+
+/// The parts of an authorization url, see [`UserTokenBuilder::url_parts`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AuthUrlParts {
+    /// The url to `https://id.twitch.tv/oauth2/authorize`, without parameters
+    pub base: url::Url,
+    /// The query parameters, in the order they are added to the url
+    pub params: Vec<(String, String)>,
+}
+
+impl AuthUrlParts {
+    /// Get the value of a parameter
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Set a parameter, replacing the value if it's already set
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        let key = key.into();
+        let value = value.into();
+        match self.params.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => self.params.push((key, value)),
+        }
+    }
+
+    /// Remove a parameter, returning its value
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let i = self.params.iter().position(|(k, _)| k == key)?;
+        Some(self.params.remove(i).1)
+    }
+
+    /// Assemble the url
+    ///
+    /// Parameters are encoded as `application/x-www-form-urlencoded`, so the spaces separating scopes are always encoded as `+`, which twitch accepts.
+    pub fn to_url(&self) -> url::Url {
+        let mut url = self.base.clone();
+        url.query_pairs_mut().extend_pairs(&self.params);
+        url
+    }
+}
+
+/// How redirect urls are compared in [`UserTokenBuilder::check_redirect`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RedirectMatch {
+    /// The redirect urls must match exactly, except for the parameters added by twitch. This is how twitch compares them.
+    #[default]
+    Exact,
+    /// Only the scheme, host, port and path must match
+    IgnoreQuery,
+}
+
+impl RedirectMatch {
+    /// Parameters twitch adds to the redirect url
+    const TWITCH_PARAMS: &'static [&'static str] =
+        &["code", "scope", "state", "error", "error_description"];
+
+    fn matches(&self, expected: &url::Url, found: &url::Url) -> bool {
+        let base = |url: &url::Url| {
+            (
+                url.scheme().to_owned(),
+                url.host_str().map(str::to_owned),
+                url.port_or_known_default(),
+                url.path().to_owned(),
+            )
+        };
+        if base(expected) != base(found) {
+            return false;
+        }
+        match self {
+            RedirectMatch::IgnoreQuery => true,
+            RedirectMatch::Exact => {
+                let query = |url: &url::Url| -> Vec<(String, String)> {
+                    url.query_pairs()
+                        .filter(|(k, _)| !Self::TWITCH_PARAMS.contains(&k.as_ref()))
+                        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                        .collect()
+                };
+                query(expected) == query(found) && expected.fragment() == found.fragment()
+            }
+        }
+    }
+}
+
+/// Redirect urls registered for an application, see [`UserTokenBuilder::validate_redirect`]
+///
+/// Entries are kept as given, since that is how twitch compares them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RedirectAllowlist {
+    urls: Vec<String>,
+}
+
+impl RedirectAllowlist {
+    /// Create an empty allowlist
+    pub fn new() -> Self { Self::default() }
+
+    /// Add a redirect url to the allowlist
+    pub fn with(mut self, url: impl Into<String>) -> Self {
+        self.push(url);
+        self
+    }
+
+    /// Add a redirect url to the allowlist
+    pub fn push(&mut self, url: impl Into<String>) { self.urls.push(url.into()) }
+
+    /// The redirect urls in the allowlist
+    pub fn urls(&self) -> &[String] { &self.urls }
+
+    /// Check if the url, as sent to twitch, is in the allowlist
+    pub fn contains(&self, url: &url::Url) -> bool {
+        self.urls.iter().any(|allowed| allowed == url.as_str())
+    }
+
+    fn validate(&self, url: &url::Url) -> Result<(), RedirectUrlError> {
+        if self.contains(url) {
+            return Ok(());
+        }
+        match self
+            .urls
+            .iter()
+            .find(|allowed| url::Url::parse(allowed).ok().as_ref() == Some(url))
+        {
+            Some(given) => Err(RedirectUrlError::TrailingSlash {
+                given: given.clone(),
+                sent: url.to_string(),
+            }),
+            None => Err(RedirectUrlError::NotAllowed {
+                url: url.to_string(),
+            }),
+        }
+    }
+}
+
+impl<S: Into<String>> FromIterator<S> for RedirectAllowlist {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        Self {
+            urls: iter.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// Errors returned for [`Prompt::None`] when the user would have to interact with twitch
+#[cfg(feature = "client")]
+fn is_interaction_required(error: &str) -> bool {
+    matches!(
+        error,
+        "interaction_required" | "login_required" | "consent_required"
+    )
+}
+
+/// The `prompt` parameter for the authorization url, see [`UserTokenBuilder::prompt`]
+///
+/// This gives finer control than [`force_verify`](UserTokenBuilder::force_verify) over what the user is shown.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Prompt {
+    /// Don't show anything to the user. If the user would have to log in or authorize, twitch redirects back with an error instead.
+    None,
+    /// Have the user log in again
+    Login,
+    /// Have the user authorize the application again
+    Consent,
+}
+
+impl Prompt {
+    /// Get the value sent to twitch
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Prompt::None => "none",
+            Prompt::Login => "login",
+            Prompt::Consent => "consent",
+        }
+    }
+}
+
+/// Query sent by Twitch to the redirect url in the [OAuth authorization code flow](https://dev.twitch.tv/docs/authentication/getting-tokens-oauth/#oauth-authorization-code-flow)
+///
+/// This can be deserialized by the query extractors of web frameworks, and passed to [`UserTokenBuilder::complete`].
+///
+/// # Examples
+///
+/// ```rust
+/// use twitch_oauth2::tokens::TwitchCallbackQuery;
+/// let query = TwitchCallbackQuery::parse("code=thecode&scope=chat%3Aread+chat%3Aedit&state=state");
+/// assert_eq!(query.code.as_deref(), Some("thecode"));
+/// assert_eq!(query.scopes().len(), 2);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[non_exhaustive]
+pub struct TwitchCallbackQuery {
+    /// The authorization code
+    pub code: Option<String>,
+    /// The granted scopes, separated by spaces
+    pub scope: Option<String>,
+    /// The CSRF state
+    pub state: Option<String>,
+    /// Error type
+    pub error: Option<String>,
+    /// Description of error
+    pub error_description: Option<String>,
+}
+
+impl TwitchCallbackQuery {
+    /// Parse the query, with or without the leading `?`
+    pub fn parse(query: &str) -> TwitchCallbackQuery {
+        let query = query.strip_prefix('?').unwrap_or(query);
+        let mut params = TwitchCallbackQuery::default();
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "code" => params.code = Some(value.into_owned()),
+                "scope" => params.scope = Some(value.into_owned()),
+                "state" => params.state = Some(value.into_owned()),
+                "error" => params.error = Some(value.into_owned()),
+                "error_description" => params.error_description = Some(value.into_owned()),
+                _ => {}
+            }
+        }
+        params
+    }
+
+    /// Get the granted scopes
+    pub fn scopes(&self) -> Vec<Scope> {
+        self.scope
+            .as_deref()
+            .unwrap_or_default()
+            .split(' ')
+            .filter(|s| !s.is_empty())
+            .map(|s| Scope::parse(s.to_owned()))
+            .collect()
+    }
+}
+
+/// Parameters sent by Twitch to the redirect url in the [OAuth implicit code flow](https://dev.twitch.tv/docs/authentication/getting-tokens-oauth/#oauth-implicit-code-flow)
+///
+/// Get this with [`ImplicitUserTokenBuilder::parse_fragment`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FragmentParams {
+    /// The access token
+    pub access_token: Option<AccessToken>,
+    /// Scopes attached to the token
+    pub scopes: Option<Vec<Scope>>,
+    /// The CSRF state
+    pub state: Option<String>,
+    /// Type of the token, should always be `bearer`
+    pub token_type: Option<String>,
+    /// The OIDC id token, if requested with [`ImplicitUserTokenBuilder::with_response_types`]
+    pub id_token: Option<String>,
+    /// Error type
+    pub error: Option<String>,
+    /// Description of error
+    pub error_description: Option<String>,
+}
+
+impl FragmentParams {
+    /// Parse the parameters from a fragment or query, without the leading `#` or `?`
+    pub fn parse(fragment: &str) -> FragmentParams {
+        let mut params = FragmentParams::default();
+        for (key, value) in url::form_urlencoded::parse(fragment.as_bytes()) {
+            match key.as_ref() {
+                "access_token" => params.access_token = Some(AccessToken::new(value.into_owned())),
+                "scope" => {
+                    params.scopes = Some(
+                        value
+                            .split(' ')
+                            .filter(|s| !s.is_empty())
+                            .map(|s| Scope::parse(s.to_owned()))
+                            .collect(),
+                    )
+                }
+                "state" => params.state = Some(value.into_owned()),
+                "token_type" => params.token_type = Some(value.into_owned()),
+                "id_token" => params.id_token = Some(value.into_owned()),
+                "error" => params.error = Some(value.into_owned()),
+                "error_description" => params.error_description = Some(value.into_owned()),
+                _ => (),
+            }
+        }
+        params
+    }
+
+    /// Parse the parameters from a JSON object with the same fields, like `{"access_token": "...", "scope": "...", "state": "...", "token_type": "bearer"}`
+    ///
+    /// This is useful for browser extensions, where a content script does the implicit flow and messages the parameters to the background script.
+    /// The `scope` can be either a space separated string, as in the fragment, or an array of scopes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use twitch_oauth2::tokens::FragmentParams;
+    /// let params = FragmentParams::from_json(
+    ///     r#"{"access_token":"0123456789abcdefghijABCDEFGHIJ","scope":"chat:read chat:edit","state":"c3ab8aa609ea11e793ae92361f002671","token_type":"bearer"}"#,
+    /// )?;
+    /// assert_eq!(params.state.as_deref(), Some("c3ab8aa609ea11e793ae92361f002671"));
+    /// // get the token with `ImplicitUserTokenBuilder::get_user_token_from_params`
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_json(json: &str) -> Result<FragmentParams, serde_json::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Scopes {
+            List(String),
+            Array(Vec<String>),
+        }
+
+        #[derive(serde::Deserialize)]
+        struct Payload {
+            access_token: Option<AccessToken>,
+            scope: Option<Scopes>,
+            state: Option<String>,
+            token_type: Option<String>,
+            id_token: Option<String>,
+            error: Option<String>,
+            error_description: Option<String>,
+        }
+
+        let payload: Payload = serde_json::from_str(json)?;
+        let scopes = payload.scope.map(|scope| {
+            let scopes = match scope {
+                Scopes::List(list) => list.split(' ').map(str::to_owned).collect(),
+                Scopes::Array(array) => array,
+            };
+            scopes
+                .into_iter()
+                .filter(|s| !s.is_empty())
+                .map(Scope::parse)
+                .collect()
+        });
+        Ok(FragmentParams {
+            access_token: payload.access_token,
+            scopes,
+            state: payload.state,
+            token_type: payload.token_type,
+            id_token: payload.id_token,
+            error: payload.error,
+            error_description: payload.error_description,
+        })
+    }
+}
+
+#[cfg(test)]
+impl UserToken {
+    /// Token for `login` with id `1234` and client id `clientid`, with the refresh token `refreshtoken` and client secret `secret` if `refreshable`
+    pub(crate) fn test_token(
+        refreshable: bool,
+        scopes: &[Scope],
+        expires_in: Option<std::time::Duration>,
+    ) -> UserToken {
+        UserToken::from_existing_unchecked(
+            "accesstoken",
+            refreshable.then(|| RefreshToken::from("refreshtoken")),
+            "clientid",
+            refreshable.then(|| ClientSecret::from("secret")),
+            "login".into(),
+            "1234".into(),
+            Some(scopes.to_vec()),
+            expires_in,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::id::TwitchTokenResponse;
+
+    pub use super::*;
+
+    #[test]
+    fn from_validated_and_token() {
+        let body = br#"
+        {
+            "client_id": "wbmytr93xzw8zbg0p1izqyzzc5mbiz",
+            "login": "twitchdev",
+            "scopes": [
+              "channel:read:subscriptions"
+            ],
+            "user_id": "141981764",
+            "expires_in": 5520838
+        }
+        "#;
+        let response = http::Response::builder().status(200).body(body).unwrap();
+        let validated = ValidatedToken::from_response(&response).unwrap();
+        let body = br#"
+        {
+            "access_token": "rfx2uswqe8l4g1mkagrvg5tv0ks3",
+            "expires_in": 14124,
+            "refresh_token": "5b93chm6hdve3mycz05zfzatkfdenfspp1h1ar2xxdalen01",
+            "scope": [
+                "channel:read:subscriptions"
+            ],
+            "token_type": "bearer"
+          }
+        "#;
+        let response = http::Response::builder().status(200).body(body).unwrap();
+        let response = TwitchTokenResponse::from_response(&response).unwrap();
+
+        UserToken::from_response(response, validated, None).unwrap();
+    }
+
+    #[test]
+    fn raw_token_response() {
+        let validated: ValidatedToken = serde_json::from_str(
+            r#"{"client_id":"clientid","login":"login","scopes":[],"user_id":"1234","expires_in":14124}"#,
+        )
+        .unwrap();
+        let response: TwitchTokenResponse = serde_json::from_str(
+            r#"{"access_token":"accesstoken","expires_in":14124,"refresh_token":"refreshtoken","id_token":"idtoken","scope":[],"token_type":"bearer"}"#,
+        )
+        .unwrap();
+
+        let token = UserToken::from_response(response, validated, None).unwrap();
+        let raw = token.raw_token_response().unwrap();
+        assert_eq!(raw["access_token"], "[redacted access token]");
+        assert_eq!(raw["refresh_token"], "[redacted refresh token]");
+        assert_eq!(raw["id_token"], "[redacted id token]");
+        assert_eq!(raw["expires_in"], 14124);
+    }
+
+    #[test]
+    fn expiry_precedence() {
+        let validated: ValidatedToken = serde_json::from_str(
+            r#"{"client_id":"clientid","login":"login","scopes":[],"user_id":"1234","expires_in":5520838}"#,
+        )
+        .unwrap();
+        let response = |expires_in: &str| -> TwitchTokenResponse {
+            serde_json::from_str(&format!(
+                r#"{{"access_token":"accesstoken",{expires_in}"refresh_token":"refreshtoken","scope":[],"token_type":"bearer"}}"#
+            ))
+            .unwrap()
+        };
+
+        let token = UserToken::new("accesstoken".into(), None, validated.clone(), None).unwrap();
+        assert_eq!(token.expiry_source(), ExpirySource::Validation);
+        assert!(token.expires_in() > std::time::Duration::from_secs(14124));
+
+        let token =
+            UserToken::from_response(response(r#""expires_in":14124,"#), validated.clone(), None)
+                .unwrap();
+        assert_eq!(token.expiry_source(), ExpirySource::TokenResponse);
+        assert!(token.expires_in() <= std::time::Duration::from_secs(14124));
+
+        let token = UserToken::from_response(response(""), validated, None).unwrap();
+        assert_eq!(token.expiry_source(), ExpirySource::Validation);
+        assert!(token.expires_in() > std::time::Duration::from_secs(14124));
+
+        let token = UserToken::test_token(false, &[], None);
+        assert_eq!(token.expiry_source(), ExpirySource::Given);
+    }
+
+    #[test]
+    fn generate_url() {
+        UserTokenBuilder::new(
+            ClientId::from("random_client"),
+            ClientSecret::from("random_secret"),
+            url::Url::parse("https://localhost").unwrap(),
+        )
+        .force_verify(true)
+        .generate_url()
+        .to_string();
+    }
+
+    #[test]
+    fn reconsent_url() {
+        let token = UserToken::test_token(
+            false,
+            &[Scope::ChatRead, Scope::ChatEdit, Scope::ChannelModerate],
+            None,
+        );
+        let (url, csrf) = token.reconsent_url(
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+            vec![Scope::ChatRead, Scope::ChatEdit],
+        );
+        let params: std::collections::HashMap<_, _> = url.query_pairs().collect();
+        assert_eq!(params["scope"], "chat:read chat:edit");
+        assert_eq!(params["client_id"], "clientid");
+        assert_eq!(params["response_type"], "code");
+        assert_eq!(params["redirect_uri"], "http://localhost/twitch/register");
+        assert_eq!(params["state"], csrf.secret());
+        assert_eq!(params["force_verify"], "true");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn refresh_if_expired() {
+        let client = crate::client::MockClient::default().respond(
+            200,
+            r#"{"access_token":"newaccesstoken","expires_in":14124,"refresh_token":"newrefreshtoken","scope":["chat:read"],"token_type":"bearer"}"#,
+        );
+        let mut token = UserToken::test_token(true, &[], Some(std::time::Duration::ZERO));
+        assert!(token.refresh_if_expired(&client).await.unwrap());
+        assert_eq!(token.access_token.secret(), "newaccesstoken");
+        assert!(!token.is_elapsed());
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+
+        // token is not expired anymore, so no request should be made
+        assert!(!token.refresh_if_expired(&client).await.unwrap());
+        assert_eq!(token.access_token.secret(), "newaccesstoken");
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn refresh_resets_clock() {
+        let client = crate::client::MockClient::default().respond(
+            200,
+            r#"{"access_token":"newaccesstoken","expires_in":60,"refresh_token":"newrefreshtoken","token_type":"bearer"}"#,
+        );
+        let mut token = UserToken::test_token(true, &[], Some(std::time::Duration::from_secs(60)));
+        let backdate = std::time::Duration::from_secs(120);
+        token.struct_created = token.struct_created.checked_sub(backdate).unwrap();
+        token.created_at -= backdate;
+        assert!(token.is_elapsed());
+
+        assert!(token.refresh_if_expired(&client).await.unwrap());
+        assert!(!token.is_elapsed());
+        assert!(token.expires_in() > std::time::Duration::from_secs(50));
+        assert!(token.expires_at().unwrap() > std::time::SystemTime::now());
+        // not refreshed again
+        assert!(!token.refresh_if_expired(&client).await.unwrap());
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn refresh_token_with_outcome() {
+        let client = crate::client::MockClient::default()
+            .respond(
+                200,
+                r#"{"access_token":"newaccesstoken","expires_in":14124,"refresh_token":"newrefreshtoken","scope":["chat:read","chat:edit"],"token_type":"bearer"}"#,
+            )
+            .respond(
+                200,
+                r#"{"access_token":"newaccesstoken","expires_in":14124,"token_type":"bearer"}"#,
+            );
+        let mut token = UserToken::test_token(
+            true,
+            &[Scope::ChatRead, Scope::ModeratorManageBannedUsers],
+            Some(std::time::Duration::ZERO),
+        );
+        let outcome = token.refresh_token_with_outcome(&client).await.unwrap();
+        assert!(outcome.access_token_changed);
+        assert!(outcome.refresh_token_rotated);
+        assert_eq!(outcome.old_expires_in, std::time::Duration::ZERO);
+        assert!(outcome.new_expires_in > std::time::Duration::from_secs(14000));
+        assert_eq!(outcome.scopes_added, vec![Scope::ChatEdit]);
+        assert_eq!(
+            outcome.scopes_removed,
+            vec![Scope::ModeratorManageBannedUsers]
+        );
+        #[cfg(feature = "fingerprint")]
+        {
+            assert_eq!(
+                outcome.old_fingerprint,
+                AccessToken::from("accesstoken").fingerprint()
+            );
+            assert_eq!(outcome.new_fingerprint, token.access_token.fingerprint());
+        }
+        assert!(!format!("{outcome:?}").contains("newaccesstoken"));
+
+        // same access token, no new refresh token and no scopes
+        let outcome = token.refresh_token_with_outcome(&client).await.unwrap();
+        assert!(!outcome.access_token_changed);
+        assert!(!outcome.refresh_token_rotated);
+        assert!(outcome.scopes_added.is_empty());
+        assert!(outcome.scopes_removed.is_empty());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn min_refresh_interval() {
+        let client = crate::client::MockClient::default()
+            .respond(
+                400,
+                r#"{"status":400,"message":"Invalid refresh token"}"#,
+            )
+            .respond(
+                200,
+                r#"{"access_token":"newaccesstoken","expires_in":14124,"refresh_token":"newrefreshtoken","scope":["chat:read"],"token_type":"bearer"}"#,
+            );
+        let mut token = UserToken::test_token(true, &[], Some(std::time::Duration::ZERO));
+        token.set_min_refresh_interval(std::time::Duration::from_secs(60));
+        assert!(matches!(
+            token.refresh_token(&client).await,
+            Err(RefreshTokenError::RequestParseError(_))
+        ));
+        // failed attempts count as well
+        let err = token.refresh_token(&client).await.unwrap_err();
+        assert!(matches!(
+            err,
+            RefreshTokenError::TooSoon { retry_after } if retry_after <= std::time::Duration::from_secs(60) && retry_after > std::time::Duration::from_secs(50)
+        ));
+        assert_eq!(err.http_status(), http::StatusCode::SERVICE_UNAVAILABLE);
+        assert!(token.refresh_if_expired(&client).await.is_err());
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn refresh_keeps_refresh_token() {
+        let client = crate::client::MockClient::default()
+            .respond(
+                200,
+                r#"{"access_token":"newaccesstoken","expires_in":14124,"scope":["chat:read"],"token_type":"bearer"}"#,
+            )
+            .respond(
+                200,
+                r#"{"access_token":"newestaccesstoken","expires_in":14124,"refresh_token":"newrefreshtoken","scope":["chat:read"],"token_type":"bearer"}"#,
+            );
+        let mut token = UserToken::test_token(true, &[], Some(std::time::Duration::ZERO));
+        token.refresh_token(&client).await.unwrap();
+        assert_eq!(token.access_token.secret(), "newaccesstoken");
+        assert_eq!(
+            token.refresh_token.as_ref().unwrap().secret(),
+            "refreshtoken"
+        );
+
+        token.refresh_token(&client).await.unwrap();
+        assert_eq!(token.access_token.secret(), "newestaccesstoken");
+        assert_eq!(
+            token.refresh_token.as_ref().unwrap().secret(),
+            "newrefreshtoken"
+        );
+        let requests = client.requests.lock().unwrap();
+        assert!(requests[1]
+            .uri()
+            .query()
+            .unwrap()
+            .contains("refresh_token=refreshtoken"));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn from_existing_for_client_mismatch() {
+        let client = crate::client::MockClient::default().respond(
+            200,
+            r#"{"client_id":"otherclient","login":"login","scopes":[],"user_id":"1234","expires_in":14124}"#,
+        );
+        let err = UserToken::from_existing_for_client(
+            &client,
+            ClientIdRef::from_static("clientid"),
+            AccessToken::from("accesstoken"),
+            None,
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ValidationError::ClientIdMismatch(crate::tokens::errors::ClientIdMismatch { ref expected, ref found })
+                if expected.as_str() == "clientid" && found.as_str() == "otherclient"
+        ));
+        assert_eq!(err.http_status(), http::StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn exchange_pasted_code() {
+        use crate::client::MockClient;
+
+        let builder = || {
+            UserTokenBuilder::with_csrf(
+                "clientid",
+                "secret",
+                url::Url::parse("http://localhost/twitch/register").unwrap(),
+                "st/ate",
+            )
+        };
+        let client = MockClient::default()
+            .respond(
+                200,
+                r#"{"access_token":"accesstoken","expires_in":14124,"refresh_token":"refreshtoken","scope":["chat:read"],"token_type":"bearer"}"#,
+            )
+            .respond(
+                200,
+                r#"{"client_id":"clientid","login":"login","scopes":["chat:read"],"user_id":"1234","expires_in":14124}"#,
+            );
+        let token = builder()
+            .exchange_pasted_code(&client, "the%2Fcode%3D ", "st%2Fate")
+            .await
+            .unwrap();
+        assert_eq!(token.login.as_str(), "login");
+        {
+            let requests = client.requests.lock().unwrap();
+            let query: std::collections::HashMap<_, _> =
+                url::form_urlencoded::parse(requests[0].uri().query().unwrap().as_bytes())
+                    .collect();
+            assert_eq!(query["code"], "the/code=");
+        }
+
+        let err = builder()
+            .exchange_pasted_code(&client, "thecode", "other")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UserTokenExchangeError::StateMismatch));
+
+        let err = builder()
+            .exchange_pasted_code(&client, "thecode", "st%2Fate%FF")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UserTokenExchangeError::InvalidEncoding(_)));
+        let err = builder()
+            .exchange_pasted_code(&client, "the%C3code", "st%2Fate")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UserTokenExchangeError::InvalidEncoding(_)));
+        assert_eq!(client.requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn complete_from_query() {
+        use crate::client::MockClient;
+
+        let builder = || {
+            UserTokenBuilder::with_csrf(
+                "clientid",
+                "secret",
+                url::Url::parse("http://localhost/twitch/register").unwrap(),
+                "state",
+            )
+        };
+
+        let client = MockClient::default()
+            .respond(
+                200,
+                r#"{"access_token":"accesstoken","expires_in":14124,"refresh_token":"refreshtoken","scope":["chat:read"],"token_type":"bearer"}"#,
+            )
+            .respond(
+                200,
+                r#"{"client_id":"clientid","login":"login","scopes":["chat:read"],"user_id":"1234","expires_in":14124}"#,
+            );
+        let token = builder()
+            .complete_from_query(&client, "?code=thecode&scope=chat%3Aread&state=state")
+            .await
+            .unwrap();
+        assert_eq!(token.access_token.secret(), "accesstoken");
+        assert_eq!(token.login.as_str(), "login");
+        assert!(client.requests.lock().unwrap()[0]
+            .uri()
+            .query()
+            .unwrap()
+            .contains("code=thecode"));
+
+        let client = MockClient::default();
+        assert!(matches!(
+            builder()
+                .complete_from_query(
+                    &client,
+                    "error=access_denied&error_description=The+user+denied+you+access&state=state"
+                )
+                .await,
+            Err(UserTokenExchangeError::AccessDenied { description: Some(description) })
+                if description == "The user denied you access"
+        ));
+        assert!(matches!(
+            builder()
+                .complete_from_query(
+                    &client,
+                    "error=login_required&error_description=The+user+is+not+logged+in&state=state"
+                )
+                .await,
+            Err(UserTokenExchangeError::InteractionRequired { error, description: Some(_) })
+                if error == "login_required"
+        ));
+        assert!(matches!(
+            builder()
+                .complete_from_query(&client, "error=interaction_required&state=state")
+                .await,
+            Err(UserTokenExchangeError::InteractionRequired { error, description: None })
+                if error == "interaction_required"
+        ));
+        assert!(matches!(
+            builder()
+                .complete_from_query(&client, "error=access_denied&state=otherstate")
+                .await,
+            Err(UserTokenExchangeError::StateMismatch)
+        ));
+        assert!(matches!(
+            builder()
+                .complete_from_query(&client, "code=thecode&state=otherstate")
+                .await,
+            Err(UserTokenExchangeError::StateMismatch)
+        ));
+        assert!(matches!(
+            builder().complete_from_query(&client, "code=thecode").await,
+            Err(UserTokenExchangeError::StateMismatch)
+        ));
+        assert!(matches!(
+            builder().complete_from_query(&client, "state=state").await,
+            Err(UserTokenExchangeError::MissingCode)
+        ));
+        assert!(client.requests.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn scope_encoding() {
+        let mut builder = UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        )
+        .set_scopes(vec![Scope::ChatRead, Scope::ChatEdit]);
+        let url = builder.generate_url();
+        assert!(url
+            .query()
+            .unwrap()
+            .split('&')
+            .any(|p| p == "scope=chat%3Aread+chat%3Aedit"));
+
+        let mut builder = ImplicitUserTokenBuilder::new(
+            "clientid".into(),
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        )
+        .set_scopes(vec![Scope::ChatRead, Scope::ChatEdit]);
+        let (url, _) = builder.generate_url();
+        assert!(url
+            .query()
+            .unwrap()
+            .split('&')
+            .any(|p| p == "scope=chat%3Aread+chat%3Aedit"));
+
+        let request = crate::AppAccessToken::get_app_access_token_request(
+            crate::ClientIdRef::from_static("clientid"),
+            crate::ClientSecretRef::from_static("secret"),
+            vec![Scope::ChatRead, Scope::ChatEdit],
+        );
+        assert!(request
+            .uri()
+            .query()
+            .unwrap()
+            .split('&')
+            .any(|p| p == "scope=chat%3Aread+chat%3Aedit"));
+    }
+
+    #[tokio::test]
+    async fn force_fresh_connection() {
+        let builder = || {
+            UserTokenBuilder::with_csrf(
+                "clientid",
+                "secret",
+                url::Url::parse("http://localhost/twitch/register").unwrap(),
+                "state",
+            )
+        };
+        let requests = builder().get_user_token_requests("code", &AccessToken::from("token"));
+        assert!(requests
+            .exchange
+            .headers()
+            .get(http::header::CONNECTION)
+            .is_none());
+        assert!(requests
+            .validate
+            .headers()
+            .get(http::header::CONNECTION)
+            .is_none());
+
+        let requests = builder()
+            .force_fresh_connection(true)
+            .get_user_token_requests("code", &AccessToken::from("token"));
+        assert_eq!(
+            requests.exchange.headers()[http::header::CONNECTION],
+            "close"
+        );
+        assert_eq!(
+            requests.validate.headers()[http::header::CONNECTION],
+            "close"
+        );
+
+        #[cfg(feature = "client")]
+        {
+            let client = crate::client::MockClient::default()
+                .respond(
+                    200,
+                    r#"{"access_token":"accesstoken","expires_in":14124,"refresh_token":"refreshtoken","scope":["chat:read"],"token_type":"bearer"}"#,
+                )
+                .respond(
+                    200,
+                    r#"{"client_id":"clientid","login":"login","scopes":["chat:read"],"user_id":"1234","expires_in":14124}"#,
+                );
+            builder()
+                .force_fresh_connection(true)
+                .get_user_token(&client, "code")
+                .await
+                .unwrap();
+            let requests = client.requests.lock().unwrap();
+            assert_eq!(requests.len(), 2);
+            assert!(requests
+                .iter()
+                .all(|r| r.headers()[http::header::CONNECTION] == "close"));
+        }
+    }
+
+    #[test]
+    fn get_user_token_request_encoding() {
+        let builder = UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        );
+        let request = builder.get_user_token_request("code");
+        assert!(request.body().is_empty());
+        assert!(request.uri().query().unwrap().contains("code=code"));
+        assert!(request.headers().get(http::header::CONTENT_TYPE).is_none());
+
+        let builder = builder.body_encoding(crate::BodyEncoding::Form);
+        let request = builder.get_user_token_request("code");
+        assert!(request.uri().query().is_none());
+        assert_eq!(
+            request.headers()[http::header::CONTENT_TYPE],
+            "application/x-www-form-urlencoded"
+        );
+        let body: std::collections::HashMap<_, _> =
+            url::form_urlencoded::parse(request.body()).collect();
+        assert_eq!(body["code"], "code");
+        assert_eq!(body["grant_type"], "authorization_code");
+        assert_eq!(body["redirect_uri"], "http://localhost/twitch/register");
+
+        let builder = builder.body_encoding(crate::BodyEncoding::Json);
+        let request = builder.get_user_token_request("code");
+        assert!(request.uri().query().is_none());
+        assert_eq!(
+            request.headers()[http::header::CONTENT_TYPE],
+            "application/json"
+        );
+        let body: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+        assert_eq!(body["code"], "code");
+        assert_eq!(body["client_secret"], "secret");
+        assert_eq!(body["grant_type"], "authorization_code");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn on_request() {
+        use std::sync::{Arc, Mutex};
+
+        let client = crate::client::MockClient::default()
+            .respond(
+                200,
+                r#"{"access_token":"accesstoken","expires_in":14124,"refresh_token":"refreshtoken","scope":["chat:read"],"token_type":"bearer"}"#,
+            )
+            .respond(
+                200,
+                r#"{"client_id":"clientid","login":"twitchdev","scopes":["chat:read"],"user_id":"141981764","expires_in":14124}"#,
+            )
+            .respond(
+                200,
+                r#"{"client_id":"clientid","login":"twitchdev","scopes":["chat:read"],"user_id":"141981764","expires_in":14000}"#,
+            )
+            .respond(
+                200,
+                r#"{"access_token":"newtoken","expires_in":14124,"refresh_token":"newrefresh","scope":["chat:read"],"token_type":"bearer"}"#,
+            );
+        let seen = Arc::new(Mutex::new(vec![]));
+        let seen2 = seen.clone();
+        let builder = UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        )
+        .on_request(move |req| {
+            req.headers_mut()
+                .insert("X-Trace", http::HeaderValue::from_static("trace"));
+            seen2
+                .lock()
+                .unwrap()
+                .push((req.uri().clone(), req.headers().clone()))
+        });
+        let mut token = builder.get_user_token(&client, "code").await.unwrap();
+        token.validate(&client).await.unwrap();
+        token.refresh_token(&client).await.unwrap();
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 4);
+        assert_eq!(seen[2].0.path(), "/oauth2/validate");
+        assert_eq!(seen[3].0.path(), "/oauth2/token");
+        let params: std::collections::HashMap<_, _> =
+            url::form_urlencoded::parse(seen[0].0.query().unwrap().as_bytes()).collect();
+        assert_eq!(params["client_id"], "clientid");
+        assert_eq!(params["client_secret"], "[redacted]");
+        assert_eq!(params["code"], "[redacted]");
+        assert_eq!(seen[1].1[http::header::AUTHORIZATION], "[redacted]");
+        // the request sent is not redacted, but has the header from the hook
+        let requests = client.requests.lock().unwrap();
+        assert_eq!(
+            requests[1].headers()[http::header::AUTHORIZATION],
+            "OAuth accesstoken"
+        );
+        assert_eq!(
+            requests[2].headers()[http::header::AUTHORIZATION],
+            "OAuth accesstoken"
+        );
+        assert!(requests.iter().all(|r| r.headers()["X-Trace"] == "trace"));
+        assert!(requests[3]
+            .uri()
+            .query()
+            .unwrap()
+            .contains("refresh_token=refreshtoken"));
+    }
+
+    #[test]
+    fn correlation_id() {
+        let mut builder = UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        )
+        .set_csrf(Some("csrf".into()))
+        .with_correlation_id("session:42")
+        .unwrap();
+        let url = builder.generate_url();
+        let state = url
+            .query_pairs()
+            .find(|(k, _)| k == "state")
+            .unwrap()
+            .1
+            .into_owned();
+        assert_eq!(state, "csrf:session:42");
+        assert!(builder.csrf_is_valid(&state));
+        assert_eq!(
+            builder.parse_correlated_state(&state),
+            (true, Some("session:42"))
+        );
+        assert_eq!(
+            UserTokenBuilder::split_correlated_state(&state),
+            ("csrf", Some("session:42"))
+        );
+        assert_eq!(
+            UserTokenBuilder::split_correlated_state("csrf"),
+            ("csrf", None)
+        );
+        assert_eq!(
+            builder.parse_correlated_state("csrf:other"),
+            (false, Some("other"))
+        );
+        assert_eq!(builder.parse_correlated_state("csrf"), (false, None));
+        assert_eq!(
+            builder.parse_correlated_state("wrong:session:42"),
+            (false, Some("session:42"))
+        );
+
+        // replacing the correlation id keeps the csrf
+        let builder = builder.with_correlation_id("7").unwrap();
+        assert!(builder.csrf_is_valid("csrf:7"));
+
+        // a csrf with the separator can't be told apart from the correlation id
+        assert!(UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        )
+        .set_csrf(Some("csrf:1".into()))
+        .with_correlation_id("2")
+        .is_err());
+
+        let builder = UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        )
+        .set_csrf(None)
+        .with_correlation_id("1")
+        .unwrap();
+        let state = builder.csrf.clone().unwrap();
+        assert!(state.secret().ends_with(":1"));
+        assert_eq!(
+            builder.parse_correlated_state(state.secret()),
+            (true, Some("1"))
+        );
+    }
+
+    #[test]
+    fn scope_advisory() {
+        let token = UserToken::test_token(false, &[Scope::ChatRead, Scope::ChatEdit], None);
+        let builder = || {
+            UserTokenBuilder::new(
+                "clientid",
+                "secret",
+                url::Url::parse("http://localhost/twitch/register").unwrap(),
+            )
+        };
+        assert_eq!(
+            builder().set_scopes(vec![Scope::ChatRead]).scope_advisory(),
+            None
+        );
+
+        let incremental = builder().incremental_from(&token);
+        assert_eq!(incremental.scopes, [Scope::ChatRead, Scope::ChatEdit]);
+        assert_eq!(
+            incremental.scope_advisory(),
+            Some(ScopeAdvisory::NoNewScopes {
+                already_granted: vec![Scope::ChatRead, Scope::ChatEdit]
+            })
+        );
+
+        let subset = builder()
+            .incremental_from(&token)
+            .set_scopes(vec![Scope::ChatEdit]);
+        assert_eq!(
+            subset.scope_advisory(),
+            Some(ScopeAdvisory::NoNewScopes {
+                already_granted: vec![Scope::ChatEdit]
+            })
+        );
+
+        let new = builder()
+            .incremental_from(&token)
+            .add_scope(Scope::ModeratorManageBannedUsers);
+        assert_eq!(new.scope_advisory(), None);
+    }
+
+    #[test]
+    fn generate_urls_for_logins() {
+        let builder = UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        )
+        .set_scopes(vec![Scope::ChatRead]);
+        let accounts = builder
+            .generate_urls_for_logins(["login_a".into(), "login_b".into(), "login_c".into()])
+            .unwrap();
+        assert_eq!(accounts.len(), 3);
+
+        let mut states = std::collections::HashSet::new();
+        for (account, login) in accounts.iter().zip(["login_a", "login_b", "login_c"]) {
+            assert_eq!(account.login.as_str(), login);
+            let params: std::collections::HashMap<_, _> = account.url.query_pairs().collect();
+            assert_eq!(params["login_hint"], login);
+            assert_eq!(params["force_verify"], "true");
+            assert_eq!(params["client_id"], "clientid");
+            assert_eq!(params["scope"], "chat:read");
+            assert_eq!(params["redirect_uri"], "http://localhost/twitch/register");
+            assert_eq!(params["state"], account.csrf.secret());
+            assert!(states.insert(account.csrf.clone()));
+
+            let builder = UserTokenBuilder::with_csrf(
+                "clientid",
+                "secret",
+                url::Url::parse("http://localhost/twitch/register").unwrap(),
+                account.csrf.clone(),
+            );
+            assert!(builder.csrf_is_valid(&params["state"]));
+        }
+
+        let rejecting = UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        )
+        .scope_validator(|_| Err("no".to_owned()));
+        assert!(rejecting
+            .generate_urls_for_logins(["login_a".into()])
+            .is_err());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn token_exchanger() {
+        struct Broker;
+
+        impl crate::client::TokenExchanger for Broker {
+            fn request(&self, mut request: http::Request<Vec<u8>>) -> http::Request<Vec<u8>> {
+                if request.uri().path() == "/oauth2/token" {
+                    let query = request.uri().query().unwrap_or_default().to_owned();
+                    *request.uri_mut() = format!("https://broker.example.com/token?{query}")
+                        .parse()
+                        .unwrap();
+                }
+                request
+            }
+        }
+
+        let client = crate::client::MockClient::default()
+            .respond(
+                200,
+                r#"{"access_token":"accesstoken","expires_in":14124,"refresh_token":"refreshtoken","scope":["chat:read"],"token_type":"bearer"}"#,
+            )
+            .respond(
+                200,
+                r#"{"client_id":"clientid","login":"twitchdev","scopes":["chat:read"],"user_id":"141981764","expires_in":14124}"#,
+            )
+            .respond(
+                200,
+                r#"{"access_token":"newaccesstoken","expires_in":14124,"refresh_token":"newrefreshtoken","scope":["chat:read"],"token_type":"bearer"}"#,
+            );
+        let mut token = UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        )
+        .token_exchanger(Broker)
+        .get_user_token(&client, "code")
+        .await
+        .unwrap();
+        token.refresh_token(&client).await.unwrap();
+        assert_eq!(token.access_token.secret(), "newaccesstoken");
+
+        let requests = client.requests.lock().unwrap();
+        assert_eq!(requests[0].uri().host(), Some("broker.example.com"));
+        assert!(requests[0].uri().query().unwrap().contains("code=code"));
+        assert_eq!(requests[1].uri(), "https://id.twitch.tv/oauth2/validate");
+        assert_eq!(requests[2].uri().host(), Some("broker.example.com"));
+        assert!(requests[2]
+            .uri()
+            .query()
+            .unwrap()
+            .contains("grant_type=refresh_token"));
+    }
+
+    #[test]
+    fn to_public_json() {
+        let mut token = UserToken::test_token(
+            true,
+            &[Scope::ChatRead],
+            Some(std::time::Duration::from_secs(3600)),
+        );
+        let json = token.to_public_json();
+        let serialized = json.to_string();
+        for secret in ["accesstoken", "refreshtoken", "\"secret\""] {
+            assert!(!serialized.contains(secret), "{secret} leaked");
+        }
+        assert!(json["access_token"].is_null());
+        assert!(json["refresh_token"].is_null());
+        assert_eq!(json["client_id"], "clientid");
+        assert_eq!(json["login"], "login");
+        assert_eq!(json["user_id"], "1234");
+        assert_eq!(json["scopes"], serde_json::json!(["chat:read"]));
+        assert!(json["expires_in"].as_u64().unwrap() <= 3600);
+
+        token.never_expiring = true;
+        assert!(token.to_public_json()["expires_in"].is_null());
+    }
+
+    #[test]
+    fn can_read_email() {
+        assert!(
+            UserToken::test_token(false, &[Scope::ChatRead, Scope::UserReadEmail], None)
+                .can_read_email()
+        );
+        assert!(
+            !UserToken::test_token(false, &[Scope::ChatRead, Scope::UserEditBroadcast], None)
+                .can_read_email()
+        );
+        assert!(!UserToken::test_token(false, &[], None).can_read_email());
+    }
+
+    #[test]
+    fn never_expiring_override() {
+        let mut token = UserToken::test_token(
+            false,
+            &[],
+            Some(std::time::Duration::from_secs(60 * 60 * 24 * 365 * 100)),
+        );
+        assert!(!token.never_expires());
+        token.set_never_expiring(true);
+        assert!(token.never_expires());
+        assert_eq!(token.expiry(), crate::tokens::Expiry::Never);
+        token.set_never_expiring(false);
+        assert!(!token.never_expires());
+
+        token.set_never_expiring_threshold(std::time::Duration::from_secs(60 * 60 * 24 * 365));
+        assert!(token.never_expires());
+        assert_eq!(token.expires_in(), std::time::Duration::MAX);
+
+        let mut token =
+            UserToken::test_token(false, &[], Some(std::time::Duration::from_secs(3600)));
+        token.set_never_expiring_threshold(std::time::Duration::from_secs(60 * 60 * 24 * 365));
+        assert!(!token.never_expires());
+    }
+
+    #[test]
+    fn expiry_never() {
+        use crate::tokens::Expiry;
+
+        let token = UserToken::test_token(false, &[], None);
+        assert!(token.never_expires());
+        assert_eq!(token.expiry(), Expiry::Never);
+        assert_eq!(token.expires_in(), std::time::Duration::MAX);
+        assert!(!token.is_elapsed());
+        assert_eq!(
+            token.expiry().expires_at(std::time::SystemTime::now()),
+            None
+        );
+
+        let token = UserToken::test_token(false, &[], Some(std::time::Duration::from_secs(3600)));
+        let expiry = token.expiry();
+        assert!(matches!(expiry, Expiry::At(d) if d <= std::time::Duration::from_secs(3600)));
+        assert!(expiry.expires_at(std::time::SystemTime::now()).is_some());
+
+        // adding the old sentinel directly overflows, the expiry doesn't
+        assert!(std::time::SystemTime::now()
+            .checked_add(std::time::Duration::MAX)
+            .is_none());
+        assert_eq!(
+            Expiry::from(None::<std::time::Duration>).expires_at(std::time::SystemTime::now()),
+            None
+        );
+    }
+
+    #[test]
+    fn get_user_token_requests() {
+        let builder = UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        );
+        let requests = builder.get_user_token_requests("code", &AccessToken::from("token"));
+
+        assert_eq!(requests.exchange.method(), http::Method::POST);
+        assert_eq!(requests.exchange.uri().path(), crate::TOKEN_URL.path());
+        let params: std::collections::HashMap<_, _> =
+            url::form_urlencoded::parse(requests.exchange.uri().query().unwrap().as_bytes())
+                .collect();
+        assert_eq!(params["client_id"], "clientid");
+        assert_eq!(params["client_secret"], "secret");
+        assert_eq!(params["code"], "code");
+        assert_eq!(params["grant_type"], "authorization_code");
+        assert_eq!(params["redirect_uri"], "http://localhost/twitch/register");
+
+        assert_eq!(requests.validate.method(), http::Method::GET);
+        assert_eq!(requests.validate.uri().path(), crate::VALIDATE_URL.path());
+        assert_eq!(
+            requests.validate.headers()[http::header::AUTHORIZATION],
+            "OAuth token"
+        );
+    }
+
+    #[test]
+    fn check_redirect() {
+        let builder = UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("https://localhost:8080/twitch/register?app=1").unwrap(),
+        );
+        let check = |builder: &UserTokenBuilder, url: &str| {
+            builder.check_redirect(&url::Url::parse(url).unwrap())
+        };
+        let same =
+            "https://localhost:8080/twitch/register?app=1&code=code&scope=chat%3Aread&state=state";
+        let other_query = "https://localhost:8080/twitch/register?app=2&code=code&state=state";
+        let fragment = "https://localhost:8080/twitch/register?app=1&code=code#frag";
+        let other_path = "https://localhost:8080/twitch/callback?app=1&code=code";
+        let other_port = "https://localhost:8081/twitch/register?app=1&code=code";
+        let other_scheme = "http://localhost:8080/twitch/register?app=1&code=code";
+
+        assert!(check(&builder, same).is_ok());
+        assert!(matches!(
+            check(&builder, other_query),
+            Err(RedirectUrlError::Mismatch { .. })
+        ));
+        assert!(check(&builder, fragment).is_err());
+        for url in [other_path, other_port, other_scheme] {
+            assert!(check(&builder, url).is_err(), "{url}");
+        }
+
+        let builder = builder.redirect_match(RedirectMatch::IgnoreQuery);
+        assert!(check(&builder, same).is_ok());
+        assert!(check(&builder, other_query).is_ok());
+        assert!(check(&builder, fragment).is_ok());
+        for url in [other_path, other_port, other_scheme] {
+            assert!(check(&builder, url).is_err(), "{url}");
+        }
+    }
+
+    #[test]
+    fn validate_redirect() {
+        let allowlist: RedirectAllowlist = ["http://localhost", "http://localhost/twitch/register"]
+            .into_iter()
+            .collect();
+
+        let builder = UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        );
+        builder.validate_redirect(&allowlist).unwrap();
+
+        let builder = UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost/twitch/callback").unwrap(),
+        );
+        assert!(matches!(
+            builder.validate_redirect(&allowlist),
+            Err(RedirectUrlError::NotAllowed { url }) if url == "http://localhost/twitch/callback"
+        ));
+
+        // `http://localhost` is sent as `http://localhost/`, which twitch doesn't consider the same
+        let builder = UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost").unwrap(),
+        );
+        assert!(matches!(
+            builder.validate_redirect(&allowlist),
+            Err(RedirectUrlError::TrailingSlash { given, sent })
+                if given == "http://localhost" && sent == "http://localhost/"
+        ));
+        builder
+            .validate_redirect(&allowlist.with("http://localhost/"))
+            .unwrap();
+    }
+
+    #[test]
+    fn prompt() {
+        let builder = || {
+            UserTokenBuilder::new(
+                "clientid",
+                "secret",
+                url::Url::parse("http://localhost/twitch/register").unwrap(),
+            )
+        };
+        let prompt = |mut builder: UserTokenBuilder| {
+            builder
+                .generate_url()
+                .query_pairs()
+                .find(|(k, _)| k == "prompt")
+                .map(|(_, v)| v.into_owned())
+        };
+        assert_eq!(prompt(builder()), None);
+        assert_eq!(prompt(builder().prompt(Prompt::None)).unwrap(), "none");
+        assert_eq!(prompt(builder().prompt(Prompt::Login)).unwrap(), "login");
+        assert_eq!(
+            prompt(builder().prompt(Prompt::Consent)).unwrap(),
+            "consent"
+        );
+        assert_eq!(prompt(builder().prompt(Prompt::Login).prompt(None)), None);
+
+        let mut builder = builder().prompt(Prompt::None).force_verify(true);
+        let url = builder.generate_url();
+        let params: std::collections::HashMap<_, _> = url.query_pairs().collect();
+        assert_eq!(params["prompt"], "none");
+        assert_eq!(params["force_verify"], "true");
+    }
+
+    #[test]
+    fn scope_validator() {
+        let builder = |scopes| {
+            UserTokenBuilder::new(
+                "clientid",
+                "secret",
+                url::Url::parse("http://localhost/twitch/register").unwrap(),
+            )
+            .set_scopes(scopes)
+            .scope_validator(|scopes| {
+                if scopes.contains(&Scope::ChatEdit)
+                    && scopes.iter().any(|s| s.as_str().starts_with("moderator:"))
+                {
+                    Err("chat:edit can't be requested with moderation scopes".to_owned())
+                } else {
+                    Ok(())
+                }
+            })
+        };
+
+        let error = builder(vec![Scope::ChatEdit, Scope::ModeratorManageBannedUsers])
+            .try_generate_url()
+            .unwrap_err();
+        assert_eq!(
+            error.0,
+            "chat:edit can't be requested with moderation scopes"
+        );
+        let mut rejected = builder(vec![Scope::ChatEdit, Scope::ModeratorManageBannedUsers]);
+        assert!(rejected.url_parts().is_err());
+        assert!(rejected
+            .generate_url()
+            .as_str()
+            .contains("scope=chat%3Aedit+moderator%3Amanage%3Abanned_users"));
+
+        let url = builder(vec![Scope::ChatRead, Scope::ChatEdit])
+            .try_generate_url()
+            .unwrap();
+        assert!(url.as_str().contains("scope=chat%3Aread+chat%3Aedit"));
+    }
+
+    #[test]
+    fn url_parts() {
+        let mut builder = UserTokenBuilder::with_csrf(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+            "state",
+        )
+        .set_scopes(vec![Scope::ChatRead, Scope::ChatEdit])
+        .force_verify(true);
+        let mut parts = builder.url_parts().unwrap();
+        assert_eq!(parts.to_url(), builder.generate_url());
+        assert_eq!(parts.base.as_str(), "https://id.twitch.tv/oauth2/authorize");
+        assert_eq!(parts.get("response_type"), Some("code"));
+        assert_eq!(parts.get("client_id"), Some("clientid"));
+        assert_eq!(
+            parts.get("redirect_uri"),
+            Some("http://localhost/twitch/register")
+        );
+        assert_eq!(parts.get("scope"), Some("chat:read chat:edit"));
+        assert_eq!(parts.get("state"), Some("state"));
+        assert_eq!(parts.get("force_verify"), Some("true"));
+
+        assert_eq!(parts.remove("force_verify").as_deref(), Some("true"));
+        parts.set("scope", "chat:read");
+        let mut builder = builder
+            .force_verify(false)
+            .set_scopes(vec![Scope::ChatRead]);
+        assert_eq!(parts.to_url(), builder.generate_url());
+    }
+
+    #[test]
+    fn scope_presets() {
+        let builder = UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        )
+        .add_scope(Scope::ChatRead)
+        .with_moderation_scopes()
+        .with_chat_bot_scopes()
+        .with_subscription_scopes();
+        let moderation = crate::scopes::presets::MODERATION;
+        assert!(moderation.iter().all(|s| {
+            s.as_str().starts_with("moderator:read:") || s.as_str().starts_with("moderator:manage:")
+        }));
+        assert!(moderation.contains(&Scope::ModeratorManageBannedUsers));
+        assert!(moderation.contains(&Scope::ModeratorReadChatters));
+        assert_eq!(
+            crate::scopes::presets::CHAT_BOT,
+            &[
+                Scope::ChatRead,
+                Scope::ChatEdit,
+                Scope::WhispersRead,
+                Scope::WhispersEdit
+            ]
+        );
+        assert_eq!(
+            crate::scopes::presets::SUBSCRIPTIONS,
+            &[
+                Scope::ChannelReadSubscriptions,
+                Scope::UserReadSubscriptions
+            ]
+        );
+        // ChatRead is only requested once
+        assert_eq!(
+            builder.scopes.len(),
+            1 + moderation.len() + 3 + crate::scopes::presets::SUBSCRIPTIONS.len()
+        );
+        assert_eq!(builder.scopes[0], Scope::ChatRead);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn revalidate_login_changed() {
+        let validated = |login: &str| {
+            format!(
+                r#"{{"client_id":"clientid","login":"{login}","scopes":[],"user_id":"1234","expires_in":5520838}}"#
+            )
+        };
+        let client = crate::client::MockClient::default()
+            .respond(200, &validated("login"))
+            .respond(200, &validated("newlogin"));
+        let mut token = UserToken::test_token(false, &[], None);
+        assert_eq!(token.revalidate(&client).await.unwrap(), None);
+        assert_eq!(
+            token.revalidate(&client).await.unwrap(),
+            Some(LoginChanged {
+                old: "login".into(),
+                new: "newlogin".into()
+            })
+        );
+        assert_eq!(token.login.as_str(), "newlogin");
+        assert_eq!(token.user_id.as_str(), "1234");
+    }
+
+    #[test]
+    fn update_login_other_user() {
+        let validated: ValidatedToken = serde_json::from_str(
+            r#"{"client_id":"clientid","login":"otherlogin","scopes":[],"user_id":"5678","expires_in":5520838}"#,
+        )
+        .unwrap();
+        let mut token = UserToken::test_token(false, &[], None);
+        token.last_validated = std::time::UNIX_EPOCH;
+        assert_eq!(token.update_login(&validated), None);
+        assert_eq!(token.login.as_str(), "login");
+        assert_eq!(token.last_validated, std::time::UNIX_EPOCH);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn validate_without_mutation() {
+        let client = crate::client::MockClient::default().respond(
+            200,
+            r#"{"client_id":"clientid","login":"newlogin","scopes":["chat:read","chat:edit"],"user_id":"1234","expires_in":1200}"#,
+        );
+        let token = UserToken::test_token(
+            false,
+            &[Scope::ChatRead],
+            Some(std::time::Duration::from_secs(3600)),
+        );
+        let due = token.next_validation_due();
+        let validated = token.validate(&client).await.unwrap();
+        assert_eq!(validated.login.unwrap().as_str(), "newlogin");
+        assert_eq!(
+            validated.expires_in,
+            Some(std::time::Duration::from_secs(1200))
+        );
+        assert_eq!(
+            validated.scopes.unwrap(),
+            [Scope::ChatRead, Scope::ChatEdit]
+        );
+
+        assert_eq!(token.login.as_str(), "login");
+        assert_eq!(token.scopes(), [Scope::ChatRead]);
+        assert!(token.expires_in() > std::time::Duration::from_secs(1200));
+        assert_eq!(token.next_validation_due(), due);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn next_validation_due() {
+        let client = crate::client::MockClient::default().respond(
+            200,
+            r#"{"client_id":"clientid","login":"login","scopes":[],"user_id":"1234","expires_in":5520838}"#,
+        );
+        let mut token = UserToken::test_token(false, &[], None);
+        let hour = std::time::Duration::from_secs(60 * 60);
+        assert_eq!(token.next_validation_due(), token.created_at + hour);
+
+        token.last_validated -= hour * 2;
+        let due = token.next_validation_due();
+        assert!(due < std::time::SystemTime::now());
+        token.revalidate(&client).await.unwrap();
+        assert!(token.next_validation_due() > due + hour);
+        assert!(token.next_validation_due() > std::time::SystemTime::now());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn implicit_refresh() {
+        let client = crate::client::MockClient::default().respond(
+            200,
+            r#"{"client_id":"clientid","login":"login","scopes":[],"user_id":"1234","expires_in":5520838}"#,
+        );
+        let mut token = UserToken::from_existing(&client, "accesstoken".into(), None, None)
+            .await
+            .unwrap();
+        assert!(!token.can_refresh());
+        assert!(matches!(
+            token.refresh_token(&client).await,
+            Err(RefreshTokenError::Implicit)
+        ));
+        // no refresh request was made
+        assert_eq!(client.requests.lock().unwrap().len(), 1);
+
+        let token = UserToken::test_token(true, &[], None);
+        assert!(token.can_refresh());
+        let mut token = UserToken::test_token(true, &[], None);
+        token.refresh_token = None;
+        assert!(!token.can_refresh());
+        assert!(matches!(
+            token.refresh_token(&client).await,
+            Err(RefreshTokenError::NoRefreshToken)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "tokio")]
+    fn expires_at_tokio() {
+        let before = tokio::time::Instant::now();
+        let expires_at =
+            UserToken::test_token(false, &[], Some(std::time::Duration::from_secs(3600)))
+                .expires_at_tokio()
+                .unwrap();
+        let after = tokio::time::Instant::now();
+        assert!(expires_at >= before + std::time::Duration::from_secs(3600));
+        assert!(expires_at <= after + std::time::Duration::from_secs(3600));
+        assert_eq!(
+            UserToken::test_token(false, &[], None).expires_at_tokio(),
+            None
+        );
+    }
+
+    #[test]
+    fn is_expired_at() {
+        let hour = std::time::Duration::from_secs(3600);
+        let second = std::time::Duration::from_secs(1);
+
+        let t = UserToken::test_token(false, &[], Some(hour));
+        let expires_at = t.expires_at().unwrap();
+        assert_eq!(expires_at, t.created_at + hour);
+        assert!(!t.is_expired_at(t.created_at));
+        assert!(!t.is_expired_at(expires_at - second));
+        assert!(t.is_expired_at(expires_at));
+        assert!(t.is_expired_at(expires_at + second));
+        assert!(!t.is_expired_at(std::time::UNIX_EPOCH));
+
+        let t = UserToken::test_token(false, &[], Some(std::time::Duration::ZERO));
+        assert!(t.is_expired_at(t.created_at));
+
+        let t = UserToken::test_token(false, &[], None);
+        assert_eq!(t.expires_at(), None);
+        assert!(!t.is_expired_at(t.created_at + hour * 24 * 365 * 100));
+    }
+
+    #[test]
+    fn scope_escalation() {
+        let token = UserToken::test_token(
+            false,
+            &[
+                Scope::ChatRead,
+                Scope::ChatEdit,
+                Scope::ModeratorManageBannedUsers,
+            ],
+            None,
+        );
+        assert!(token
+            .scope_escalation(&[
+                Scope::ChatRead,
+                Scope::ChatEdit,
+                Scope::ModeratorManageBannedUsers
+            ])
+            .is_empty());
+        // fewer scopes than the baseline is not an escalation
+        assert!(token
+            .scope_escalation(&[
+                Scope::ChatRead,
+                Scope::ChatEdit,
+                Scope::ModeratorManageBannedUsers,
+                Scope::UserReadEmail
+            ])
+            .is_empty());
+        assert_eq!(
+            token.scope_escalation(&[Scope::ChatRead]),
+            vec![Scope::ChatEdit, Scope::ModeratorManageBannedUsers]
+        );
+        assert_eq!(token.scope_escalation(&[]).len(), 3);
+    }
+
+    #[test]
+    fn clone_shares_scopes() {
+        let token = UserToken::test_token(false, &[Scope::ChatRead, Scope::ChatEdit], None);
+        let clone = token.clone();
+        assert_eq!(clone.scopes(), &[Scope::ChatRead, Scope::ChatEdit]);
+        // the scopes are not copied
+        assert!(std::sync::Arc::ptr_eq(&token.scopes, &clone.scopes));
+        assert_eq!(std::sync::Arc::strong_count(&token.scopes), 2);
+    }
+
+    #[test]
+    fn age() {
+        let token = UserToken::test_token(false, &[], None);
+        let first = token.age();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let second = token.age();
+        assert!(second >= first + std::time::Duration::from_millis(10));
+    }
+
+    #[test]
+    fn with_csrf() {
+        let mut builder = UserTokenBuilder::with_csrf(
+            "clientid",
+            "secret",
+            url::Url::parse("https://localhost/twitch/register").unwrap(),
+            "knownstate",
+        );
+        assert!(builder.csrf_is_valid("knownstate"));
+        assert!(!builder.csrf_is_valid("otherstate"));
+        let url = builder.generate_url();
+        assert!(url
+            .query_pairs()
+            .any(|(k, v)| k == "state" && v == "knownstate"));
+    }
+
+    #[test]
+    fn redirect_url_trailing_slash() {
+        let builder =
+            UserTokenBuilder::try_new("clientid", "secret", "http://localhost/twitch/register")
+                .unwrap();
+        assert_eq!(
+            builder.redirect_url_as_registered(),
+            "http://localhost/twitch/register"
+        );
+        let builder = UserTokenBuilder::try_new("clientid", "secret", "http://localhost/").unwrap();
+        assert_eq!(builder.redirect_url_as_registered(), "http://localhost/");
+        assert!(UserTokenBuilder::try_new("clientid", "secret", "http://localhost/?a=b").is_ok());
+
+        match UserTokenBuilder::try_new("clientid", "secret", "https://example.com") {
+            Err(RedirectUrlError::TrailingSlash { given, sent }) => {
+                assert_eq!(given, "https://example.com");
+                assert_eq!(sent, "https://example.com/");
+            }
+            _ => panic!("expected trailing slash error"),
+        }
+        assert!(matches!(
+            ImplicitUserTokenBuilder::try_new("clientid".into(), "http://localhost:8080"),
+            Err(RedirectUrlError::TrailingSlash { .. })
+        ));
+        assert!(matches!(
+            UserTokenBuilder::try_new("clientid", "secret", "not a url"),
+            Err(RedirectUrlError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn scope_order() {
+        let scopes = vec![
+            Scope::UserReadEmail,
+            Scope::ChatRead,
+            Scope::ChannelReadSubscriptions,
+            Scope::ChatEdit,
+            Scope::ChannelManageRedemptions,
+        ];
+        let builder = UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        )
+        .set_scopes(scopes.clone());
+        assert_eq!(
+            builder.url_parts().unwrap().get("scope"),
+            Some("user:read:email chat:read channel:read:subscriptions chat:edit channel:manage:redemptions")
+        );
+        let builder = builder.scope_order(ScopeOrder::Category);
+        assert_eq!(
+            builder.url_parts().unwrap().get("scope"),
+            Some("channel:manage:redemptions channel:read:subscriptions chat:edit chat:read user:read:email")
+        );
+        assert_eq!(builder.scopes, scopes);
+
+        let mut builder = ImplicitUserTokenBuilder::new(
+            "clientid".into(),
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        )
+        .set_scopes(vec![Scope::ChatRead, Scope::ChannelReadSubscriptions])
+        .scope_order(ScopeOrder::Category);
+        let (url, _) = builder.generate_url();
+        assert_eq!(
+            url.query_pairs().find(|(k, _)| k == "scope").unwrap().1,
+            "channel:read:subscriptions chat:read"
+        );
+    }
+
+    #[test]
+    fn callback_query() {
+        let query: TwitchCallbackQuery =
+            serde_urlencoded::from_str("code=thecode&scope=chat%3Aread+chat%3Aedit&state=state")
+                .unwrap();
+        assert_eq!(query.code.as_deref(), Some("thecode"));
+        assert_eq!(query.state.as_deref(), Some("state"));
+        assert_eq!(query.scopes(), vec![Scope::ChatRead, Scope::ChatEdit]);
+        assert_eq!(query.error, None);
+        assert_eq!(
+            query,
+            TwitchCallbackQuery::parse("?code=thecode&scope=chat%3Aread+chat%3Aedit&state=state")
+        );
+
+        let query: TwitchCallbackQuery = serde_urlencoded::from_str(
+            "error=access_denied&error_description=The+user+denied+you+access&state=state",
+        )
+        .unwrap();
+        assert_eq!(query.code, None);
+        assert_eq!(query.error.as_deref(), Some("access_denied"));
+        assert_eq!(
+            query.error_description.as_deref(),
+            Some("The user denied you access")
+        );
+        assert!(query.scopes().is_empty());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn complete() {
+        let client = crate::client::MockClient::default()
+            .respond(
+                200,
+                r#"{"access_token":"accesstoken","expires_in":14124,"refresh_token":"refreshtoken","scope":["chat:read"],"token_type":"bearer"}"#,
+            )
+            .respond(
+                200,
+                r#"{"client_id":"clientid","login":"login","scopes":["chat:read"],"user_id":"1234","expires_in":14124}"#,
+            );
+        let builder = || {
+            UserTokenBuilder::new(
+                "clientid",
+                "secret",
+                url::Url::parse("http://localhost/twitch/register").unwrap(),
+            )
+            .set_csrf(Some("state".into()))
+        };
+        let query: TwitchCallbackQuery =
+            serde_urlencoded::from_str("code=thecode&scope=chat%3Aread&state=state").unwrap();
+        let token = builder().complete(&client, query).await.unwrap();
+        assert_eq!(token.access_token.secret(), "accesstoken");
+
+        let query: TwitchCallbackQuery =
+            serde_urlencoded::from_str("error=access_denied&state=state").unwrap();
+        assert!(matches!(
+            builder().complete(&client, query).await,
+            Err(UserTokenExchangeError::AccessDenied { description: None })
+        ));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn strict_token_type() {
+        const MAC: &str = r#"{"access_token":"accesstoken","expires_in":14124,"refresh_token":"refreshtoken","token_type":"mac"}"#;
+        const VALIDATED: &str = r#"{"client_id":"clientid","login":"login","scopes":[],"user_id":"1234","expires_in":14124}"#;
+        let client = crate::client::MockClient::default()
+            .respond(200, MAC)
+            .respond(200, VALIDATED)
+            .respond(200, MAC)
+            .respond(
+                200,
+                r#"{"access_token":"accesstoken","expires_in":14124,"refresh_token":"refreshtoken","token_type":"bearer"}"#,
+            )
+            .respond(200, VALIDATED)
+            .respond(200, MAC);
+        let builder = |strict| {
+            UserTokenBuilder::new(
+                "clientid",
+                "secret",
+                url::Url::parse("http://localhost/twitch/register").unwrap(),
+            )
+            .strict_token_type(strict)
+        };
+
+        // lenient by default
+        builder(false)
+            .get_user_token(&client, "code")
+            .await
+            .unwrap();
+        assert!(matches!(
+            builder(true).get_user_token(&client, "code").await,
+            Err(UserTokenExchangeError::RequestParseError(
+                crate::RequestParseError::UnexpectedTokenType { found: Some(ref found) }
+            )) if found == "mac"
+        ));
+
+        let mut token = builder(true).get_user_token(&client, "code").await.unwrap();
+        assert!(matches!(
+            token.refresh_token(&client).await,
+            Err(RefreshTokenError::RequestParseError(
+                crate::RequestParseError::UnexpectedTokenType { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn extra_token_param() {
+        let builder = UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        )
+        .body_encoding(crate::BodyEncoding::Form)
+        .extra_token_param("integration", "some product")
+        .extra_token_param("code", "othercode")
+        .extra_token_param("grant_type", "client_credentials");
+        let request = builder.get_user_token_request("thecode");
+        let params: std::collections::HashMap<_, _> = url::form_urlencoded::parse(request.body())
+            .into_owned()
+            .collect();
+        assert_eq!(params["integration"], "some product");
+        assert_eq!(params["code"], "thecode");
+        assert_eq!(params["grant_type"], "authorization_code");
+        assert_eq!(params["client_id"], "clientid");
+        assert_eq!(params.len(), 6);
+    }
+
+    #[test]
+    fn redirect_url_fragment() {
+        assert!(
+            UserTokenBuilder::try_new("clientid", "secret", "http://localhost/callback").is_ok()
+        );
+        assert!(matches!(
+            UserTokenBuilder::try_new("clientid", "secret", "http://localhost/callback#token"),
+            Err(RedirectUrlError::Fragment { url }) if url == "http://localhost/callback#token"
+        ));
+        assert!(matches!(
+            UserTokenBuilder::try_new("clientid", "secret", "http://localhost/callback#"),
+            Err(RedirectUrlError::Fragment { .. })
+        ));
+        assert!(matches!(
+            ImplicitUserTokenBuilder::try_new("clientid".into(), "http://localhost#"),
+            Err(RedirectUrlError::Fragment { .. })
+        ));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn implicit_error_state() {
+        fn builder() -> ImplicitUserTokenBuilder {
+            let mut builder = ImplicitUserTokenBuilder::new(
+                "clientid".into(),
+                url::Url::parse("http://localhost/twitch/register").unwrap(),
+            );
+            builder.csrf = Some(crate::CsrfToken::from("random"));
+            builder
+        }
+        let client = crate::client::MockClient::default();
+
+        let error = builder()
+            .get_user_token(
+                &client,
+                Some("random"),
+                None,
+                Some("invalid_scope"),
+                Some("Invalid scope"),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ImplicitUserTokenExchangeError::TwitchError { error: Some(e), .. } if e == "invalid_scope"
+        ));
+
+        let error = builder()
+            .get_user_token(
+                &client,
+                None,
+                None,
+                Some("invalid_scope"),
+                Some("Invalid scope"),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ImplicitUserTokenExchangeError::TwitchError { error: Some(e), .. } if e == "invalid_scope"
+        ));
+
+        let error = builder()
+            .get_user_token_from_url(
+                &client,
+                &url::Url::parse("http://localhost/twitch/register?error=access_denied&error_description=The+user+denied+you+access&state=random").unwrap(),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ImplicitUserTokenExchangeError::AccessDenied { description: Some(d) } if d == "The user denied you access"
+        ));
+
+        let error = builder()
+            .get_user_token(
+                &client,
+                Some("random"),
+                None,
+                Some("consent_required"),
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ImplicitUserTokenExchangeError::InteractionRequired { error: e, description: None } if e == "consent_required"
+        ));
+
+        let error = builder()
+            .get_user_token(
+                &client,
+                Some("wrong"),
+                None,
+                Some("invalid_scope"),
+                Some("Invalid scope"),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ImplicitUserTokenExchangeError::StateMismatch
+        ));
+
+        let error = builder()
+            .get_user_token(&client, None, Some("accesstoken"), None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ImplicitUserTokenExchangeError::StateMismatch
+        ));
+        assert!(client.requests.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn environment() {
+        let mut token = UserToken::test_token(false, &[], None);
+        assert_eq!(token.environment(), &crate::Environment::Production);
+        token.set_environment(crate::Environment::Mock);
+        assert_eq!(token.environment(), &crate::Environment::Mock);
+        assert_eq!(Box::new(token).environment(), &crate::Environment::Mock);
+    }
+
+    #[test]
+    fn parse_fragment() {
+        let url = url::Url::parse("http://localhost/twitch/register#access_token=0123456789abcdefghijABCDEFGHIJ&scope=channel%3Amanage%3Aredemptions%20user%3Aread%3Aemail&state=c3ab8aa609ea11e793ae92361f002671&token_type=bearer").unwrap();
+        let params = ImplicitUserTokenBuilder::parse_fragment(&url);
+        assert_eq!(
+            params.access_token.as_ref().map(|t| t.secret()),
+            Some("0123456789abcdefghijABCDEFGHIJ")
+        );
+        assert_eq!(
+            params.scopes,
+            Some(vec![Scope::ChannelManageRedemptions, Scope::UserReadEmail])
+        );
+        assert_eq!(
+            params.state.as_deref(),
+            Some("c3ab8aa609ea11e793ae92361f002671")
+        );
+        assert_eq!(params.token_type.as_deref(), Some("bearer"));
+        assert!(params.error.is_none());
+
+        let url = url::Url::parse("http://localhost/twitch/register?error=access_denied&error_description=The+user+denied+you+access&state=c3ab8aa609ea11e793ae92361f002671").unwrap();
+        let params = ImplicitUserTokenBuilder::parse_fragment(&url);
+        assert!(params.access_token.is_none());
+        assert_eq!(params.error.as_deref(), Some("access_denied"));
+        assert_eq!(
+            params.error_description.as_deref(),
+            Some("The user denied you access")
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn extra_granted_scopes() {
+        let client = crate::client::MockClient::default()
+            .respond(
+                200,
+                r#"{"access_token":"accesstoken","expires_in":14124,"refresh_token":"refreshtoken","scope":["chat:read","chat:edit","user:read:email"],"token_type":"bearer"}"#,
+            )
+            .respond(
+                200,
+                r#"{"client_id":"clientid","login":"login","scopes":["chat:read","chat:edit","user:read:email"],"user_id":"1234","expires_in":14124}"#,
+            );
+        let token = UserTokenBuilder::new(
+            "clientid",
+            "secret",
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        )
+        .set_scopes(vec![Scope::ChatRead])
+        .get_user_token(&client, "code")
+        .await
+        .unwrap();
+        assert_eq!(
+            token.extra_granted_scopes(),
+            [Scope::ChatEdit, Scope::UserReadEmail]
+        );
+
+        let token = UserToken::test_token(false, &[Scope::ChatRead], None);
+        assert!(token.extra_granted_scopes().is_empty());
+    }
+
+    #[test]
+    fn implicit_response_types() {
+        let builder = || {
+            ImplicitUserTokenBuilder::new(
+                "clientid".into(),
+                url::Url::parse("http://localhost/twitch/register").unwrap(),
+            )
+        };
+        let response_type = |mut builder: ImplicitUserTokenBuilder| {
+            builder
+                .generate_url()
+                .0
+                .query_pairs()
+                .find(|(k, _)| k == "response_type")
+                .map(|(_, v)| v.into_owned())
+                .unwrap()
+        };
+        assert_eq!(response_type(builder()), "token");
+        assert_eq!(
+            response_type(builder().with_response_types(&["token", "id_token"])),
+            "token id_token"
+        );
+
+        let params = FragmentParams::parse(
+            "access_token=accesstoken&id_token=idtoken&scope=openid&state=state&token_type=bearer",
+        );
+        assert_eq!(params.id_token.as_deref(), Some("idtoken"));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn implicit_missing_id_token() {
+        let mut builder = ImplicitUserTokenBuilder::new(
+            "clientid".into(),
+            url::Url::parse("http://localhost/twitch/register").unwrap(),
+        )
+        .with_response_types(&["token", "id_token"]);
+        builder.csrf = Some(crate::CsrfToken::from("state"));
+        let client = crate::client::MockClient::default();
+        let error = builder
+            .get_user_token_from_fragment(
+                &client,
+                "access_token=accesstoken&scope=openid&state=state&token_type=bearer",
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ImplicitUserTokenExchangeError::MissingIdToken
+        ));
+        assert!(client.requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn get_user_token_from_fragment() {
+        let builder = || {
+            let mut builder = ImplicitUserTokenBuilder::new(
+                "clientid".into(),
+                url::Url::parse("http://localhost/twitch/register").unwrap(),
+            );
+            builder.csrf = Some(crate::CsrfToken::from("c3ab8aa609ea11e793ae92361f002671"));
+            builder
+        };
+
+        let client = crate::client::MockClient::default().respond(
+            200,
+            r#"{"client_id":"clientid","login":"login","scopes":["channel:manage:redemptions","user:read:email"],"user_id":"1234","expires_in":5520838}"#,
+        );
+        let token = builder()
+            .get_user_token_from_fragment(&client, "access_token=0123456789abcdefghijABCDEFGHIJ&scope=channel%3Amanage%3Aredemptions%20user%3Aread%3Aemail&state=c3ab8aa609ea11e793ae92361f002671&token_type=bearer")
+            .await
+            .unwrap();
+        assert_eq!(
+            token.access_token.secret(),
+            "0123456789abcdefghijABCDEFGHIJ"
+        );
+        assert_eq!(token.login.as_str(), "login");
+        assert_eq!(
+            token.scopes(),
+            [Scope::ChannelManageRedemptions, Scope::UserReadEmail]
+        );
+
+        let client = crate::client::MockClient::default();
+        let error = builder()
+            .get_user_token_from_fragment(
+                &client,
+                "#access_token=0123456789abcdefghijABCDEFGHIJ&scope=&state=wrong&token_type=bearer",
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ImplicitUserTokenExchangeError::StateMismatch
+        ));
+        assert!(client.requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "client")]
+    async fn get_user_token_from_json() {
+        let builder = || {
+            let mut builder = ImplicitUserTokenBuilder::new(
+                "clientid".into(),
+                url::Url::parse("http://localhost/twitch/register").unwrap(),
+            );
+            builder.csrf = Some(crate::CsrfToken::from("c3ab8aa609ea11e793ae92361f002671"));
+            builder
+        };
+
+        let params = FragmentParams::from_json(
+            r#"{"access_token":"0123456789abcdefghijABCDEFGHIJ","scope":"channel:manage:redemptions user:read:email","state":"c3ab8aa609ea11e793ae92361f002671","token_type":"bearer"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            params.scopes.as_deref(),
+            Some(&[Scope::ChannelManageRedemptions, Scope::UserReadEmail][..])
+        );
+        assert_eq!(params.token_type.as_deref(), Some("bearer"));
+        let client = crate::client::MockClient::default().respond(
+            200,
+            r#"{"client_id":"clientid","login":"login","scopes":["channel:manage:redemptions","user:read:email"],"user_id":"1234","expires_in":5520838}"#,
+        );
+        let token = builder()
+            .get_user_token_from_params(&client, params)
+            .await
+            .unwrap();
+        assert_eq!(
+            token.access_token.secret(),
+            "0123456789abcdefghijABCDEFGHIJ"
+        );
+        assert_eq!(token.login.as_str(), "login");
+
+        let params = FragmentParams::from_json(
+            r#"{"access_token":"0123456789abcdefghijABCDEFGHIJ","scope":["chat:read"],"state":"wrong","token_type":"bearer"}"#,
+        )
+        .unwrap();
+        assert_eq!(params.scopes.as_deref(), Some(&[Scope::ChatRead][..]));
+        let client = crate::client::MockClient::default();
+        let error = builder()
+            .get_user_token_from_params(&client, params)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ImplicitUserTokenExchangeError::StateMismatch
+        ));
+        assert!(client.requests.lock().unwrap().is_empty());
+
+        assert!(FragmentParams::from_json(r#"{"scope":1}"#).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    #[cfg(feature = "surf")]
+    async fn get_token() {
+        let mut t = UserTokenBuilder::new(
+            ClientId::new(
+                std::env::var("TWITCH_CLIENT_ID").expect("no env:TWITCH_CLIENT_ID provided"),
+            ),
+            ClientSecret::new(
+                std::env::var("TWITCH_CLIENT_SECRET")
+                    .expect("no env:TWITCH_CLIENT_SECRET provided"),
+            ),
+            url::Url::parse(r#"https://localhost"#).unwrap(),
+        )
+        .force_verify(true);
+        t.csrf = Some(crate::CsrfToken::from("random"));
+
+        if !t.csrf_is_valid("random") {
+            panic!("csrf token is not valid");
+        }
 
-        if !t.csrf_is_valid("random") {
-            panic!("csrf token is not valid");
-        }
-        
         let token = t
             .get_user_token(&surf::Client::new(), "authcode")
             .await
@@ -9,9 +9,201 @@ use base64::Engine;
 #[aliri_braid::braid(serde)]
 pub struct ClientId;
 
-/// A Client Secret
-#[aliri_braid::braid(display = "owned", debug = "owned", serde)]
-pub struct ClientSecret;
+/// Owned half of a secret braid, see [`aliri_braid::braid`].
+///
+/// The owned type is written out instead of generated, so that it can clear its secret when dropped with the `zeroize` feature,
+/// which the generated code doesn't allow as it moves the secret out of `self`.
+macro_rules! secret_braid {
+    ($(#[$meta:meta])* $owned:ident, $borrowed:ident) => {
+        $(#[$meta])*
+        #[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+        #[repr(transparent)]
+        pub struct $owned(String);
+
+        #[doc = concat!("A reference to a borrowed [`", stringify!($owned), "`]")]
+        #[aliri_braid::braid_ref(display = "omit", debug = "omit", serde)]
+        pub struct $borrowed(str);
+
+        impl $owned {
+            #[doc = concat!("Constructs a new ", stringify!($owned))]
+            #[inline]
+            pub const fn new(raw: String) -> Self { Self(raw) }
+
+            #[doc = concat!("Constructs a new ", stringify!($owned), " from a static reference")]
+            #[inline]
+            pub fn from_static(raw: &'static str) -> Self { Self(raw.to_owned()) }
+
+            #[doc = concat!("Converts this `", stringify!($owned), "` into a [`Box<", stringify!($borrowed), ">`]")]
+            ///
+            /// This will drop any excess capacity.
+            #[allow(unsafe_code)]
+            #[inline]
+            pub fn into_boxed_ref(self) -> Box<$borrowed> {
+                let box_str = self.take().into_boxed_str();
+                // SAFETY: the borrowed type is `#[repr(transparent)]` around a single `str` field
+                unsafe { Box::from_raw(Box::into_raw(box_str) as *mut $borrowed) }
+            }
+
+            /// Unwraps the underlying [`String`] value
+            #[inline]
+            pub fn take(mut self) -> String { std::mem::take(&mut self.0) }
+        }
+
+        impl std::ops::Deref for $owned {
+            type Target = $borrowed;
+
+            #[inline]
+            fn deref(&self) -> &$borrowed { $borrowed::from_str(&self.0) }
+        }
+
+        impl std::borrow::Borrow<$borrowed> for $owned {
+            #[inline]
+            fn borrow(&self) -> &$borrowed { self }
+        }
+
+        impl std::borrow::Borrow<str> for $owned {
+            #[inline]
+            fn borrow(&self) -> &str { self.as_str() }
+        }
+
+        impl AsRef<$borrowed> for $owned {
+            #[inline]
+            fn as_ref(&self) -> &$borrowed { self }
+        }
+
+        impl AsRef<str> for $owned {
+            #[inline]
+            fn as_ref(&self) -> &str { self.as_str() }
+        }
+
+        impl ToOwned for $borrowed {
+            type Owned = $owned;
+
+            #[inline]
+            fn to_owned(&self) -> $owned { $owned(self.as_str().to_owned()) }
+        }
+
+        impl From<String> for $owned {
+            #[inline]
+            fn from(s: String) -> Self { Self::new(s) }
+        }
+
+        impl From<&'_ str> for $owned {
+            #[inline]
+            fn from(s: &str) -> Self { Self::new(s.to_owned()) }
+        }
+
+        impl From<Box<str>> for $owned {
+            #[inline]
+            fn from(s: Box<str>) -> Self { Self::new(s.into()) }
+        }
+
+        impl From<&'_ $borrowed> for $owned {
+            #[inline]
+            fn from(s: &$borrowed) -> Self { s.to_owned() }
+        }
+
+        impl From<$owned> for String {
+            #[inline]
+            fn from(s: $owned) -> Self { s.take() }
+        }
+
+        impl From<$owned> for Box<$borrowed> {
+            #[inline]
+            fn from(s: $owned) -> Self { s.into_boxed_ref() }
+        }
+
+        impl<'a> From<std::borrow::Cow<'a, $borrowed>> for $owned {
+            #[inline]
+            fn from(s: std::borrow::Cow<'a, $borrowed>) -> Self { s.into_owned() }
+        }
+
+        impl<'a> From<$owned> for std::borrow::Cow<'a, $borrowed> {
+            #[inline]
+            fn from(s: $owned) -> Self { std::borrow::Cow::Owned(s) }
+        }
+
+        impl<'a> From<&'a $borrowed> for std::borrow::Cow<'a, $borrowed> {
+            #[inline]
+            fn from(s: &'a $borrowed) -> Self { std::borrow::Cow::Borrowed(s) }
+        }
+
+        impl std::str::FromStr for $owned {
+            type Err = std::convert::Infallible;
+
+            #[inline]
+            fn from_str(s: &str) -> Result<Self, Self::Err> { Ok(Self::from(s)) }
+        }
+
+        impl PartialEq<$borrowed> for $owned {
+            #[inline]
+            fn eq(&self, other: &$borrowed) -> bool { self.as_str() == other.as_str() }
+        }
+
+        impl PartialEq<$owned> for $borrowed {
+            #[inline]
+            fn eq(&self, other: &$owned) -> bool { self.as_str() == other.as_str() }
+        }
+
+        impl PartialEq<&'_ $borrowed> for $owned {
+            #[inline]
+            fn eq(&self, other: &&$borrowed) -> bool { self.as_str() == other.as_str() }
+        }
+
+        impl PartialEq<$owned> for &'_ $borrowed {
+            #[inline]
+            fn eq(&self, other: &$owned) -> bool { self.as_str() == other.as_str() }
+        }
+
+        impl fmt::Debug for $owned {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { fmt::Debug::fmt(&**self, f) }
+        }
+
+        impl fmt::Display for $owned {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(&**self, f)
+            }
+        }
+
+        impl serde::Serialize for $owned {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $owned {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                String::deserialize(deserializer).map(Self::new)
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for Box<$borrowed> {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                $owned::deserialize(deserializer).map($owned::into_boxed_ref)
+            }
+        }
+
+        /// Clones are separate allocations, and are cleared separately when they are dropped.
+        #[cfg(feature = "zeroize")]
+        impl zeroize::Zeroize for $owned {
+            fn zeroize(&mut self) { self.0.zeroize() }
+        }
+
+        #[cfg(feature = "zeroize")]
+        impl Drop for $owned {
+            fn drop(&mut self) { zeroize::Zeroize::zeroize(&mut self.0) }
+        }
+
+        #[cfg(feature = "zeroize")]
+        impl zeroize::ZeroizeOnDrop for $owned {}
+    };
+}
+
+secret_braid!(
+    /// A Client Secret
+    ClientSecret,
+    ClientSecretRef
+);
 
 impl fmt::Debug for ClientSecretRef {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -24,9 +216,11 @@ impl fmt::Display for ClientSecretRef {
     }
 }
 
-/// An Access Token
-#[aliri_braid::braid(display = "owned", debug = "owned", serde)]
-pub struct AccessToken;
+secret_braid!(
+    /// An Access Token
+    AccessToken,
+    AccessTokenRef
+);
 
 impl fmt::Debug for AccessTokenRef {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -39,9 +233,11 @@ impl fmt::Display for AccessTokenRef {
     }
 }
 
-/// A Refresh Token
-#[aliri_braid::braid(display = "owned", debug = "owned", serde)]
-pub struct RefreshToken;
+secret_braid!(
+    /// A Refresh Token
+    RefreshToken,
+    RefreshTokenRef
+);
 
 impl fmt::Debug for RefreshTokenRef {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -54,9 +250,11 @@ impl fmt::Display for RefreshTokenRef {
     }
 }
 
-/// A Csrf Token
-#[aliri_braid::braid(display = "owned", debug = "owned", serde)]
-pub struct CsrfToken;
+secret_braid!(
+    /// A Csrf Token
+    CsrfToken,
+    CsrfTokenRef
+);
 
 impl fmt::Debug for CsrfTokenRef {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -81,6 +279,59 @@ impl CsrfToken {
     }
 }
 
+/// An authorization code, received when the user authorizes the app in the [OAuth authorization code flow](https://dev.twitch.tv/docs/authentication/getting-tokens-oauth/#oauth-authorization-code-flow)
+#[aliri_braid::braid(display = "owned", debug = "owned", serde)]
+pub struct AuthorizationCode;
+
+impl fmt::Debug for AuthorizationCodeRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[redacted authorization code]")
+    }
+}
+impl fmt::Display for AuthorizationCodeRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[redacted authorization code]")
+    }
+}
+
+impl AuthorizationCode {
+    /// Parse a code, for example one pasted by the user, trimming surrounding whitespace.
+    ///
+    /// Codes that are empty, or contain whitespace or characters that suggest a whole url was pasted, are rejected.
+    ///
+    /// ```rust
+    /// use twitch_oauth2::AuthorizationCode;
+    /// let code = AuthorizationCode::parse(" gulfwdmys5lsm6qyz4xiz9q32l10 \n")?;
+    /// assert_eq!(code.secret(), "gulfwdmys5lsm6qyz4xiz9q32l10");
+    /// assert!(AuthorizationCode::parse("code=abc&state=def").is_err());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn parse(
+        code: &str,
+    ) -> Result<AuthorizationCode, crate::tokens::errors::InvalidAuthorizationCode> {
+        use crate::tokens::errors::InvalidAuthorizationCode;
+
+        let code = code.trim();
+        if code.is_empty() {
+            return Err(InvalidAuthorizationCode::Empty);
+        }
+        if let Some(c) = code
+            .chars()
+            .find(|c| !c.is_ascii_graphic() || matches!(c, '&' | '=' | '?' | '#' | '/'))
+        {
+            return Err(InvalidAuthorizationCode::InvalidCharacter(c));
+        }
+        Ok(AuthorizationCode::new(code.to_owned()))
+    }
+}
+
+impl AuthorizationCodeRef {
+    /// Get the secret from this string.
+    ///
+    /// This function is the same as [`AuthorizationCode::as_str`](AuthorizationCodeRef::as_str), but has another name for searchability, prefer to use this function.
+    pub fn secret(&self) -> &str { self.as_str() }
+}
+
 impl ClientSecretRef {
     /// Get the secret from this string.
     ///
@@ -93,6 +344,29 @@ impl AccessTokenRef {
     ///
     /// This function is the same as [`AccessToken::as_str`](AccessTokenRef::as_str), but has another name for searchability, prefer to use this function.
     pub fn secret(&self) -> &str { self.as_str() }
+
+    /// Get a short, stable identifier for this token, for example to correlate logs without exposing the token.
+    ///
+    /// This is the first 8 bytes of the SHA-256 hash of the token, in hex.
+    ///
+    /// ```rust
+    /// use twitch_oauth2::AccessToken;
+    /// let token = AccessToken::from("my_access_token");
+    /// assert_eq!(token.fingerprint().len(), 16);
+    /// ```
+    #[cfg(feature = "fingerprint")]
+    pub fn fingerprint(&self) -> String {
+        use sha2::Digest as _;
+        use std::fmt::Write as _;
+
+        let hash = sha2::Sha256::digest(self.secret().as_bytes());
+        hash[..8]
+            .iter()
+            .fold(String::with_capacity(16), |mut s, b| {
+                let _ = write!(s, "{b:02x}");
+                s
+            })
+    }
 }
 impl RefreshTokenRef {
     /// Get the secret from this string.
@@ -105,4 +379,351 @@ impl CsrfTokenRef {
     ///
     /// This function is the same as [`CsrfToken::as_str`](CsrfTokenRef::as_str), but has another name for searchability, prefer to use this function.
     pub fn secret(&self) -> &str { self.as_str() }
+
+    /// Get the length of the encoded token, in characters
+    pub fn len(&self) -> usize { self.as_str().chars().count() }
+
+    /// Returns whether or not the token is empty
+    pub fn is_empty(&self) -> bool { self.as_str().is_empty() }
+
+    /// Estimate the entropy of the token in bits, assuming it was generated randomly.
+    ///
+    /// The estimate is based on the length and the alphabet of the token:
+    /// hex tokens have 4 bits per character, base64 tokens (like the ones from [`CsrfToken::new_random`]) have 6 bits per character, rounded down to whole bytes,
+    /// and other tokens are assumed to use all printable ASCII characters.
+    ///
+    /// ```rust
+    /// use twitch_oauth2::CsrfToken;
+    /// assert_eq!(CsrfToken::new_random().entropy_bits(), 128);
+    /// assert_eq!(CsrfToken::new_random_len(32).entropy_bits(), 256);
+    /// ```
+    pub fn entropy_bits(&self) -> usize {
+        let token = self.as_str();
+        if token.is_empty() {
+            0
+        } else if token.chars().all(|c| c.is_ascii_hexdigit()) {
+            token.len() * 4
+        } else if token
+            .trim_end_matches('=')
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '-' | '_'))
+        {
+            token.trim_end_matches('=').len() * 6 / 8 * 8
+        } else {
+            // log2(95) = 6.57 bits per printable ASCII character
+            self.len() * 657 / 100
+        }
+    }
+}
+
+/// Client id and client secret, for example loaded from a secrets file with [`ClientCredentials::from_file`]
+///
+/// ```json
+/// { "client_id": "myclientid", "client_secret": "myclientsecret" }
+/// ```
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[non_exhaustive]
+pub struct ClientCredentials {
+    /// The client id
+    pub client_id: ClientId,
+    /// The client secret
+    pub client_secret: ClientSecret,
+}
+
+impl ClientCredentials {
+    /// Create new credentials
+    pub fn new(client_id: impl Into<ClientId>, client_secret: impl Into<ClientSecret>) -> Self {
+        Self {
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+        }
+    }
+
+    /// Load credentials from a JSON file with `client_id` and `client_secret` fields
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use twitch_oauth2::{tokens::TwitchAuth, types::ClientCredentials};
+    /// let credentials = ClientCredentials::from_file("secrets/twitch.json")?;
+    /// let auth = TwitchAuth::from(credentials);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, ClientCredentialsError> {
+        let contents = std::fs::read(path)?;
+        Ok(serde_json::from_slice(&contents)?)
+    }
+
+    /// Parse credentials from JSON with `client_id` and `client_secret` fields
+    pub fn from_json(json: &str) -> Result<Self, ClientCredentialsError> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// Errors for [`ClientCredentials::from_file`]
+#[derive(thiserror::Error, Debug, displaydoc::Display)]
+#[non_exhaustive]
+pub enum ClientCredentialsError {
+    /// could not read credentials file
+    Io(#[from] std::io::Error),
+    /// could not parse credentials
+    Json(#[from] serde_json::Error),
+}
+
+/// Configuration for the [OAuth authorization code flow](crate::tokens::UserTokenBuilder), for example loaded from a TOML config file.
+///
+/// Create a builder from it with [`UserTokenBuilder::from_oauth_config`](crate::tokens::UserTokenBuilder::from_oauth_config).
+///
+/// ```toml
+/// client_id = "myclientid"
+/// client_secret = "myclientsecret"
+/// redirect_url = "http://localhost/twitch/register"
+/// # Optional
+/// scopes = ["chat:read", "chat:edit"]
+/// force_verify = false
+/// ```
+#[derive(Clone, Debug, serde::Deserialize)]
+#[non_exhaustive]
+pub struct OAuthConfig {
+    /// The client id
+    pub client_id: ClientId,
+    /// The client secret
+    pub client_secret: ClientSecret,
+    /// The redirect url, as registered in the developer console
+    pub redirect_url: String,
+    /// The scopes to request, parsed with [`Scope::from_str`](std::str::FromStr::from_str)
+    #[serde(default, deserialize_with = "config_scopes")]
+    pub scopes: Vec<crate::Scope>,
+    /// Force the user to reauthorize, see [`UserTokenBuilder::force_verify`](crate::tokens::UserTokenBuilder::force_verify)
+    #[serde(default)]
+    pub force_verify: bool,
+}
+
+fn config_scopes<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<crate::Scope>, D::Error> {
+    let scopes: Vec<String> = serde::Deserialize::deserialize(deserializer)?;
+    Ok(scopes
+        .iter()
+        .map(|s| s.parse().unwrap_or_else(|e| match e {}))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn csrf_entropy() {
+        use super::CsrfToken;
+
+        let token = CsrfToken::new_random();
+        assert_eq!(token.len(), 24);
+        assert_eq!(token.entropy_bits(), 128);
+        let token = CsrfToken::new_random_len(20);
+        assert_eq!(token.len(), 28);
+        assert_eq!(token.entropy_bits(), 160);
+
+        assert_eq!(
+            CsrfToken::from("c3ab8aa609ea11e793ae92361f002671").entropy_bits(),
+            128
+        );
+        assert_eq!(
+            CsrfToken::from("YWJjZGVmZ2hpamtsbW5vcA").entropy_bits(),
+            128
+        );
+        assert_eq!(CsrfToken::from("a!b@c#d$").entropy_bits(), 52);
+        assert_eq!(CsrfToken::from("").entropy_bits(), 0);
+        assert!(CsrfToken::from("").is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "fingerprint")]
+    fn fingerprint() {
+        use super::AccessToken;
+
+        let token = AccessToken::from("my_access_token");
+        assert_eq!(token.fingerprint(), token.fingerprint());
+        assert_eq!(
+            token.fingerprint(),
+            AccessToken::from("my_access_token").fingerprint()
+        );
+        assert_ne!(
+            token.fingerprint(),
+            AccessToken::from("my_access_tokem").fingerprint()
+        );
+        assert_eq!(token.fingerprint().len(), 16);
+        assert!(!token.fingerprint().contains("my_access_token"));
+        // sha256("my_access_token") starts with these bytes
+        assert_eq!(token.fingerprint(), "9f5285665ba18003");
+    }
+
+    #[test]
+    fn authorization_code_parse() {
+        use super::AuthorizationCode;
+        use crate::tokens::errors::InvalidAuthorizationCode;
+
+        for (input, expected) in [
+            (
+                "gulfwdmys5lsm6qyz4xiz9q32l10",
+                "gulfwdmys5lsm6qyz4xiz9q32l10",
+            ),
+            (
+                "  gulfwdmys5lsm6qyz4xiz9q32l10\r\n",
+                "gulfwdmys5lsm6qyz4xiz9q32l10",
+            ),
+            ("\tabc-_.~123", "abc-_.~123"),
+        ] {
+            assert_eq!(AuthorizationCode::parse(input).unwrap().secret(), expected);
+        }
+
+        for (input, err) in [
+            ("", InvalidAuthorizationCode::Empty),
+            (" \n ", InvalidAuthorizationCode::Empty),
+            ("abc def", InvalidAuthorizationCode::InvalidCharacter(' ')),
+            ("abc\tdef", InvalidAuthorizationCode::InvalidCharacter('\t')),
+            ("code=abc", InvalidAuthorizationCode::InvalidCharacter('=')),
+            (
+                "abc&state=def",
+                InvalidAuthorizationCode::InvalidCharacter('&'),
+            ),
+            (
+                "http://localhost/?code=abc",
+                InvalidAuthorizationCode::InvalidCharacter('/'),
+            ),
+            ("abcé", InvalidAuthorizationCode::InvalidCharacter('é')),
+        ] {
+            assert_eq!(
+                AuthorizationCode::parse(input).unwrap_err(),
+                err,
+                "{input:?}"
+            );
+        }
+        assert_eq!(
+            format!("{:?}", AuthorizationCode::parse("abc").unwrap()),
+            "[redacted authorization code]"
+        );
+    }
+
+    #[test]
+    fn oauth_config_from_toml() {
+        use super::OAuthConfig;
+        use crate::{tokens::UserTokenBuilder, Scope};
+
+        let config: OAuthConfig = toml::from_str(
+            r#"
+            client_id = "clientid"
+            client_secret = "clientsecret"
+            redirect_url = "http://localhost/twitch/register"
+            scopes = ["chat:read", " Chat:Edit ", "custom:scope"]
+            force_verify = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.scopes,
+            [
+                Scope::ChatRead,
+                Scope::ChatEdit,
+                Scope::Other("custom:scope".into())
+            ]
+        );
+
+        let mut builder = UserTokenBuilder::from_oauth_config(config).unwrap();
+        assert_eq!(
+            builder.redirect_url_as_registered(),
+            "http://localhost/twitch/register"
+        );
+        let url = builder.generate_url();
+        let params: std::collections::HashMap<_, _> = url.query_pairs().collect();
+        assert_eq!(params["client_id"], "clientid");
+        assert_eq!(params["scope"], "chat:read chat:edit custom:scope");
+        assert_eq!(params["force_verify"], "true");
+
+        let config: OAuthConfig = toml::from_str(
+            r#"
+            client_id = "clientid"
+            client_secret = "clientsecret"
+            redirect_url = "http://localhost"
+            "#,
+        )
+        .unwrap();
+        assert!(config.scopes.is_empty());
+        assert!(matches!(
+            UserTokenBuilder::from_oauth_config(config),
+            Err(crate::tokens::errors::RedirectUrlError::TrailingSlash { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "zeroize")]
+    fn zeroize() {
+        use super::{AccessToken, ClientSecret, CsrfToken, RefreshToken};
+        use zeroize::{Zeroize, ZeroizeOnDrop};
+
+        fn zeroize_on_drop<T: ZeroizeOnDrop>() {}
+        zeroize_on_drop::<ClientSecret>();
+        zeroize_on_drop::<AccessToken>();
+        zeroize_on_drop::<RefreshToken>();
+        zeroize_on_drop::<CsrfToken>();
+
+        let mut secret = ClientSecret::new("clientsecret".to_owned());
+        let clone = secret.clone();
+        secret.zeroize();
+        assert_eq!(secret.secret(), "");
+        // clones are not affected
+        assert_eq!(clone.secret(), "clientsecret");
+
+        let mut token = RefreshToken::new("refreshtoken".to_owned());
+        token.zeroize();
+        assert_eq!(token.secret(), "");
+
+        let mut token = AccessToken::new("accesstoken".to_owned());
+        let clone = token.clone();
+        token.zeroize();
+        assert_eq!(token.secret(), "");
+        assert_eq!(clone.secret(), "accesstoken");
+
+        // `take` leaves an empty token behind to be dropped
+        let token = RefreshToken::new("refreshtoken".to_owned());
+        assert_eq!(token.take(), "refreshtoken");
+    }
+
+    #[test]
+    fn client_credentials_from_file() {
+        use super::{ClientCredentials, ClientCredentialsError};
+
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!(
+            "twitch_oauth2_credentials_{}.json",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            r#"{ "client_id": "clientid", "client_secret": "secret" }"#,
+        )
+        .unwrap();
+        let credentials = ClientCredentials::from_file(&path).unwrap();
+        assert_eq!(credentials.client_id.as_str(), "clientid");
+        assert_eq!(credentials.client_secret.secret(), "secret");
+
+        std::fs::write(&path, r#"{ "client_id": "clientid" }"#).unwrap();
+        let error = ClientCredentials::from_file(&path).unwrap_err();
+        assert!(matches!(error, ClientCredentialsError::Json(_)));
+        assert!(error.to_string().contains("could not parse credentials"));
+        assert!(std::error::Error::source(&error)
+            .unwrap()
+            .to_string()
+            .contains("missing field `client_secret`"));
+
+        std::fs::write(&path, r#"{ "client_id": "clientid", "#).unwrap();
+        assert!(matches!(
+            ClientCredentials::from_file(&path),
+            Err(ClientCredentialsError::Json(_))
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            ClientCredentials::from_file(&path),
+            Err(ClientCredentialsError::Io(_))
+        ));
+    }
 }
@@ -0,0 +1,57 @@
+//! Checks that hot paths don't allocate
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use twitch_oauth2::{Scope, TwitchToken, UserToken};
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|a| a.set(a.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) { System.dealloc(ptr, layout) }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Count the allocations made by `f` on this thread
+fn allocations<R>(f: impl FnOnce() -> R) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    std::hint::black_box(f());
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn has_scope() {
+    let token = UserToken::from_existing_unchecked(
+        "accesstoken",
+        None,
+        "clientid",
+        None,
+        "login".into(),
+        "1234".into(),
+        Some(vec![
+            Scope::ChatRead,
+            Scope::ChatEdit,
+            Scope::Other("custom:scope".into()),
+        ]),
+        None,
+    );
+    let custom = Scope::Other("custom:scope".into());
+
+    assert_eq!(allocations(|| token.has_scope(&Scope::ChatEdit)), 0);
+    assert_eq!(allocations(|| token.has_scope(&Scope::UserReadEmail)), 0);
+    assert_eq!(allocations(|| token.has_scope(&custom)), 0);
+    assert!(token.has_scope(&Scope::ChatEdit));
+    assert!(!token.has_scope(&Scope::UserReadEmail));
+    assert!(token.has_scope(&custom));
+}